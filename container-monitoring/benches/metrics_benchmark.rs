@@ -25,8 +25,14 @@ fn generate_mock_container_data(count: usize) -> Vec<ContainerInfo> {
                 network_tx_bytes: 500 * i as u64,
                 block_read_bytes: 2000 * i as u64,
                 block_write_bytes: 1000 * i as u64,
+                block_devices: Vec::new(),
                 pids: i as u64,
             }),
+            stale: false,
+            details: None,
+            processes: Vec::new(),
+            labels: Default::default(),
+            pod: None,
         };
         
         containers.push(container);
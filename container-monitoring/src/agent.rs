@@ -0,0 +1,277 @@
+use anyhow::{Context, Result};
+use opentelemetry::metrics::{Meter, ObservableCounter, ObservableGauge, Unit};
+use opentelemetry::KeyValue;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio::runtime::Handle;
+use tracing::{debug, instrument};
+
+/// /proc/<pid>/statのCPU時間の単位（USER_HZ。Linuxでは常に100）
+const USER_HZ: f64 = 100.0;
+
+/// エージェント自身のプロセスの統計情報（/proc/self）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessStats {
+    pub cpu_user_seconds: f64,
+    pub cpu_system_seconds: f64,
+    pub resident_memory_bytes: u64,
+    pub virtual_memory_bytes: u64,
+    pub threads: u64,
+    pub open_fds: u64,
+    /// ファイルディスクリプタ数のソフトリミット（unlimitedの場合はNone）
+    pub max_fds: Option<u64>,
+}
+
+impl ProcessStats {
+    /// procfsのプロセスディレクトリ（通常は/proc/self）から読み取り
+    pub fn read(process_dir: &Path) -> Result<Self> {
+        let (cpu_user_seconds, cpu_system_seconds) = parse_process_stat(&read_file(process_dir, "stat")?)?;
+        let status = read_file(process_dir, "status")?;
+        let status_value = |key: &str| parse_status_value(&status, key).unwrap_or(0);
+
+        let fd_dir = process_dir.join("fd");
+        let open_fds = std::fs::read_dir(&fd_dir)
+            .with_context(|| format!("Failed to read {}", fd_dir.display()))?
+            .count() as u64;
+
+        Ok(Self {
+            cpu_user_seconds,
+            cpu_system_seconds,
+            resident_memory_bytes: status_value("VmRSS"),
+            virtual_memory_bytes: status_value("VmSize"),
+            threads: status_value("Threads"),
+            open_fds,
+            max_fds: read_file(process_dir, "limits")
+                .ok()
+                .and_then(|limits| parse_max_open_files(&limits)),
+        })
+    }
+}
+
+/// tokioランタイムの統計情報
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RuntimeStats {
+    pub workers: u64,
+    pub alive_tasks: u64,
+    pub global_queue_depth: u64,
+    /// 全ワーカーがタスクの実行に費やした時間の合計
+    pub busy_seconds: f64,
+}
+
+impl RuntimeStats {
+    /// ランタイムのハンドルから読み取り
+    pub fn read(handle: &Handle) -> Self {
+        let metrics = handle.metrics();
+        let busy_seconds = (0..metrics.num_workers())
+            .map(|worker| metrics.worker_total_busy_duration(worker).as_secs_f64())
+            .sum();
+
+        Self {
+            workers: metrics.num_workers() as u64,
+            alive_tasks: metrics.num_alive_tasks() as u64,
+            global_queue_depth: metrics.global_queue_depth() as u64,
+            busy_seconds,
+        }
+    }
+}
+
+/// エージェント自身の統計情報
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AgentStats {
+    pub process: ProcessStats,
+    pub runtime: Option<RuntimeStats>,
+}
+
+// プロセスディレクトリ内のファイルを読み取り
+fn read_file(process_dir: &Path, name: &str) -> Result<String> {
+    let path = process_dir.join(name);
+    std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))
+}
+
+// /proc/<pid>/statからユーザー・システムのCPU時間（秒）を解析
+// コマンド名に空白や括弧が含まれ得るため、最後の')'以降をフィールドとして扱う
+fn parse_process_stat(content: &str) -> Result<(f64, f64)> {
+    let (_, fields) = content.rsplit_once(')').context("Invalid process stat")?;
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+    // stateが3番目のフィールドなので、utime（14番目）とstime（15番目）は11と12
+    let ticks = |index: usize| -> Result<f64> {
+        let value: u64 = fields
+            .get(index)
+            .context("Missing CPU time field in process stat")?
+            .parse()
+            .context("Invalid CPU time in process stat")?;
+        Ok(value as f64 / USER_HZ)
+    };
+
+    Ok((ticks(11)?, ticks(12)?))
+}
+
+// /proc/<pid>/statusの値を解析（kB単位の値はバイトに変換）
+fn parse_status_value(content: &str, key: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let (name, rest) = line.split_once(':')?;
+        if name != key {
+            return None;
+        }
+        let mut fields = rest.split_whitespace();
+        let value: u64 = fields.next()?.parse().ok()?;
+        Some(if fields.next() == Some("kB") { value * 1024 } else { value })
+    })
+}
+
+// /proc/<pid>/limitsの"Max open files"のソフトリミットを解析
+fn parse_max_open_files(content: &str) -> Option<u64> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("Max open files"))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|soft| soft.parse().ok())
+}
+
+// ゲージ型・カウンター型メトリクスのコールバックが参照する最新の統計情報
+type AgentSnapshot = Arc<RwLock<Option<AgentStats>>>;
+
+// ゲージ名、説明、単位と、統計情報から値を取り出す関数（値がない場合は報告しない）
+type AgentGauge = (&'static str, &'static str, Option<&'static str>, fn(&AgentStats) -> Option<f64>);
+
+/// エージェントコレクター - エージェント自身のCPU、メモリ、ファイルディスクリプタ、スレッド、tokioランタイムを収集
+///
+/// 小規模なホストでエージェントがリソース予算内に収まっていることを確認するためのメトリクスです。
+pub struct AgentCollector {
+    process_dir: PathBuf,
+    runtime: Option<Handle>,
+
+    // ゲージ型メトリクスが報告する最新の統計情報
+    snapshot: AgentSnapshot,
+    _gauges: Vec<ObservableGauge<f64>>,
+    _counters: Vec<ObservableCounter<f64>>,
+}
+
+impl AgentCollector {
+    /// 新しいエージェントコレクターを作成（tokioランタイム内で呼ばれた場合はランタイムの統計も収集）
+    pub fn new() -> Self {
+        Self::with_process_dir("/proc/self")
+    }
+
+    /// 読み取るプロセスディレクトリを指定してエージェントコレクターを作成
+    pub fn with_process_dir(process_dir: impl Into<PathBuf>) -> Self {
+        Self::build(process_dir.into(), &opentelemetry::global::meter("container-monitoring"))
+    }
+
+    /// メトリクスを記録するメーターを指定してエージェントコレクターを作成
+    pub fn new_with_meter(meter: &Meter) -> Self {
+        Self::build(PathBuf::from("/proc/self"), meter)
+    }
+
+    fn build(process_dir: PathBuf, meter: &Meter) -> Self {
+        let snapshot = Arc::new(RwLock::new(None));
+
+        Self {
+            process_dir,
+            runtime: Handle::try_current().ok(),
+            _gauges: Self::init_gauges(meter, &snapshot),
+            _counters: Self::init_counters(meter, &snapshot),
+            snapshot,
+        }
+    }
+
+    // 最新の統計情報を報告するゲージを初期化
+    fn init_gauges(meter: &Meter, snapshot: &AgentSnapshot) -> Vec<ObservableGauge<f64>> {
+        let gauges: [AgentGauge; 8] = [
+            ("agent_resident_memory_bytes", "Resident memory of the agent process", Some("By"), |s| Some(s.process.resident_memory_bytes as f64)),
+            ("agent_virtual_memory_bytes", "Virtual memory of the agent process", Some("By"), |s| Some(s.process.virtual_memory_bytes as f64)),
+            ("agent_threads", "OS threads of the agent process", None, |s| Some(s.process.threads as f64)),
+            ("agent_open_fds", "Open file descriptors of the agent process", None, |s| Some(s.process.open_fds as f64)),
+            ("agent_max_fds", "Soft limit of open file descriptors of the agent process", None, |s| s.process.max_fds.map(|max| max as f64)),
+            ("agent_tokio_workers", "Worker threads of the agent's tokio runtime", None, |s| s.runtime.as_ref().map(|r| r.workers as f64)),
+            ("agent_tokio_alive_tasks", "Tasks alive in the agent's tokio runtime", None, |s| s.runtime.as_ref().map(|r| r.alive_tasks as f64)),
+            ("agent_tokio_global_queue_depth", "Tasks waiting in the tokio runtime's global queue", None, |s| s.runtime.as_ref().map(|r| r.global_queue_depth as f64)),
+        ];
+
+        gauges
+            .into_iter()
+            .map(|(name, description, unit, value)| {
+                let snapshot = snapshot.clone();
+                let mut builder = meter
+                    .f64_observable_gauge(name)
+                    .with_description(description);
+                if let Some(unit) = unit {
+                    builder = builder.with_unit(Unit::new(unit));
+                }
+
+                builder
+                    .with_callback(move |instrument| {
+                        if let Ok(snapshot) = snapshot.read() {
+                            if let Some(value) = snapshot.as_ref().and_then(value) {
+                                instrument.observe(value, &[]);
+                            }
+                        }
+                    })
+                    .init()
+            })
+            .collect()
+    }
+
+    // 累計値をそのまま報告するカウンターを初期化
+    fn init_counters(meter: &Meter, snapshot: &AgentSnapshot) -> Vec<ObservableCounter<f64>> {
+        let cpu_snapshot = snapshot.clone();
+        let cpu_seconds = meter
+            .f64_observable_counter("agent_cpu_seconds_total")
+            .with_description("CPU time consumed by the agent process")
+            .with_unit(Unit::new("s"))
+            .with_callback(move |instrument| {
+                if let Ok(snapshot) = cpu_snapshot.read() {
+                    if let Some(stats) = snapshot.as_ref() {
+                        instrument.observe(stats.process.cpu_user_seconds, &[KeyValue::new("mode", "user")]);
+                        instrument.observe(stats.process.cpu_system_seconds, &[KeyValue::new("mode", "system")]);
+                    }
+                }
+            })
+            .init();
+
+        let busy_snapshot = snapshot.clone();
+        let busy_seconds = meter
+            .f64_observable_counter("agent_tokio_busy_seconds_total")
+            .with_description("Time the agent's tokio workers spent running tasks")
+            .with_unit(Unit::new("s"))
+            .with_callback(move |instrument| {
+                if let Ok(snapshot) = busy_snapshot.read() {
+                    if let Some(runtime) = snapshot.as_ref().and_then(|stats| stats.runtime.as_ref()) {
+                        instrument.observe(runtime.busy_seconds, &[]);
+                    }
+                }
+            })
+            .init();
+
+        vec![cpu_seconds, busy_seconds]
+    }
+
+    /// プロセスとランタイムの統計情報を読み取り、メトリクスを更新
+    #[instrument(skip(self), fields(process_dir = %self.process_dir.display()), level = "debug")]
+    pub fn collect(&mut self) -> Result<AgentStats> {
+        let stats = AgentStats {
+            process: ProcessStats::read(&self.process_dir)?,
+            runtime: self.runtime.as_ref().map(RuntimeStats::read),
+        };
+
+        debug!(
+            resident_memory_mb = stats.process.resident_memory_bytes / (1024 * 1024),
+            open_fds = stats.process.open_fds,
+            alive_tasks = stats.runtime.as_ref().map(|runtime| runtime.alive_tasks),
+            "Agent stats collected"
+        );
+
+        if let Ok(mut snapshot) = self.snapshot.write() {
+            *snapshot = Some(stats.clone());
+        }
+
+        Ok(stats)
+    }
+}
+
+impl Default for AgentCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::{bail, Result};
+use opentelemetry::KeyValue;
+use std::collections::{HashMap, HashSet};
+use std::ops::AddAssign;
+use tracing::debug;
+
+/// コンテナごとのメトリクスに付与できるラベル
+pub const CONTAINER_LABELS: [&str; 3] = ["container_id", "container_name", "image"];
+
+/// 系列数の上限を超えた値を集約する系列の属性（OpenTelemetry SDKの上限超過時と同じ名前）
+pub const OVERFLOW_LABEL: &str = "otel.metric.overflow";
+
+/// コンテナごとのメトリクスに付与するラベルの選択
+///
+/// 短命なコンテナが多いホストでは`container_id`を外すと、同じ名前のコンテナが1つの系列にまとまります。
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerLabels {
+    container_id: bool,
+    container_name: bool,
+    image: bool,
+}
+
+impl ContainerLabels {
+    /// 設定のラベル名から作成（未知のラベル名はエラー）
+    pub fn new(names: &[String]) -> Result<Self> {
+        if let Some(unknown) = names.iter().find(|name| !CONTAINER_LABELS.contains(&name.as_str())) {
+            bail!(
+                "Unsupported container label '{}' (expected {})",
+                unknown,
+                CONTAINER_LABELS.join(", ")
+            );
+        }
+
+        let selected = |label: &str| names.iter().any(|name| name == label);
+        Ok(Self {
+            container_id: selected("container_id"),
+            container_name: selected("container_name"),
+            image: selected("image"),
+        })
+    }
+
+    /// コンテナのラベルを作成（hostは常に付与）
+    pub fn labels(&self, host: &str, container_id: &str, container_name: &str, image: &str) -> Vec<KeyValue> {
+        let mut labels = vec![KeyValue::new("host", host.to_string())];
+        if self.container_id {
+            labels.push(KeyValue::new("container_id", container_id.to_string()));
+        }
+        if self.container_name {
+            labels.push(KeyValue::new("container_name", container_name.to_string()));
+        }
+        if self.image {
+            labels.push(KeyValue::new("image", image.to_string()));
+        }
+        labels
+    }
+}
+
+impl Default for ContainerLabels {
+    fn default() -> Self {
+        Self {
+            container_id: true,
+            container_name: true,
+            image: true,
+        }
+    }
+}
+
+/// 系列数の上限を超えた値を集約する系列のラベル
+pub fn overflow_labels(host: &str) -> Vec<KeyValue> {
+    vec![
+        KeyValue::new("host", host.to_string()),
+        KeyValue::new(OVERFLOW_LABEL, "true"),
+    ]
+}
+
+/// ラベルの組を一意に表すキー
+pub fn series_key(labels: &[KeyValue]) -> String {
+    labels
+        .iter()
+        .map(|label| format!("{}={}", label.key, label.value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// メトリクスごとの系列数を制限するリミッター
+///
+/// 上限（0は無制限）に達した後の新しい系列は受け入れず、呼び出し側でoverflow系列に集約します。
+/// 受け入れ済みの系列は新しい系列より優先され、報告されている限り入れ替わりません。
+#[derive(Debug, Clone, Default)]
+pub struct SeriesLimiter {
+    max_series: usize,
+    admitted: HashMap<String, HashSet<String>>,
+}
+
+impl SeriesLimiter {
+    /// メトリクスごとの系列数の上限を指定してリミッターを作成
+    pub fn new(max_series: usize) -> Self {
+        Self {
+            max_series,
+            admitted: HashMap::new(),
+        }
+    }
+
+    /// 累計値の系列を受け入れるか判定
+    ///
+    /// SDKは累計値の系列を削除できないため、一度受け入れた系列の枠は解放しません。
+    pub fn admit(&mut self, metric: &str, labels: &[KeyValue]) -> bool {
+        let max_series = self.max_series;
+        let admitted = self.admitted.entry(metric.to_string()).or_default();
+        let key = series_key(labels);
+        if admitted.contains(&key) {
+            return true;
+        }
+        if max_series > 0 && admitted.len() >= max_series {
+            return false;
+        }
+        admitted.insert(key);
+        true
+    }
+
+    /// 今回報告する系列のキーから受け入れる系列を選択
+    ///
+    /// 前回も受け入れた系列を優先し、残りの枠に新しい系列を先頭から受け入れます。
+    /// 今回報告されなかった系列の枠は解放されます。
+    pub fn select(&mut self, metric: &str, keys: &[String]) -> Vec<bool> {
+        let previous = self.admitted.remove(metric).unwrap_or_default();
+        let mut admitted: HashSet<String> = keys.iter()
+            .filter(|key| previous.contains(*key))
+            .cloned()
+            .collect();
+
+        let selected = keys.iter()
+            .map(|key| {
+                if admitted.contains(key) {
+                    return true;
+                }
+                if self.max_series > 0 && admitted.len() >= self.max_series {
+                    return false;
+                }
+                admitted.insert(key.clone());
+                true
+            })
+            .collect();
+
+        self.admitted.insert(metric.to_string(), admitted);
+        selected
+    }
+
+    /// ゲージの系列を制限
+    ///
+    /// 同じラベルの系列（container_idを外した場合など）は合計し、
+    /// 受け入れられなかった系列は`otel.metric.overflow="true"`の系列に合計します。
+    pub fn limit<T: Copy + AddAssign>(&mut self, metric: &str, host: &str, series: Vec<(Vec<KeyValue>, T)>) -> Vec<(Vec<KeyValue>, T)> {
+        let mut keys: Vec<String> = Vec::new();
+        let mut merged: Vec<(Vec<KeyValue>, T)> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for (labels, value) in series {
+            let key = series_key(&labels);
+            match positions.get(&key) {
+                Some(&position) => merged[position].1 += value,
+                None => {
+                    positions.insert(key.clone(), merged.len());
+                    keys.push(key);
+                    merged.push((labels, value));
+                }
+            }
+        }
+
+        let selected = self.select(metric, &keys);
+        let mut limited = Vec::with_capacity(merged.len());
+        let mut overflow: Option<T> = None;
+        let mut overflowed = 0;
+        for ((labels, value), selected) in merged.into_iter().zip(selected) {
+            if selected {
+                limited.push((labels, value));
+            } else {
+                overflowed += 1;
+                match overflow.as_mut() {
+                    Some(total) => *total += value,
+                    None => overflow = Some(value),
+                }
+            }
+        }
+
+        if let Some(total) = overflow {
+            debug!(metric, overflowed, "Series limit reached, aggregating the rest into the overflow series");
+            limited.push((overflow_labels(host), total));
+        }
+        limited
+    }
+}
@@ -1,33 +1,419 @@
 use anyhow::{Context, Result};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
-#[derive(Debug, Deserialize, Clone)]
+/// 設定全体（ライブラリとして組み込む場合は`Config::default()`から組み立てられます）
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct Config {
     pub general: GeneralConfig,
     pub telemetry: TelemetryConfig,
     pub docker: DockerConfig,
     pub metrics: MetricsConfig,
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub server: ServerConfig,
+    #[serde(default)]
+    pub container_logs: ContainerLogsConfig,
+    #[serde(default)]
+    pub state: StateConfig,
+    #[serde(default)]
+    pub remote_write: RemoteWriteConfig,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct GeneralConfig {
     pub interval: u64,
+    
+    /// 収集グループごとの間隔（秒）。未指定のグループは`interval`を使用します
+    #[serde(default)]
+    pub intervals: GroupIntervals,
+    
+    /// 収集サイクルが間隔を超えた場合に間隔を延長し、余裕のあるサイクルが続けば少しずつ元の間隔へ縮める
+    #[serde(default)]
+    pub adaptive: bool,
+    
+    /// 適応モードで延長する間隔の上限（各グループの間隔に対する倍率）
+    #[serde(default = "default_max_interval_factor")]
+    pub max_interval_factor: u32,
+    
+    /// 収集が間隔に間に合わなかった場合の動作（"burst"、"delay"、"skip"）
+    #[serde(default = "default_missed_tick_behavior")]
+    pub missed_tick_behavior: String,
+}
+
+/// 収集グループごとの間隔（秒）
+///
+/// インスペクト結果とディスク使用量は`metrics.inspect_interval`と`metrics.disk_usage_interval`で指定します。
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct GroupIntervals {
+    /// コンテナの統計情報（CPU、メモリ、ネットワーク、ブロックI/O）の収集サイクル
+    #[serde(default)]
+    pub containers: Option<u64>,
+    
+    /// ホスト全体のメトリクス
+    #[serde(default)]
+    pub host: Option<u64>,
+    
+    /// エージェント自身のメトリクス
+    #[serde(default)]
+    pub agent: Option<u64>,
+    
+    /// コンテナログの追跡対象の同期
+    #[serde(default)]
+    pub logs: Option<u64>,
+}
+
+impl Default for GeneralConfig {
+    fn default() -> Self {
+        Self {
+            interval: 15,
+            intervals: GroupIntervals::default(),
+            adaptive: false,
+            max_interval_factor: default_max_interval_factor(),
+            missed_tick_behavior: default_missed_tick_behavior(),
+        }
+    }
+}
+
+fn default_max_interval_factor() -> u32 {
+    4
+}
+
+fn default_missed_tick_behavior() -> String {
+    "skip".to_string()
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct TelemetryConfig {
     pub service_name: String,
+    /// メトリクスとトレースの送信先（"otlp"、"stdout"、"file"、"none"）。
+    /// いずれの場合もメトリクスは`/metrics`で公開されます
     pub otel_exporter: String,
     pub otel_endpoint: String,
     pub prometheus_port: u16,
+    
+    /// OTLPエクスポーターの詳細設定
+    #[serde(default)]
+    pub otlp: OtlpConfig,
+    
+    /// `otel_exporter = "file"`の場合に追記するJSON Linesファイルのパス
+    #[serde(default)]
+    pub file_path: Option<String>,
+    
+    /// トレースのサンプラー（"always_on"、"always_off"、"traceidratio"、"parentbased_always_on"、
+    /// "parentbased_always_off"、"parentbased_traceidratio"）
+    #[serde(default = "default_sampler")]
+    pub sampler: String,
+    
+    /// traceidratio系のサンプラーで記録するトレースの割合（0.0〜1.0）
+    #[serde(default = "default_sampling_ratio")]
+    pub sampling_ratio: f64,
+    
+    /// エージェント自身のリソース属性を検出する検出器（"host"、"os"、"process"、"container"）
+    #[serde(default = "default_resource_detectors")]
+    pub resource_detectors: Vec<String>,
+    
+    /// `otel_exporter`と並行してメトリクスを送信する追加のシンク（DogStatsD、InfluxDBラインプロトコル）
+    #[serde(default)]
+    pub sinks: Vec<SinkConfig>,
+}
+
+impl Default for TelemetryConfig {
+    /// OTLPへの送信は行わない（`otel_exporter = "none"`）
+    fn default() -> Self {
+        Self {
+            service_name: "container-monitoring".to_string(),
+            otel_exporter: "none".to_string(),
+            otel_endpoint: "http://localhost:4317".to_string(),
+            prometheus_port: 8080,
+            otlp: OtlpConfig::default(),
+            file_path: None,
+            sampler: default_sampler(),
+            sampling_ratio: default_sampling_ratio(),
+            resource_detectors: default_resource_detectors(),
+            sinks: Vec::new(),
+        }
+    }
+}
+
+fn default_sampler() -> String {
+    "parentbased_always_on".to_string()
+}
+
+fn default_sampling_ratio() -> f64 {
+    1.0
+}
+
+fn default_resource_detectors() -> Vec<String> {
+    ["host", "os", "process", "container"].iter().map(|name| name.to_string()).collect()
+}
+
+/// メトリクスの追加のシンクの設定
+#[derive(Debug, Deserialize, Clone)]
+pub struct SinkConfig {
+    /// シンクの種類（"dogstatsd"、"influxdb"）
+    #[serde(rename = "type")]
+    pub kind: String,
+    
+    /// DogStatsDの送信先（host:port、UDP）
+    #[serde(default)]
+    pub address: Option<String>,
+    
+    /// InfluxDBの書き込みURL（例: http://localhost:8086/api/v2/write?org=example&bucket=containers&precision=ns）
+    #[serde(default)]
+    pub url: Option<String>,
+    
+    /// InfluxDBのAPIトークン（`Authorization: Token <token>`として送信）
+    #[serde(default)]
+    pub token: Option<String>,
+    
+    /// urlの代わりにラインプロトコルを追記するファイルのパス
+    #[serde(default)]
+    pub path: Option<String>,
+    
+    /// メトリクス名の接頭辞
+    #[serde(default)]
+    pub prefix: String,
+    
+    /// すべてのメトリクスに付与するタグ（"key:value"）
+    #[serde(default)]
+    pub tags: Vec<String>,
+    
+    /// 送信間隔（秒）
+    #[serde(default = "default_sink_interval")]
+    pub interval: u64,
+}
+
+impl Default for SinkConfig {
+    fn default() -> Self {
+        Self {
+            kind: "dogstatsd".to_string(),
+            address: None,
+            url: None,
+            token: None,
+            path: None,
+            prefix: String::new(),
+            tags: Vec::new(),
+            interval: default_sink_interval(),
+        }
+    }
+}
+
+fn default_sink_interval() -> u64 {
+    10
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct OtlpConfig {
+    /// 送信プロトコル（"grpc"、"http/protobuf"、"none"）。http/protobufでは`/v1/metrics`などがエンドポイントに付与されます
+    #[serde(default = "default_otlp_protocol")]
+    pub protocol: String,
+    
+    /// リクエストに付与するヘッダー（gRPCではメタデータ）
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    
+    /// 圧縮方式（"gzip"）。省略時は圧縮しません
+    #[serde(default)]
+    pub compression: Option<String>,
+    
+    /// コレクターとのTLS設定
+    #[serde(default)]
+    pub tls: Option<OtlpTlsConfig>,
+    
+    /// メトリクスのエクスポート間隔（秒）。stdout/fileへの出力にも使用されます
+    #[serde(default = "default_otlp_export_interval")]
+    pub export_interval: u64,
+    
+    /// 1回のエクスポートのタイムアウト（秒）
+    #[serde(default = "default_otlp_export_timeout")]
+    pub export_timeout: u64,
+    
+    /// カウンターとヒストグラムの集計方法（"cumulative"または"delta"）
+    #[serde(default = "default_otlp_temporality")]
+    pub temporality: String,
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            protocol: default_otlp_protocol(),
+            headers: HashMap::new(),
+            compression: None,
+            tls: None,
+            export_interval: default_otlp_export_interval(),
+            export_timeout: default_otlp_export_timeout(),
+            temporality: default_otlp_temporality(),
+        }
+    }
+}
+
+fn default_otlp_protocol() -> String {
+    "grpc".to_string()
+}
+
+fn default_otlp_export_interval() -> u64 {
+    10
+}
+
+fn default_otlp_export_timeout() -> u64 {
+    10
+}
+
+fn default_otlp_temporality() -> String {
+    "cumulative".to_string()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct OtlpTlsConfig {
+    /// コレクターの証明書を検証するCA（PEM）のパス。省略時はシステムのルート証明書
+    #[serde(default)]
+    pub ca_path: Option<String>,
+    
+    /// クライアント証明書（PEM）のパス（mTLS、`key_path`と併用）
+    #[serde(default)]
+    pub cert_path: Option<String>,
+    
+    /// クライアント秘密鍵（PEM, PKCS#8）のパス
+    #[serde(default)]
+    pub key_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct DockerConfig {
+    /// Dockerソケットパス（hostが指定されていない場合に使用）
+    #[serde(default = "default_socket_path")]
     pub socket_path: String,
+    
+    /// Dockerデーモンのアドレス（unix://, tcp://, ssh://user@host）
+    #[serde(default)]
+    pub host: Option<String>,
+    
+    /// tcp://接続時のTLSクライアント証明書設定
+    #[serde(default)]
+    pub tls: Option<DockerTlsConfig>,
+    
+    /// ssh://接続時の設定
+    #[serde(default)]
+    pub ssh: DockerSshConfig,
+    
+    /// Docker APIリクエストのタイムアウト（秒）
+    #[serde(default = "default_docker_timeout")]
+    pub timeout: u64,
+    
+    /// 使用するDocker APIバージョン（例: "1.43"）。省略時はクライアントのデフォルト
+    #[serde(default)]
+    pub api_version: Option<String>,
+    
+    /// 複数のDockerデーモンを監視する場合のホスト一覧（`[[docker.hosts]]`）。
+    /// 指定されている場合、上記の接続設定は使用されません
+    #[serde(default)]
+    pub hosts: Vec<DockerHostConfig>,
+}
+
+impl Default for DockerConfig {
+    fn default() -> Self {
+        Self {
+            socket_path: default_socket_path(),
+            host: None,
+            tls: None,
+            ssh: DockerSshConfig::default(),
+            timeout: default_docker_timeout(),
+            api_version: None,
+            hosts: Vec::new(),
+        }
+    }
+}
+
+impl DockerConfig {
+    /// 監視対象のホスト一覧を取得（`hosts`が空の場合は自身の接続設定を"local"として返す）
+    pub fn resolved_hosts(&self) -> Vec<DockerHostConfig> {
+        if !self.hosts.is_empty() {
+            return self.hosts.clone();
+        }
+        
+        vec![DockerHostConfig {
+            name: default_host_name(),
+            connection: DockerConfig {
+                hosts: Vec::new(),
+                ..self.clone()
+            },
+        }]
+    }
+}
+
+fn default_host_name() -> String {
+    "local".to_string()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DockerHostConfig {
+    /// メトリクスの`host`属性に付与する名前
+    pub name: String,
+    
+    /// 接続設定（`[docker]`と同じキー。ネストした`hosts`は無視されます）
+    #[serde(flatten)]
+    pub connection: DockerConfig,
+}
+
+fn default_socket_path() -> String {
+    "/var/run/docker.sock".to_string()
+}
+
+fn default_docker_timeout() -> u64 {
+    120
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DockerTlsConfig {
+    /// デーモン証明書を検証するCA（PEM）のパス
+    pub ca_path: String,
+    
+    /// クライアント証明書（PEM）のパス
+    pub cert_path: String,
+    
+    /// クライアント秘密鍵（PEM, RSA）のパス
+    pub key_path: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DockerSshConfig {
+    /// 実行するsshコマンド（PATHから検索、またはパス）
+    #[serde(default = "default_ssh_command")]
+    pub command: String,
+    
+    /// sshコマンドに渡す秘密鍵ファイル
+    #[serde(default)]
+    pub identity_file: Option<String>,
+    
+    /// リモートホスト上のDockerソケットパス
+    #[serde(default = "default_socket_path")]
+    pub remote_socket_path: String,
+    
+    /// トンネル確立を待つ時間（秒）
+    #[serde(default = "default_ssh_connect_timeout")]
+    pub connect_timeout: u64,
+}
+
+impl Default for DockerSshConfig {
+    fn default() -> Self {
+        Self {
+            command: default_ssh_command(),
+            identity_file: None,
+            remote_socket_path: default_socket_path(),
+            connect_timeout: default_ssh_connect_timeout(),
+        }
+    }
+}
+
+fn default_ssh_command() -> String {
+    "ssh".to_string()
+}
+
+fn default_ssh_connect_timeout() -> u64 {
+    10
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -38,6 +424,111 @@ pub struct MetricsConfig {
     pub enable_disk: bool,
     #[serde(default)]
     pub container_filters: ContainerFilters,
+    
+    /// 統計情報を同時に取得するコンテナ数の上限
+    #[serde(default = "default_stats_concurrency")]
+    pub stats_concurrency: usize,
+    
+    /// コンテナごとの統計情報取得のタイムアウト（秒）。超過したコンテナはstaleとして扱います
+    #[serde(default = "default_stats_timeout")]
+    pub stats_timeout: u64,
+    
+    /// コンテナのインスペクト（ヘルスチェック、再起動回数、OOMなど）を有効化
+    #[serde(default = "default_true")]
+    pub enable_inspect: bool,
+    
+    /// インスペクト結果のキャッシュ有効期間（秒）。ライフサイクルイベントでも更新されます
+    #[serde(default = "default_inspect_interval")]
+    pub inspect_interval: u64,
+    
+    /// ブロックデバイス名の解決に使用するsysfsのパス（コンテナ内ではホストの/sysをマウント）
+    #[serde(default = "default_sysfs_path")]
+    pub sysfs_path: String,
+    
+    /// コンテナの書き込み可能レイヤーとボリュームのディスク使用量の収集を有効化
+    #[serde(default)]
+    pub enable_disk_usage: bool,
+    
+    /// ディスク使用量の収集間隔（秒）。Dockerデーモンの負荷が高いため通常の収集より長くします
+    #[serde(default = "default_disk_usage_interval")]
+    pub disk_usage_interval: u64,
+    
+    /// ホスト全体のメトリクス（CPU、メモリ、ロード、ネットワーク、ディスク）とDockerデーモン情報の収集を有効化
+    #[serde(default)]
+    pub enable_host: bool,
+    
+    /// ホストメトリクスの読み取りに使用するprocfsのパス（コンテナ内ではホストの/procをマウント）
+    #[serde(default = "default_procfs_path")]
+    pub procfs_path: String,
+    
+    /// コンテナごとにCPU使用率上位のプロセスをN件報告（0で無効）
+    #[serde(default)]
+    pub top_processes: usize,
+    
+    /// エージェント自身のメトリクス（RSS、CPU時間、ファイルディスクリプタ、スレッド、tokioランタイム）の収集を有効化
+    #[serde(default = "default_true")]
+    pub enable_agent: bool,
+    
+    /// コンテナごとのメトリクスのラベルと系列数の制御
+    #[serde(default)]
+    pub cardinality: CardinalityConfig,
+    
+    /// Kubernetesのポッド情報の付与
+    #[serde(default)]
+    pub kubernetes: KubernetesConfig,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enable_cpu: true,
+            enable_memory: true,
+            enable_network: true,
+            enable_disk: true,
+            container_filters: ContainerFilters::default(),
+            stats_concurrency: default_stats_concurrency(),
+            stats_timeout: default_stats_timeout(),
+            enable_inspect: true,
+            inspect_interval: default_inspect_interval(),
+            sysfs_path: default_sysfs_path(),
+            enable_disk_usage: false,
+            disk_usage_interval: default_disk_usage_interval(),
+            enable_host: false,
+            procfs_path: default_procfs_path(),
+            top_processes: 0,
+            enable_agent: true,
+            cardinality: CardinalityConfig::default(),
+            kubernetes: KubernetesConfig::default(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_inspect_interval() -> u64 {
+    60
+}
+
+fn default_disk_usage_interval() -> u64 {
+    300
+}
+
+fn default_procfs_path() -> String {
+    "/proc".to_string()
+}
+
+fn default_sysfs_path() -> String {
+    "/sys".to_string()
+}
+
+fn default_stats_concurrency() -> usize {
+    16
+}
+
+fn default_stats_timeout() -> u64 {
+    10
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -55,11 +546,447 @@ pub struct ContainerFilters {
     pub image_patterns: Vec<String>,
 }
 
+/// コンテナごとのメトリクスのカーディナリティ制御の設定
+#[derive(Debug, Deserialize, Clone)]
+pub struct CardinalityConfig {
+    /// コンテナごとのメトリクスに付与するラベル（container_id、container_name、imageから選択。hostは常に付与）
+    #[serde(default = "default_container_labels")]
+    pub container_labels: Vec<String>,
+    
+    /// 累計値（`_total`）のカウンターに付与するラベル。SDKは累計値の系列を削除できないため、既定ではcontainer_idを付与しません
+    #[serde(default = "default_counter_labels")]
+    pub counter_labels: Vec<String>,
+    
+    /// ホストごと・メトリクスごとの系列数の上限（0で無制限）。超過分はotel.metric.overflow系列に集約します
+    #[serde(default = "default_max_series_per_metric")]
+    pub max_series_per_metric: usize,
+}
+
+impl Default for CardinalityConfig {
+    fn default() -> Self {
+        Self {
+            container_labels: default_container_labels(),
+            counter_labels: default_counter_labels(),
+            max_series_per_metric: default_max_series_per_metric(),
+        }
+    }
+}
+
+fn default_container_labels() -> Vec<String> {
+    vec!["container_id".to_string(), "container_name".to_string(), "image".to_string()]
+}
+
+fn default_counter_labels() -> Vec<String> {
+    vec!["container_name".to_string(), "image".to_string()]
+}
+
+fn default_max_series_per_metric() -> usize {
+    1000
+}
+
+/// Kubernetesノードでコンテナをポッドに対応付ける設定
+#[derive(Debug, Deserialize, Clone)]
+pub struct KubernetesConfig {
+    /// コンテナのメトリクスにpod、namespace、workload、nodeラベルを付与し、pauseコンテナを除外
+    #[serde(default)]
+    pub enabled: bool,
+    
+    /// ポッド情報の取得元（"kubelet"はkubeletの/pods、"labels"はコンテナのラベルと名前のみ）
+    #[serde(default = "default_kubernetes_source")]
+    pub source: String,
+    
+    /// kubeletのURL
+    #[serde(default = "default_kubelet_url")]
+    pub kubelet_url: String,
+    
+    /// kubeletへのリクエストに付与するBearerトークンのファイル（ローテーションに対応するため毎回読み込み）
+    #[serde(default)]
+    pub token_path: Option<String>,
+    
+    /// kubeletの証明書を検証するCA（省略時はシステムのルート証明書）
+    #[serde(default)]
+    pub ca_path: Option<String>,
+    
+    /// kubeletの証明書を検証しない（自己署名の証明書を使うクラスタ向け）
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+    
+    /// nodeラベルの値（省略時はポッドのspec.nodeName）
+    #[serde(default)]
+    pub node_name: Option<String>,
+    
+    /// ポッド一覧の更新間隔（秒）。未知のポッドのコンテナを見つけた場合は間隔を待たずに更新します
+    #[serde(default = "default_kubernetes_refresh_interval")]
+    pub refresh_interval: u64,
+}
+
+impl Default for KubernetesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            source: default_kubernetes_source(),
+            kubelet_url: default_kubelet_url(),
+            token_path: None,
+            ca_path: None,
+            insecure_skip_verify: false,
+            node_name: None,
+            refresh_interval: default_kubernetes_refresh_interval(),
+        }
+    }
+}
+
+fn default_kubernetes_source() -> String {
+    "kubelet".to_string()
+}
+
+fn default_kubelet_url() -> String {
+    "https://127.0.0.1:10250".to_string()
+}
+
+fn default_kubernetes_refresh_interval() -> u64 {
+    30
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct LoggingConfig {
     pub level: String,
 }
 
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+        }
+    }
+}
+
+/// コンテナログの追跡によるスループット・エラー率メトリクスの設定
+#[derive(Debug, Deserialize, Clone)]
+pub struct ContainerLogsConfig {
+    /// 監視対象コンテナのログストリームの追跡を有効化
+    #[serde(default)]
+    pub enabled: bool,
+    
+    /// 一致した行数を数える正規表現パターン
+    #[serde(default)]
+    pub patterns: Vec<LogPatternConfig>,
+    
+    /// 1行として扱う最大バイト数（改行のない出力はこのサイズで区切ります）
+    #[serde(default = "default_max_line_bytes")]
+    pub max_line_bytes: usize,
+    
+    /// ログ行をOpenTelemetryのログレコードとしてOTLPで転送する設定
+    #[serde(default)]
+    pub otlp: LogExportConfig,
+}
+
+impl Default for ContainerLogsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            patterns: Vec::new(),
+            max_line_bytes: default_max_line_bytes(),
+            otlp: LogExportConfig::default(),
+        }
+    }
+}
+
+/// コンテナログのOTLP転送設定
+#[derive(Debug, Deserialize, Clone)]
+pub struct LogExportConfig {
+    /// OTLPへの転送を有効化（`container_logs.enabled`も必要）
+    #[serde(default)]
+    pub enabled: bool,
+    
+    /// 転送先のOTLPエンドポイント（省略時は`telemetry.otel_endpoint`）
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    
+    /// JSON形式の行を構造化された属性に変換
+    #[serde(default = "default_true")]
+    pub parse_json: bool,
+    
+    /// 送信待ちのログレコードの上限。超過したレコードは破棄され、ログの追跡は止まりません
+    #[serde(default = "default_log_max_queue_size")]
+    pub max_queue_size: usize,
+    
+    /// 1回のエクスポートで送信するレコード数の上限
+    #[serde(default = "default_log_max_export_batch_size")]
+    pub max_export_batch_size: usize,
+    
+    /// バッチを送信する間隔（ミリ秒）
+    #[serde(default = "default_log_batch_delay_ms")]
+    pub batch_delay_ms: u64,
+}
+
+impl Default for LogExportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            parse_json: true,
+            max_queue_size: default_log_max_queue_size(),
+            max_export_batch_size: default_log_max_export_batch_size(),
+            batch_delay_ms: default_log_batch_delay_ms(),
+        }
+    }
+}
+
+fn default_log_max_queue_size() -> usize {
+    2048
+}
+
+fn default_log_max_export_batch_size() -> usize {
+    512
+}
+
+fn default_log_batch_delay_ms() -> u64 {
+    1000
+}
+
+fn default_max_line_bytes() -> usize {
+    64 * 1024
+}
+
+/// ログ行のパターン（メトリクスの`pattern`属性に名前が使われます）
+#[derive(Debug, Deserialize, Clone)]
+pub struct LogPatternConfig {
+    pub name: String,
+    pub regex: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ServerConfig {
+    /// メトリクスサーバーのバインドアドレス
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
+    
+    /// TLS設定。指定されていればHTTPSで待ち受けます
+    #[serde(default)]
+    pub tls: Option<ServerTlsConfig>,
+    
+    /// 認証設定
+    #[serde(default)]
+    pub auth: AuthConfig,
+    
+    /// /debug/pprof エンドポイントの設定（`profiling`フィーチャー付きでビルドした場合のみ有効）
+    #[serde(default)]
+    pub pprof: PprofConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: default_bind_address(),
+            tls: None,
+            auth: AuthConfig::default(),
+            pprof: PprofConfig::default(),
+        }
+    }
+}
+
+fn default_bind_address() -> String {
+    "0.0.0.0".to_string()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ServerTlsConfig {
+    /// サーバー証明書（PEM）のパス
+    pub cert_path: String,
+    
+    /// サーバー秘密鍵（PEM）のパス
+    pub key_path: String,
+    
+    /// クライアント証明書を検証するCA（PEM）のパス。指定されていればmTLSを要求します
+    #[serde(default)]
+    pub client_ca_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(not(feature = "profiling"), allow(dead_code))]
+pub struct PprofConfig {
+    /// /debug/pprof/profile と /debug/pprof/heap を公開するかどうか
+    #[serde(default)]
+    pub enabled: bool,
+    
+    /// 1回のCPUプロファイルで指定できる最大秒数
+    #[serde(default = "default_pprof_max_seconds")]
+    pub max_seconds: u64,
+    
+    /// CPUプロファイルのサンプリング周波数（Hz）
+    #[serde(default = "default_pprof_frequency")]
+    pub frequency: i32,
+}
+
+impl Default for PprofConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_seconds: default_pprof_max_seconds(),
+            frequency: default_pprof_frequency(),
+        }
+    }
+}
+
+fn default_pprof_max_seconds() -> u64 {
+    60
+}
+
+fn default_pprof_frequency() -> i32 {
+    100
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AuthConfig {
+    /// Basic認証の資格情報
+    #[serde(default)]
+    pub basic: Option<BasicAuthConfig>,
+    
+    /// Bearerトークン
+    #[serde(default)]
+    pub bearer_token: Option<String>,
+    
+    /// 認証なしでアクセスできるルート（例: "health"）
+    #[serde(default = "default_public_routes")]
+    pub public_routes: Vec<String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            basic: None,
+            bearer_token: None,
+            public_routes: default_public_routes(),
+        }
+    }
+}
+
+fn default_public_routes() -> Vec<String> {
+    vec!["health".to_string()]
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BasicAuthConfig {
+    pub username: String,
+    pub password: String,
+}
+
+/// エージェントの再起動をまたいでカウンターの前回値を引き継ぐ状態ファイルの設定
+#[derive(Debug, Deserialize, Clone)]
+pub struct StateConfig {
+    /// 状態ファイルのパス（未指定の場合は保存しない）
+    #[serde(default)]
+    pub path: Option<String>,
+    
+    /// 書き込むタイミング（"cycle"は収集サイクルごとと終了時、"shutdown"は終了時のみ）
+    #[serde(default = "default_state_write")]
+    pub write: String,
+}
+
+impl Default for StateConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            write: default_state_write(),
+        }
+    }
+}
+
+fn default_state_write() -> String {
+    "cycle".to_string()
+}
+
+/// Prometheus remote_writeによるメトリクスの送信設定（スクレイプできない環境向け）
+#[derive(Debug, Deserialize, Clone)]
+pub struct RemoteWriteConfig {
+    /// remote_writeによる送信を有効化
+    #[serde(default)]
+    pub enabled: bool,
+    
+    /// 送信先のURL（例: http://prometheus:9090/api/v1/write）
+    #[serde(default)]
+    pub url: String,
+    
+    /// Basic認証の資格情報
+    #[serde(default)]
+    pub basic: Option<BasicAuthConfig>,
+    
+    /// 送信間隔（秒）
+    #[serde(default = "default_remote_write_interval")]
+    pub interval: u64,
+    
+    /// 1回のリクエストのタイムアウト（秒）
+    #[serde(default = "default_remote_write_timeout")]
+    pub timeout: u64,
+    
+    /// 失敗したリクエストの再試行回数（5xx、429、接続エラーのみ）
+    #[serde(default = "default_remote_write_max_retries")]
+    pub max_retries: u32,
+    
+    /// 最初の再試行までの待ち時間（ミリ秒、再試行ごとに倍増）
+    #[serde(default = "default_remote_write_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    
+    /// すべての系列に付与するラベル（例: instance = "edge-01"）
+    #[serde(default)]
+    pub external_labels: HashMap<String, String>,
+    
+    /// 送信できなかったバッチの保持先（"memory"または"disk"）
+    #[serde(default = "default_remote_write_queue")]
+    pub queue: String,
+    
+    /// queue = "disk"の場合にバッチを保存するディレクトリ
+    #[serde(default)]
+    pub queue_path: Option<String>,
+    
+    /// 保持するバッチ数の上限。超過した場合は古いバッチから破棄します
+    #[serde(default = "default_remote_write_max_queue_batches")]
+    pub max_queue_batches: usize,
+}
+
+impl Default for RemoteWriteConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            basic: None,
+            interval: default_remote_write_interval(),
+            timeout: default_remote_write_timeout(),
+            max_retries: default_remote_write_max_retries(),
+            retry_backoff_ms: default_remote_write_retry_backoff_ms(),
+            external_labels: HashMap::new(),
+            queue: default_remote_write_queue(),
+            queue_path: None,
+            max_queue_batches: default_remote_write_max_queue_batches(),
+        }
+    }
+}
+
+fn default_remote_write_interval() -> u64 {
+    15
+}
+
+fn default_remote_write_timeout() -> u64 {
+    10
+}
+
+fn default_remote_write_max_retries() -> u32 {
+    3
+}
+
+fn default_remote_write_retry_backoff_ms() -> u64 {
+    500
+}
+
+fn default_remote_write_queue() -> String {
+    "memory".to_string()
+}
+
+fn default_remote_write_max_queue_batches() -> usize {
+    // 15秒間隔で1時間分
+    240
+}
+
 pub fn load_config<P: AsRef<Path>>(path: P) -> Result<Config> {
     let config_str = fs::read_to_string(path.as_ref())
         .with_context(|| format!("Failed to read config file: {:?}", path.as_ref()))?;
@@ -1,31 +1,80 @@
-use anyhow::{Context, Result};
-use bollard::container::{ListContainersOptions, Stats, StatsOptions};
-use bollard::Docker;
-use bollard::system::SystemInfo;
-use futures::stream::StreamExt;
-use std::collections::HashMap;
+use anyhow::{bail, Context, Result};
+use bollard::container::{
+    BlkioStats, BlkioStatsEntry, InspectContainerOptions, ListContainersOptions, LogOutput, LogsOptions, Stats,
+    StatsOptions, TopOptions,
+};
+use bollard::{ClientVersion, Docker};
+use bollard::system::{EventsOptions, SystemInfo};
+use futures::stream::{Stream, StreamExt};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 use std::sync::Arc;
-use tracing::{debug, error, info, instrument};
+use std::time::Duration;
+use tracing::{debug, error, info, instrument, warn};
 
 use crate::config::{DockerConfig, ContainerFilters};
+use crate::kubernetes::PodInfo;
+use crate::ssh::SshTunnel;
 
 /// Dockerクライアント - Docker APIとの通信を担当
+#[derive(Clone)]
 pub struct DockerClient {
     client: Docker,
+    // ssh://接続の場合、クライアントが生きている間トンネルを維持する
+    _tunnel: Option<Arc<SshTunnel>>,
 }
 
 /// コンテナ情報の構造体
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ContainerInfo {
     pub id: String,
     pub name: String,
     pub image: String,
     pub status: String,
     pub stats: Option<ContainerStats>,
+    /// 統計情報の取得がタイムアウトし、今回のサイクルでは値がないことを示す
+    pub stale: bool,
+    /// コンテナのインスペクト結果（ヘルスチェックや再起動回数）
+    pub details: Option<ContainerDetails>,
+    /// CPU使用率上位のプロセス（`top_processes`が有効な場合のみ）
+    pub processes: Vec<ProcessInfo>,
+    /// コンテナのラベル（Composeのプロジェクト名やサービス名など）
+    pub labels: HashMap<String, String>,
+    /// Kubernetesのポッド情報（`metrics.kubernetes`が有効な場合のみ）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pod: Option<PodInfo>,
+}
+
+/// コンテナ内のプロセス情報（`docker top`から取得）
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProcessInfo {
+    /// Dockerホスト上のPID
+    pub pid: u64,
+    pub command: String,
+    /// 累計CPU時間（秒、1秒単位）
+    pub cpu_seconds: f64,
+    /// CPU使用率（%）。psの値はプロセス起動からの平均のため、前回値があれば差分から再計算する
+    pub cpu_usage_percent: f64,
+    pub rss_bytes: u64,
+}
+
+/// コンテナのインスペクト結果から取得する状態情報
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ContainerDetails {
+    /// ヘルスチェックの状態（healthy, unhealthy, starting）。ヘルスチェックがない場合はNone
+    pub health_status: Option<String>,
+    pub failing_streak: i64,
+    pub restart_count: i64,
+    /// 現在の実行の開始時刻（UNIX秒）
+    pub started_at: Option<i64>,
+    /// 直近の実行の終了コード
+    pub exit_code: i64,
+    pub oom_killed: bool,
 }
 
 /// コンテナの統計情報の構造体
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ContainerStats {
     pub cpu_usage_percent: f64,
     pub memory_usage_bytes: u64,
@@ -35,16 +84,137 @@ pub struct ContainerStats {
     pub network_tx_bytes: u64,
     pub block_read_bytes: u64,
     pub block_write_bytes: u64,
+    /// ブロックデバイスごとのI/O統計情報
+    pub block_devices: Vec<BlockDeviceStats>,
     pub pids: u64,
 }
 
+/// ブロックデバイス（major:minor）ごとのI/O統計情報（累計値）
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BlockDeviceStats {
+    pub major: u64,
+    pub minor: u64,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub discard_bytes: u64,
+    pub read_ops: u64,
+    pub write_ops: u64,
+    pub discard_ops: u64,
+}
+
+/// `system df`から取得したディスク使用量
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DiskUsage {
+    pub containers: Vec<ContainerDiskUsage>,
+    pub volumes: Vec<VolumeDiskUsage>,
+}
+
+/// コンテナごとのディスク使用量
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ContainerDiskUsage {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    /// 書き込み可能レイヤーのサイズ（SizeRw）
+    pub writable_layer_bytes: u64,
+    /// イメージを含むルートファイルシステム全体のサイズ（SizeRootFs）
+    pub root_fs_bytes: u64,
+}
+
+/// ボリュームごとのディスク使用量（localドライバーのボリュームのみ）
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VolumeDiskUsage {
+    pub name: String,
+    pub driver: String,
+    pub size_bytes: u64,
+    /// ボリュームを参照しているコンテナ数
+    pub ref_count: u64,
+}
+
+/// コンテナログのストリーム種別
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+impl LogStream {
+    /// メトリクスの`stream`属性の値
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogStream::Stdout => "stdout",
+            LogStream::Stderr => "stderr",
+        }
+    }
+}
+
+/// コンテナのライフサイクルイベント（start, die, destroyなど）
+#[derive(Debug, Clone, Serialize)]
+pub struct ContainerEvent {
+    pub host: String,
+    pub timestamp: i64,
+    pub action: String,
+    pub container_id: String,
+    pub container_name: String,
+    pub image: String,
+}
+
 impl DockerClient {
     /// 新しいDockerクライアントを作成
+    ///
+    /// `host`の指定に従って、UNIXソケット・TCP（TLS）・SSHトンネルのいずれかで接続します。
     pub fn new(config: &DockerConfig) -> Result<Self> {
-        let client = Docker::connect_with_socket(&config.socket_path, 120, bollard::API_DEFAULT_VERSION)
-            .with_context(|| format!("Failed to connect to Docker socket at {}", config.socket_path))?;
+        let version = Self::client_version(config.api_version.as_deref())?;
+        let host = config.host.clone()
+            .unwrap_or_else(|| format!("unix://{}", config.socket_path));
         
-        Ok(Self { client })
+        if let Some(destination) = host.strip_prefix("ssh://") {
+            let tunnel = SshTunnel::open(destination, &config.ssh)?;
+            let socket = tunnel.local_socket().to_string_lossy().into_owned();
+            let client = Docker::connect_with_socket(&socket, config.timeout, &version)
+                .with_context(|| format!("Failed to connect to Docker daemon via {}", host))?;
+            
+            return Ok(Self { client, _tunnel: Some(Arc::new(tunnel)) });
+        }
+        
+        let client = if host.starts_with("tcp://") || host.starts_with("https://") || host.starts_with("http://") {
+            match &config.tls {
+                Some(tls) => Docker::connect_with_ssl(
+                    &host,
+                    Path::new(&tls.key_path),
+                    Path::new(&tls.cert_path),
+                    Path::new(&tls.ca_path),
+                    config.timeout,
+                    &version,
+                ),
+                None => Docker::connect_with_http(&host, config.timeout, &version),
+            }
+            .with_context(|| format!("Failed to connect to Docker daemon at {}", host))?
+        } else {
+            Docker::connect_with_socket(&host, config.timeout, &version)
+                .with_context(|| format!("Failed to connect to Docker socket at {}", host))?
+        };
+        
+        Ok(Self { client, _tunnel: None })
+    }
+    
+    /// "1.43"形式のAPIバージョンを解析（未指定の場合はクライアントのデフォルト）
+    fn client_version(api_version: Option<&str>) -> Result<ClientVersion> {
+        let Some(api_version) = api_version else {
+            return Ok(*bollard::API_DEFAULT_VERSION);
+        };
+        
+        let (major, minor) = api_version.trim_start_matches('v')
+            .split_once('.')
+            .with_context(|| format!("Invalid Docker API version: {}", api_version))?;
+        
+        Ok(ClientVersion {
+            major_version: major.parse()
+                .with_context(|| format!("Invalid Docker API version: {}", api_version))?,
+            minor_version: minor.parse()
+                .with_context(|| format!("Invalid Docker API version: {}", api_version))?,
+        })
     }
     
     /// Dockerシステム情報を取得
@@ -67,6 +237,50 @@ impl DockerClient {
         result
     }
     
+    /// コンテナとボリュームのディスク使用量を取得（`docker system df -v`相当）
+    ///
+    /// Dockerデーモンがすべてのレイヤーとボリュームのサイズを計算するため、
+    /// 通常の統計情報よりも低い頻度で呼び出してください。
+    #[instrument(skip(self), level = "debug")]
+    pub async fn disk_usage(&self) -> Result<DiskUsage> {
+        let response = self.client.df()
+            .await
+            .with_context(|| "Failed to get Docker disk usage")?;
+        
+        let containers = response.containers
+            .unwrap_or_default()
+            .into_iter()
+            .map(|container| ContainerDiskUsage {
+                id: container.id.unwrap_or_default(),
+                name: container.names
+                    .unwrap_or_default()
+                    .first()
+                    .map(|name| name.trim_start_matches('/').to_string())
+                    .unwrap_or_default(),
+                image: container.image.unwrap_or_default(),
+                writable_layer_bytes: container.size_rw.unwrap_or(0).max(0) as u64,
+                root_fs_bytes: container.size_root_fs.unwrap_or(0).max(0) as u64,
+            })
+            .collect();
+        
+        // localドライバー以外のボリュームはサイズが-1（取得不可）となるため除外
+        let volumes = response.volumes
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|volume| {
+                let usage = volume.usage_data.filter(|usage| usage.size >= 0)?;
+                Some(VolumeDiskUsage {
+                    name: volume.name,
+                    driver: volume.driver,
+                    size_bytes: usage.size as u64,
+                    ref_count: usage.ref_count.max(0) as u64,
+                })
+            })
+            .collect();
+        
+        Ok(DiskUsage { containers, volumes })
+    }
+    
     /// すべてのコンテナのリストを取得
     #[instrument(skip(self), level = "debug")]
     pub async fn list_containers(&self) -> Result<Vec<ContainerInfo>> {
@@ -93,6 +307,7 @@ impl DockerClient {
                 
                 let image = container.image.unwrap_or_default();
                 let status = container.state.unwrap_or_default();
+                let labels = container.labels.unwrap_or_default();
                 
                 ContainerInfo {
                     id,
@@ -100,6 +315,11 @@ impl DockerClient {
                     image,
                     status,
                     stats: None,
+                    stale: false,
+                    details: None,
+                    processes: Vec::new(),
+                    labels,
+                    pod: None,
                 }
             })
             .collect();
@@ -161,7 +381,7 @@ impl DockerClient {
     }
     
     /// シンプルなパターンマッチング
-    fn matches_pattern(input: &str, pattern: &str) -> bool {
+    pub(crate) fn matches_pattern(input: &str, pattern: &str) -> bool {
         // '*'と'?'のみをサポートするシンプルなワイルドカードマッチング
         if pattern.contains('*') || pattern.contains('?') {
             Self::matches_wildcard(input, pattern)
@@ -209,54 +429,207 @@ impl DockerClient {
         dp[pattern_chars.len()][input_chars.len()]
     }
     
+    /// コンテナをインスペクトして状態情報を取得
+    #[instrument(skip(self), level = "debug")]
+    pub async fn inspect_container(&self, container_id: &str) -> Result<ContainerDetails> {
+        let response = self.client.inspect_container(container_id, None::<InspectContainerOptions>)
+            .await
+            .with_context(|| format!("Failed to inspect container {}", container_id))?;
+        
+        let state = response.state.unwrap_or_default();
+        let health = state.health.unwrap_or_default();
+        
+        // ヘルスチェック未設定のコンテナは"none"または空文字列を返す
+        let health_status = health.status
+            .map(|status| status.to_string())
+            .filter(|status| !status.is_empty() && status != "none");
+        
+        // 一度も起動していないコンテナは"0001-01-01T00:00:00Z"を返す
+        let started_at = state.started_at
+            .and_then(|started_at| chrono::DateTime::parse_from_rfc3339(&started_at).ok())
+            .map(|started_at| started_at.timestamp())
+            .filter(|timestamp| *timestamp > 0);
+        
+        Ok(ContainerDetails {
+            health_status,
+            failing_streak: health.failing_streak.unwrap_or(0),
+            restart_count: response.restart_count.unwrap_or(0),
+            started_at,
+            exit_code: state.exit_code.unwrap_or(0),
+            oom_killed: state.oom_killed.unwrap_or(false),
+        })
+    }
+    
+    /// コンテナ内のプロセス一覧を取得（psはDockerホスト上で実行され、PID名前空間の外側のPIDが返る）
+    pub async fn top_processes(&self, container_id: &str) -> Result<Vec<ProcessInfo>> {
+        let options = TopOptions { ps_args: "-eo pid,pcpu,time,rss,comm" };
+        let response = self.client.top_processes(container_id, Some(options))
+            .await
+            .with_context(|| format!("Failed to list processes of container {}", container_id))?;
+        
+        let titles = response.titles.unwrap_or_default();
+        let column = |name: &str| titles.iter().position(|title| title == name);
+        let (Some(pid), Some(pcpu), Some(time), Some(rss), Some(command)) =
+            (column("PID"), column("%CPU"), column("TIME"), column("RSS"), column("COMMAND"))
+        else {
+            bail!("Unexpected docker top columns for container {}: {:?}", container_id, titles);
+        };
+        
+        let processes = response.processes
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|row| {
+                Some(ProcessInfo {
+                    pid: row.get(pid)?.parse().ok()?,
+                    command: row.get(command)?.clone(),
+                    cpu_seconds: Self::parse_ps_time(row.get(time)?)?,
+                    cpu_usage_percent: row.get(pcpu)?.parse().unwrap_or(0.0),
+                    // psのRSSはKiB単位
+                    rss_bytes: row.get(rss)?.parse::<u64>().ok()? * 1024,
+                })
+            })
+            .collect();
+        
+        Ok(processes)
+    }
+    
+    // psのTIME列（[DD-]HH:MM:SS）を秒に変換
+    fn parse_ps_time(value: &str) -> Option<f64> {
+        let (days, clock) = match value.split_once('-') {
+            Some((days, clock)) => (days.parse::<u64>().ok()?, clock),
+            None => (0, value),
+        };
+        
+        let seconds = clock
+            .split(':')
+            .try_fold(0u64, |total, part| Some(total * 60 + part.parse::<u64>().ok()?))?;
+        
+        Some((days * 86400 + seconds) as f64)
+    }
+    
+    /// コンテナのログを追跡し、出力されたチャンクをストリームとして返す
+    ///
+    /// `since`（UNIX秒）以降の出力のみを返します。TTYコンテナの出力はstdoutとして扱います。
+    pub fn follow_logs(&self, container_id: &str, since: i64) -> impl Stream<Item = Result<(LogStream, Vec<u8>)>> {
+        let options = LogsOptions::<String> {
+            follow: true,
+            stdout: true,
+            stderr: true,
+            since,
+            ..Default::default()
+        };
+        
+        let container_id = container_id.to_string();
+        self.client.logs(&container_id, Some(options)).filter_map(move |result| {
+            let chunk = match result {
+                Ok(LogOutput::StdOut { message }) | Ok(LogOutput::Console { message }) => {
+                    Some(Ok((LogStream::Stdout, message.to_vec())))
+                }
+                Ok(LogOutput::StdErr { message }) => Some(Ok((LogStream::Stderr, message.to_vec()))),
+                Ok(LogOutput::StdIn { .. }) => None,
+                Err(e) => Some(Err(anyhow::anyhow!("Failed to read logs of container {}: {}", container_id, e))),
+            };
+            futures::future::ready(chunk)
+        })
+    }
+    
+    /// コンテナのライフサイクルイベントをストリームとして購読
+    pub fn container_events(&self) -> impl Stream<Item = Result<ContainerEvent>> {
+        let mut filters = HashMap::new();
+        filters.insert("type".to_string(), vec!["container".to_string()]);
+        
+        let options = EventsOptions::<String> {
+            filters,
+            ..Default::default()
+        };
+        
+        self.client.events(Some(options)).map(|result| {
+            let message = result.with_context(|| "Failed to receive Docker event")?;
+            let actor = message.actor.unwrap_or_default();
+            let attributes = actor.attributes.unwrap_or_default();
+            
+            Ok(ContainerEvent {
+                host: String::new(),
+                timestamp: message.time.unwrap_or_default(),
+                action: message.action.unwrap_or_default(),
+                container_id: actor.id.unwrap_or_default(),
+                container_name: attributes.get("name").cloned().unwrap_or_default(),
+                image: attributes.get("image").cloned().unwrap_or_default(),
+            })
+        })
+    }
+    
     /// コンテナの統計情報を収集
+    ///
+    /// 同時リクエスト数を`max_concurrency`に制限し、`timeout`以内に応答しないコンテナは
+    /// 他のコンテナを待たせずに`stale`としてマークします。
     #[instrument(skip(self, containers), fields(container_count = containers.len()), level = "debug")]
-    pub async fn collect_container_stats(&self, containers: &mut [ContainerInfo]) -> Result<()> {
+    pub async fn collect_container_stats(
+        &self,
+        containers: &mut [ContainerInfo],
+        max_concurrency: usize,
+        timeout: Duration,
+    ) -> Result<()> {
         debug!("Collecting stats for {} containers", containers.len());
         let running_containers = containers.iter()
             .filter(|c| c.status == "running")
             .count();
         debug!("Found {} running containers", running_containers);
         
-        // Collect stats for each container concurrently using a vector
-        let stats_futures = containers.iter()
+        // Collect stats concurrently, at most max_concurrency requests in flight
+        let container_ids: Vec<String> = containers.iter()
             .filter(|c| c.status == "running")
-            .map(|container| {
-                let container_id = container.id.clone();
+            .map(|c| c.id.clone())
+            .collect();
+        
+        let results: Vec<(String, Option<Result<ContainerStats>>)> = futures::stream::iter(container_ids)
+            .map(|container_id| {
                 let client = self.client.clone();
                 
                 async move {
-                    let stats_result = Self::get_container_stats(&client, &container_id).await;
+                    // None means the request did not finish within the timeout
+                    let stats_result = tokio::time::timeout(timeout, Self::get_container_stats(&client, &container_id))
+                        .await
+                        .ok();
                     (container_id, stats_result)
                 }
             })
-            .collect::<Vec<_>>();
-        
-        // Wait for all stats collection to complete
-        let results: Vec<(String, Result<ContainerStats>)> = futures::future::join_all(stats_futures).await;
+            .buffer_unordered(max_concurrency.max(1))
+            .collect()
+            .await;
         
         // Create a map of container ID to stats
-        let stats_map: HashMap<String, ContainerStats> = results
-            .into_iter()
-            .filter_map(|(id, result)| {
-                match result {
-                    Ok(stats) => Some((id, stats)),
-                    Err(e) => {
-                        error!("Failed to collect stats for container {}: {}", id, e);
-                        None
-                    }
+        let mut stats_map: HashMap<String, ContainerStats> = HashMap::new();
+        let mut stale_ids = Vec::new();
+        for (id, result) in results {
+            match result {
+                Some(Ok(stats)) => {
+                    stats_map.insert(id, stats);
                 }
-            })
-            .collect();
+                Some(Err(e)) => {
+                    error!("Failed to collect stats for container {}: {}", id, e);
+                }
+                None => {
+                    warn!("Timed out after {:?} collecting stats for container {}", timeout, id);
+                    stale_ids.push(id);
+                }
+            }
+        }
         
         // Update container stats
+        let collected = stats_map.len();
         for container in containers.iter_mut() {
-            if let Some(stats) = stats_map.get(&container.id) {
-                container.stats = Some(stats.clone());
+            if let Some(stats) = stats_map.remove(&container.id) {
+                container.stats = Some(stats);
             }
+            container.stale = stale_ids.contains(&container.id);
         }
         
-        debug!("Successfully collected stats for {} containers", stats_map.len());
+        debug!(
+            collected,
+            stale = stale_ids.len(),
+            "Container stats collection finished"
+        );
         Ok(())
     }
     
@@ -330,18 +703,10 @@ impl DockerClient {
             })
             .unwrap_or((0, 0));
         
-        // ブロックI/O統計情報の集計
-        let (block_read_bytes, block_write_bytes) = if let Some(io_stats) = &stats.blkio_stats.io_service_bytes_recursive {
-            io_stats.iter().fold((0, 0), |(read, write), io_stat| {
-                match io_stat.op.as_deref() {
-                    Some("Read") => (read + io_stat.value.unwrap_or(0), write),
-                    Some("Write") => (read, write + io_stat.value.unwrap_or(0)),
-                    _ => (read, write),
-                }
-            })
-        } else {
-            (0, 0)
-        };
+        // ブロックI/O統計情報の集計（デバイスごと、および全デバイスの合計）
+        let block_devices = Self::parse_block_io(&stats.blkio_stats);
+        let block_read_bytes = block_devices.iter().map(|device| device.read_bytes).sum();
+        let block_write_bytes = block_devices.iter().map(|device| device.write_bytes).sum();
         
         ContainerStats {
             cpu_usage_percent,
@@ -352,7 +717,59 @@ impl DockerClient {
             network_tx_bytes,
             block_read_bytes,
             block_write_bytes,
+            block_devices,
             pids: stats.pids_stats.current.unwrap_or(0),
         }
     }
+    
+    /// blkio統計情報をデバイスごとに集計
+    ///
+    /// cgroup v1では操作名が"Read"/"Write"/"Discard"（"Sync"や"Total"なども含む）、
+    /// cgroup v2ではio.statから変換された"read"/"write"となるため、大文字小文字を区別せずに扱います。
+    pub fn parse_block_io(blkio_stats: &BlkioStats) -> Vec<BlockDeviceStats> {
+        let mut devices: BTreeMap<(u64, u64), BlockDeviceStats> = BTreeMap::new();
+        
+        let sources = [
+            (&blkio_stats.io_service_bytes_recursive, true),
+            (&blkio_stats.io_serviced_recursive, false),
+        ];
+        
+        for (entries, is_bytes) in sources {
+            for entry in entries.iter().flatten() {
+                let BlkioStatsEntry { major, minor, op, value } = entry;
+                let device = devices.entry((*major, *minor)).or_insert_with(|| BlockDeviceStats {
+                    major: *major,
+                    minor: *minor,
+                    ..Default::default()
+                });
+                
+                let counter = match (op.to_ascii_lowercase().as_str(), is_bytes) {
+                    ("read", true) => &mut device.read_bytes,
+                    ("write", true) => &mut device.write_bytes,
+                    ("discard", true) => &mut device.discard_bytes,
+                    ("read", false) => &mut device.read_ops,
+                    ("write", false) => &mut device.write_ops,
+                    ("discard", false) => &mut device.discard_ops,
+                    _ => continue,
+                };
+                *counter += value;
+            }
+        }
+        
+        devices.into_values().collect()
+    }
+}
+
+/// `<sysfs>/dev/block/<major>:<minor>/uevent` のDEVNAMEからブロックデバイス名を解決
+pub fn block_device_name(sysfs_root: &Path, major: u64, minor: u64) -> Option<String> {
+    let uevent = sysfs_root
+        .join("dev/block")
+        .join(format!("{}:{}", major, minor))
+        .join("uevent");
+    
+    std::fs::read_to_string(uevent)
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("DEVNAME="))
+        .map(|name| name.trim().to_string())
 }
\ No newline at end of file
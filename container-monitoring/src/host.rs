@@ -0,0 +1,395 @@
+use anyhow::{Context, Result};
+use opentelemetry::metrics::{Counter, Meter, ObservableGauge, Unit};
+use opentelemetry::KeyValue;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tracing::{debug, instrument};
+
+/// /proc/statのCPU時間の単位（USER_HZ。Linuxでは常に100）
+const USER_HZ: f64 = 100.0;
+
+/// /proc/diskstatsのセクタサイズ（デバイスに関わらず512バイト単位で報告される）
+const SECTOR_SIZE: u64 = 512;
+
+/// /proc/statから取得したCPU時間（全CPUの合計、USER_HZ単位の累計）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CpuTimes {
+    pub user: u64,
+    pub nice: u64,
+    pub system: u64,
+    pub idle: u64,
+    pub iowait: u64,
+    pub irq: u64,
+    pub softirq: u64,
+    pub steal: u64,
+}
+
+impl CpuTimes {
+    // モード名と値の組
+    fn modes(&self) -> [(&'static str, u64); 8] {
+        [
+            ("user", self.user),
+            ("nice", self.nice),
+            ("system", self.system),
+            ("idle", self.idle),
+            ("iowait", self.iowait),
+            ("irq", self.irq),
+            ("softirq", self.softirq),
+            ("steal", self.steal),
+        ]
+    }
+}
+
+/// /proc/meminfoから取得したメモリ情報（バイト）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryInfo {
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+    pub available_bytes: u64,
+    pub buffers_bytes: u64,
+    pub cached_bytes: u64,
+    pub swap_total_bytes: u64,
+    pub swap_free_bytes: u64,
+}
+
+/// /proc/loadavgから取得したロードアベレージ
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadAverage {
+    pub load1: f64,
+    pub load5: f64,
+    pub load15: f64,
+}
+
+/// /proc/net/devから取得したネットワークインターフェースごとの統計情報（累計）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetworkInterfaceStats {
+    pub interface: String,
+    pub receive_bytes: u64,
+    pub transmit_bytes: u64,
+}
+
+/// /proc/diskstatsから取得したブロックデバイスごとの統計情報（累計）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiskStats {
+    pub device: String,
+    pub reads_completed: u64,
+    pub writes_completed: u64,
+    pub read_bytes: u64,
+    pub written_bytes: u64,
+}
+
+/// procfsから読み取ったホスト全体の統計情報
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostStats {
+    pub cpu: CpuTimes,
+    pub cpu_count: usize,
+    pub memory: MemoryInfo,
+    pub load: LoadAverage,
+    pub networks: Vec<NetworkInterfaceStats>,
+    pub disks: Vec<DiskStats>,
+}
+
+impl HostStats {
+    /// procfsのルート（通常は/proc、コンテナ内ではホストの/procをマウントしたパス）から読み取り
+    pub fn read(procfs_root: &Path) -> Result<Self> {
+        let (cpu, cpu_count) = parse_stat(&read_procfs(procfs_root, "stat")?);
+
+        Ok(Self {
+            cpu,
+            cpu_count,
+            memory: parse_meminfo(&read_procfs(procfs_root, "meminfo")?),
+            load: parse_loadavg(&read_procfs(procfs_root, "loadavg")?)?,
+            networks: parse_net_dev(&read_procfs(procfs_root, "net/dev")?),
+            disks: parse_diskstats(&read_procfs(procfs_root, "diskstats")?),
+        })
+    }
+}
+
+// procfs内のファイルを読み取り
+fn read_procfs(procfs_root: &Path, name: &str) -> Result<String> {
+    let path = procfs_root.join(name);
+    std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))
+}
+
+// /proc/statの"cpu"行（全CPUの合計）と"cpuN"行の数を解析
+fn parse_stat(content: &str) -> (CpuTimes, usize) {
+    let mut cpu = CpuTimes::default();
+    let mut cpu_count = 0;
+
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("cpu") => {
+                let values: Vec<u64> = fields.map(|v| v.parse().unwrap_or(0)).collect();
+                let value = |index: usize| values.get(index).copied().unwrap_or(0);
+                cpu = CpuTimes {
+                    user: value(0),
+                    nice: value(1),
+                    system: value(2),
+                    idle: value(3),
+                    iowait: value(4),
+                    irq: value(5),
+                    softirq: value(6),
+                    steal: value(7),
+                };
+            }
+            Some(name) if name.starts_with("cpu") => cpu_count += 1,
+            _ => {}
+        }
+    }
+
+    (cpu, cpu_count)
+}
+
+// /proc/meminfoを解析（値はkB単位）
+fn parse_meminfo(content: &str) -> MemoryInfo {
+    let values: HashMap<&str, u64> = content
+        .lines()
+        .filter_map(|line| {
+            let (key, rest) = line.split_once(':')?;
+            let value: u64 = rest.split_whitespace().next()?.parse().ok()?;
+            Some((key, value * 1024))
+        })
+        .collect();
+    let value = |key: &str| values.get(key).copied().unwrap_or(0);
+
+    MemoryInfo {
+        total_bytes: value("MemTotal"),
+        free_bytes: value("MemFree"),
+        available_bytes: value("MemAvailable"),
+        buffers_bytes: value("Buffers"),
+        cached_bytes: value("Cached"),
+        swap_total_bytes: value("SwapTotal"),
+        swap_free_bytes: value("SwapFree"),
+    }
+}
+
+// /proc/loadavgを解析
+fn parse_loadavg(content: &str) -> Result<LoadAverage> {
+    let mut fields = content.split_whitespace().map(str::parse::<f64>);
+    let mut next = || -> Result<f64> {
+        fields
+            .next()
+            .context("Missing load average field")?
+            .context("Invalid load average value")
+    };
+
+    Ok(LoadAverage {
+        load1: next()?,
+        load5: next()?,
+        load15: next()?,
+    })
+}
+
+// /proc/net/devを解析（先頭2行はヘッダー）
+fn parse_net_dev(content: &str) -> Vec<NetworkInterfaceStats> {
+    content
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (interface, rest) = line.split_once(':')?;
+            let values: Vec<u64> = rest.split_whitespace().map(|v| v.parse().unwrap_or(0)).collect();
+            Some(NetworkInterfaceStats {
+                interface: interface.trim().to_string(),
+                receive_bytes: *values.first()?,
+                transmit_bytes: *values.get(8)?,
+            })
+        })
+        .collect()
+}
+
+// /proc/diskstatsを解析（loopデバイスとRAMディスクは除外）
+fn parse_diskstats(content: &str) -> Vec<DiskStats> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let device = *fields.get(2)?;
+            if device.starts_with("loop") || device.starts_with("ram") {
+                return None;
+            }
+            let value = |index: usize| fields.get(index).and_then(|v| v.parse::<u64>().ok());
+
+            Some(DiskStats {
+                device: device.to_string(),
+                reads_completed: value(3)?,
+                read_bytes: value(5)? * SECTOR_SIZE,
+                writes_completed: value(7)?,
+                written_bytes: value(9)? * SECTOR_SIZE,
+            })
+        })
+        .collect()
+}
+
+// ゲージ型メトリクスのコールバックが参照する最新の統計情報
+type HostSnapshot = Arc<RwLock<Option<HostStats>>>;
+
+// ゲージ名、説明、単位と、統計情報から値を取り出す関数
+type HostGauge = (&'static str, &'static str, Option<&'static str>, fn(&HostStats) -> f64);
+
+/// ホストコレクター - procfsからホスト全体のCPU、メモリ、ロード、ネットワーク、ディスクを収集
+pub struct HostCollector {
+    procfs_root: PathBuf,
+
+    // カウンター型メトリクス
+    cpu_seconds: Counter<f64>,
+    network_receive_bytes: Counter<u64>,
+    network_transmit_bytes: Counter<u64>,
+    disk_read_bytes: Counter<u64>,
+    disk_written_bytes: Counter<u64>,
+    disk_reads_completed: Counter<u64>,
+    disk_writes_completed: Counter<u64>,
+
+    // カウンター型メトリクスのための前回値（デルタ計算用）
+    prev_values: HashMap<String, u64>,
+
+    // ゲージ型メトリクスが報告する最新の統計情報
+    snapshot: HostSnapshot,
+    _gauges: Vec<ObservableGauge<f64>>,
+}
+
+impl HostCollector {
+    /// 新しいホストコレクターを作成
+    pub fn new(procfs_root: impl Into<PathBuf>) -> Self {
+        Self::new_with_meter(procfs_root, &opentelemetry::global::meter("container-monitoring"))
+    }
+
+    /// メトリクスを記録するメーターを指定してホストコレクターを作成
+    pub fn new_with_meter(procfs_root: impl Into<PathBuf>, meter: &Meter) -> Self {
+        let snapshot = Arc::new(RwLock::new(None));
+
+        Self {
+            procfs_root: procfs_root.into(),
+            cpu_seconds: meter
+                .f64_counter("host_cpu_seconds_total")
+                .with_description("CPU time spent in each mode across all CPUs")
+                .with_unit(Unit::new("s"))
+                .init(),
+            network_receive_bytes: Self::bytes_counter(meter, "host_network_receive_bytes_total", "Network bytes received per interface"),
+            network_transmit_bytes: Self::bytes_counter(meter, "host_network_transmit_bytes_total", "Network bytes transmitted per interface"),
+            disk_read_bytes: Self::bytes_counter(meter, "host_disk_read_bytes_total", "Bytes read per block device"),
+            disk_written_bytes: Self::bytes_counter(meter, "host_disk_written_bytes_total", "Bytes written per block device"),
+            disk_reads_completed: meter
+                .u64_counter("host_disk_reads_completed_total")
+                .with_description("Reads completed per block device")
+                .init(),
+            disk_writes_completed: meter
+                .u64_counter("host_disk_writes_completed_total")
+                .with_description("Writes completed per block device")
+                .init(),
+            prev_values: HashMap::new(),
+            _gauges: Self::init_gauges(meter, &snapshot),
+            snapshot,
+        }
+    }
+
+    // バイト単位のカウンターを作成
+    fn bytes_counter(meter: &Meter, name: &'static str, description: &'static str) -> Counter<u64> {
+        meter
+            .u64_counter(name)
+            .with_description(description)
+            .with_unit(Unit::new("By"))
+            .init()
+    }
+
+    // 最新の統計情報を報告するゲージを初期化
+    fn init_gauges(meter: &Meter, snapshot: &HostSnapshot) -> Vec<ObservableGauge<f64>> {
+        let gauges: [HostGauge; 11] = [
+            ("host_cpu_count", "Number of logical CPUs", None, |s| s.cpu_count as f64),
+            ("host_memory_total_bytes", "Total usable memory", Some("By"), |s| s.memory.total_bytes as f64),
+            ("host_memory_free_bytes", "Unused memory", Some("By"), |s| s.memory.free_bytes as f64),
+            ("host_memory_available_bytes", "Memory available for new workloads without swapping", Some("By"), |s| s.memory.available_bytes as f64),
+            ("host_memory_buffers_bytes", "Memory used by kernel buffers", Some("By"), |s| s.memory.buffers_bytes as f64),
+            ("host_memory_cached_bytes", "Memory used by the page cache", Some("By"), |s| s.memory.cached_bytes as f64),
+            ("host_swap_total_bytes", "Total swap space", Some("By"), |s| s.memory.swap_total_bytes as f64),
+            ("host_swap_free_bytes", "Unused swap space", Some("By"), |s| s.memory.swap_free_bytes as f64),
+            ("host_load1", "1-minute load average", None, |s| s.load.load1),
+            ("host_load5", "5-minute load average", None, |s| s.load.load5),
+            ("host_load15", "15-minute load average", None, |s| s.load.load15),
+        ];
+
+        gauges
+            .into_iter()
+            .map(|(name, description, unit, value)| {
+                let snapshot = snapshot.clone();
+                let mut builder = meter
+                    .f64_observable_gauge(name)
+                    .with_description(description);
+                if let Some(unit) = unit {
+                    builder = builder.with_unit(Unit::new(unit));
+                }
+
+                builder
+                    .with_callback(move |instrument| {
+                        if let Ok(snapshot) = snapshot.read() {
+                            if let Some(stats) = snapshot.as_ref() {
+                                instrument.observe(value(stats), &[]);
+                            }
+                        }
+                    })
+                    .init()
+            })
+            .collect()
+    }
+
+    /// procfsを読み取り、メトリクスを記録（ファイルの読み取りはブロッキングスレッドで行う）
+    #[instrument(skip(self), fields(procfs_root = %self.procfs_root.display()), level = "debug")]
+    pub async fn collect(&mut self) -> Result<()> {
+        let procfs_root = self.procfs_root.clone();
+        let stats = tokio::task::spawn_blocking(move || HostStats::read(&procfs_root))
+            .await
+            .context("Failed to read procfs")??;
+
+        for (mode, ticks) in stats.cpu.modes() {
+            let delta = self.delta(format!("cpu/{}", mode), ticks);
+            if delta > 0 {
+                self.cpu_seconds.add(delta as f64 / USER_HZ, &[KeyValue::new("mode", mode)]);
+            }
+        }
+
+        for network in &stats.networks {
+            let labels = [KeyValue::new("interface", network.interface.clone())];
+            let rx_delta = self.delta(format!("net/{}/rx", network.interface), network.receive_bytes);
+            let tx_delta = self.delta(format!("net/{}/tx", network.interface), network.transmit_bytes);
+            self.network_receive_bytes.add(rx_delta, &labels);
+            self.network_transmit_bytes.add(tx_delta, &labels);
+        }
+
+        for disk in &stats.disks {
+            let labels = [KeyValue::new("device", disk.device.clone())];
+            let read_delta = self.delta(format!("disk/{}/read_bytes", disk.device), disk.read_bytes);
+            let written_delta = self.delta(format!("disk/{}/written_bytes", disk.device), disk.written_bytes);
+            let reads_delta = self.delta(format!("disk/{}/reads", disk.device), disk.reads_completed);
+            let writes_delta = self.delta(format!("disk/{}/writes", disk.device), disk.writes_completed);
+            self.disk_read_bytes.add(read_delta, &labels);
+            self.disk_written_bytes.add(written_delta, &labels);
+            self.disk_reads_completed.add(reads_delta, &labels);
+            self.disk_writes_completed.add(writes_delta, &labels);
+        }
+
+        debug!(
+            cpu_count = stats.cpu_count,
+            memory_available_mb = stats.memory.available_bytes / (1024 * 1024),
+            load1 = stats.load.load1,
+            "Host stats collected"
+        );
+
+        if let Ok(mut snapshot) = self.snapshot.write() {
+            *snapshot = Some(stats);
+        }
+
+        Ok(())
+    }
+
+    // 前回値との差分を計算（カウンターリセット時は現在値）
+    fn delta(&mut self, key: String, current_value: u64) -> u64 {
+        let prev_value = self.prev_values.insert(key, current_value).unwrap_or(0);
+        if current_value >= prev_value {
+            current_value - prev_value
+        } else {
+            current_value
+        }
+    }
+}
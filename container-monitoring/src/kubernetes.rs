@@ -0,0 +1,324 @@
+use anyhow::{bail, Context, Result};
+use opentelemetry::KeyValue;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+use crate::config::KubernetesConfig;
+use crate::docker::ContainerInfo;
+
+/// kubelet（dockershim/cri-dockerd）がコンテナに付与するラベル
+pub const POD_NAME_LABEL: &str = "io.kubernetes.pod.name";
+pub const POD_NAMESPACE_LABEL: &str = "io.kubernetes.pod.namespace";
+pub const POD_UID_LABEL: &str = "io.kubernetes.pod.uid";
+pub const CONTAINER_NAME_LABEL: &str = "io.kubernetes.container.name";
+/// pauseコンテナ（ポッドのサンドボックス）では"podsandbox"
+pub const CONTAINER_TYPE_LABEL: &str = "io.kubernetes.docker.type";
+
+/// 未知のポッドを見つけた場合にkubeletへ問い合わせる最短の間隔
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// コンテナが属するポッドの情報
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PodInfo {
+    pub name: String,
+    pub namespace: String,
+    pub uid: String,
+    /// ポッドを管理するワークロード（Deployment、StatefulSet、DaemonSet、Jobなど）の名前
+    pub workload: Option<String>,
+    pub node: Option<String>,
+}
+
+impl PodInfo {
+    /// メトリクスに付与するラベル（不明なworkloadとnodeは付与しない）
+    pub fn labels(&self) -> Vec<KeyValue> {
+        let mut labels = vec![
+            KeyValue::new("pod", self.name.clone()),
+            KeyValue::new("namespace", self.namespace.clone()),
+        ];
+        if let Some(workload) = &self.workload {
+            labels.push(KeyValue::new("workload", workload.clone()));
+        }
+        if let Some(node) = &self.node {
+            labels.push(KeyValue::new("node", node.clone()));
+        }
+        labels
+    }
+}
+
+/// コンテナのラベルまたは名前から読み取ったポッドの参照
+#[derive(Debug, Clone, PartialEq)]
+pub struct PodRef {
+    pub pod: String,
+    pub namespace: String,
+    pub uid: String,
+    /// ポッド内のコンテナ名（pauseコンテナは"POD"）
+    pub container: String,
+}
+
+/// コンテナが属するポッドを取得
+///
+/// kubeletが付与する`io.kubernetes.*`ラベルを優先し、ない場合は
+/// `k8s_<コンテナ>_<ポッド>_<namespace>_<uid>_<試行回数>`形式のコンテナ名から読み取ります。
+pub fn pod_ref(container: &ContainerInfo) -> Option<PodRef> {
+    let labels = &container.labels;
+    if let (Some(pod), Some(namespace)) = (labels.get(POD_NAME_LABEL), labels.get(POD_NAMESPACE_LABEL)) {
+        return Some(PodRef {
+            pod: pod.clone(),
+            namespace: namespace.clone(),
+            uid: labels.get(POD_UID_LABEL).cloned().unwrap_or_default(),
+            container: labels.get(CONTAINER_NAME_LABEL).cloned().unwrap_or_else(|| "POD".to_string()),
+        });
+    }
+
+    let parts: Vec<&str> = container.name.split('_').collect();
+    match parts.as_slice() {
+        ["k8s", container, pod, namespace, uid, _attempt] => Some(PodRef {
+            pod: pod.to_string(),
+            namespace: namespace.to_string(),
+            uid: uid.to_string(),
+            container: container.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// ポッドのサンドボックスを保持するpauseコンテナか判定
+pub fn is_pause_container(container: &ContainerInfo) -> bool {
+    if let Some(kind) = container.labels.get(CONTAINER_TYPE_LABEL) {
+        return kind == "podsandbox";
+    }
+    pod_ref(container).is_some_and(|pod| pod.container == "POD")
+}
+
+/// ポッド名とポッドのラベルからワークロード名を推定（kubeletに問い合わせない場合）
+///
+/// - `pod-template-hash`があればDeployment（`<name>-<hash>-<suffix>`）
+/// - `controller-revision-hash`があり末尾が番号ならStatefulSet（`<name>-<ordinal>`）、それ以外はDaemonSet（`<name>-<suffix>`）
+pub fn workload_from_pod_name(pod: &str, labels: &HashMap<String, String>) -> Option<String> {
+    if let Some(hash) = labels.get("pod-template-hash") {
+        let (replica_set, _) = pod.rsplit_once('-')?;
+        return replica_set.strip_suffix(&format!("-{}", hash)).map(str::to_string);
+    }
+    if labels.contains_key("controller-revision-hash") {
+        let (workload, _) = pod.rsplit_once('-')?;
+        return Some(workload.to_string());
+    }
+    None
+}
+
+// kubeletの/podsの応答（使用するフィールドのみ）
+#[derive(Deserialize)]
+struct PodList {
+    #[serde(default)]
+    items: Vec<Pod>,
+}
+
+#[derive(Deserialize)]
+struct Pod {
+    metadata: PodMetadata,
+    #[serde(default)]
+    spec: PodSpec,
+}
+
+#[derive(Deserialize)]
+struct PodMetadata {
+    name: String,
+    namespace: String,
+    uid: String,
+    #[serde(default)]
+    labels: HashMap<String, String>,
+    #[serde(default, rename = "ownerReferences")]
+    owner_references: Vec<OwnerReference>,
+}
+
+#[derive(Deserialize)]
+struct OwnerReference {
+    kind: String,
+    name: String,
+    #[serde(default)]
+    controller: bool,
+}
+
+#[derive(Deserialize, Default)]
+struct PodSpec {
+    #[serde(default, rename = "nodeName")]
+    node_name: Option<String>,
+}
+
+// ポッドを管理するワークロード名（ReplicaSetはDeployment名に置き換える）
+fn owner_workload(metadata: &PodMetadata) -> Option<String> {
+    let owner = metadata.owner_references.iter()
+        .find(|owner| owner.controller)
+        .or_else(|| metadata.owner_references.first())?;
+
+    if owner.kind == "ReplicaSet" {
+        if let Some(deployment) = metadata.labels.get("pod-template-hash")
+            .and_then(|hash| owner.name.strip_suffix(&format!("-{}", hash)))
+        {
+            return Some(deployment.to_string());
+        }
+    }
+    Some(owner.name.clone())
+}
+
+/// kubeletの/podsの応答をポッドのUIDごとの情報に変換
+pub fn parse_pods(body: &[u8]) -> Result<HashMap<String, PodInfo>> {
+    let pods: PodList = serde_json::from_slice(body).context("Failed to parse kubelet pod list")?;
+    Ok(pods.items
+        .into_iter()
+        .map(|pod| {
+            let info = PodInfo {
+                workload: owner_workload(&pod.metadata),
+                name: pod.metadata.name,
+                namespace: pod.metadata.namespace,
+                uid: pod.metadata.uid.clone(),
+                node: pod.spec.node_name,
+            };
+            (pod.metadata.uid, info)
+        })
+        .collect())
+}
+
+/// コンテナにポッド情報を付与するリゾルバー
+///
+/// kubeletの/podsを`refresh_interval`ごとに取得してキャッシュします。
+/// kubeletに問い合わせられない場合やkubeletが知らないポッドは、コンテナのラベルと名前から
+/// ポッド名・namespace・推定したワークロード名を付与します。
+pub struct PodResolver {
+    config: KubernetesConfig,
+    client: Option<reqwest::Client>,
+    pods: HashMap<String, PodInfo>,
+    // 前回の更新後も一覧になかったポッドのUID（削除されたポッドの終了したコンテナなど）
+    missing: HashSet<String>,
+    refreshed_at: Option<Instant>,
+}
+
+impl PodResolver {
+    /// 設定からリゾルバーを作成
+    pub fn new(config: &KubernetesConfig) -> Result<Self> {
+        let client = match config.source.as_str() {
+            "kubelet" => Some(kubelet_client(config)?),
+            "labels" => None,
+            other => bail!("Unsupported kubernetes source '{}' (expected kubelet or labels)", other),
+        };
+
+        Ok(Self {
+            config: config.clone(),
+            client,
+            pods: HashMap::new(),
+            missing: HashSet::new(),
+            refreshed_at: None,
+        })
+    }
+
+    /// コンテナにポッド情報を付与し、pauseコンテナを除外
+    pub async fn enrich(&mut self, containers: &mut Vec<ContainerInfo>) {
+        let refs: Vec<Option<PodRef>> = containers.iter().map(pod_ref).collect();
+        let unknown = refs.iter().flatten().any(|pod| !self.pods.contains_key(&pod.uid) && !self.missing.contains(&pod.uid));
+        if self.refresh_due(unknown) {
+            self.refresh().await;
+            // 更新後も一覧にないポッドでは、最短の間隔での更新を繰り返さない
+            self.missing = refs.iter()
+                .flatten()
+                .filter(|pod| !self.pods.contains_key(&pod.uid))
+                .map(|pod| pod.uid.clone())
+                .collect();
+        }
+
+        // pauseコンテナにはポッドのラベル（pod-template-hashなど）が付与されている
+        let sandbox_labels: HashMap<String, HashMap<String, String>> = containers.iter()
+            .filter(|container| is_pause_container(container))
+            .filter_map(|container| Some((pod_ref(container)?.uid, container.labels.clone())))
+            .collect();
+
+        for (container, pod) in containers.iter_mut().zip(refs) {
+            container.pod = pod.map(|pod| {
+                self.pods.get(&pod.uid).cloned().unwrap_or_else(|| {
+                    let labels = sandbox_labels.get(&pod.uid).unwrap_or(&container.labels);
+                    PodInfo {
+                        workload: workload_from_pod_name(&pod.pod, labels),
+                        name: pod.pod,
+                        namespace: pod.namespace,
+                        uid: pod.uid,
+                        node: None,
+                    }
+                })
+            });
+            // 設定のノード名はspec.nodeNameより優先
+            if let (Some(pod), Some(node)) = (&mut container.pod, &self.config.node_name) {
+                pod.node = Some(node.clone());
+            }
+        }
+
+        containers.retain(|container| !is_pause_container(container));
+    }
+
+    // 更新間隔が経過したか、新しい未知のポッドがあり最短の間隔が経過していれば更新
+    fn refresh_due(&self, unknown: bool) -> bool {
+        if self.client.is_none() {
+            return false;
+        }
+        match self.refreshed_at {
+            None => true,
+            Some(refreshed_at) => {
+                let elapsed = refreshed_at.elapsed();
+                elapsed >= Duration::from_secs(self.config.refresh_interval)
+                    || (unknown && elapsed >= MIN_REFRESH_INTERVAL)
+            }
+        }
+    }
+
+    // kubeletからポッド一覧を取得（失敗した場合は前回の一覧を使い続ける）
+    async fn refresh(&mut self) {
+        self.refreshed_at = Some(Instant::now());
+        match self.fetch_pods().await {
+            Ok(pods) => {
+                debug!(pods = pods.len(), "Kubelet pod list refreshed");
+                self.pods = pods;
+            }
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+
+    async fn fetch_pods(&self) -> Result<HashMap<String, PodInfo>> {
+        let Some(client) = &self.client else {
+            return Ok(HashMap::new());
+        };
+
+        let url = format!("{}/pods", self.config.kubelet_url.trim_end_matches('/'));
+        let mut request = client.get(&url);
+        if let Some(token_path) = &self.config.token_path {
+            let token = std::fs::read_to_string(token_path)
+                .with_context(|| format!("Failed to read kubelet token: {}", token_path))?;
+            request = request.bearer_auth(token.trim());
+        }
+
+        let body = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to list pods from kubelet at {}", url))?
+            .bytes()
+            .await
+            .with_context(|| format!("Failed to read kubelet response from {}", url))?;
+        parse_pods(&body)
+    }
+}
+
+// kubeletへのHTTPクライアント
+fn kubelet_client(config: &KubernetesConfig) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .danger_accept_invalid_certs(config.insecure_skip_verify);
+
+    if let Some(ca_path) = &config.ca_path {
+        let ca = std::fs::read(ca_path).with_context(|| format!("Failed to read kubelet CA certificate: {}", ca_path))?;
+        let ca = reqwest::Certificate::from_pem(&ca)
+            .with_context(|| format!("Invalid kubelet CA certificate: {}", ca_path))?;
+        builder = builder.add_root_certificate(ca);
+    }
+
+    builder.build().context("Failed to build kubelet HTTP client")
+}
@@ -1,23 +1,45 @@
-// lib.rsは統合テスト用のライブラリとしてプロジェクトの各モジュールをエクスポートします
+// lib.rsはプロジェクトの各モジュールをエクスポートします
+// 他のサービスに組み込む場合はMonitorBuilderを、統合テストでは各モジュールを直接使用します
 
 // 各モジュールを公開
+pub mod agent;
+pub mod cardinality;
 pub mod config;
 pub mod docker;
+pub mod host;
+pub mod kubernetes;
+pub mod log_export;
+pub mod logs;
 pub mod metrics;
+pub mod monitor;
+#[cfg(feature = "profiling")]
+pub mod profiling;
+pub mod remote_write;
+pub mod resource;
+pub mod schedule;
 pub mod telemetry;
 pub mod server;
+pub mod sinks;
+pub mod ssh;
+pub mod state;
+pub mod stream;
 
 // 主要な型やトレイトを再エクスポート
+pub use agent::AgentCollector;
 pub use config::{Config, load_config};
 pub use docker::{DockerClient, ContainerInfo, ContainerStats};
+pub use host::HostCollector;
 pub use metrics::MetricsCollector;
+pub use monitor::{HostSnapshot, Monitor, MonitorBuilder, Snapshot};
 pub use telemetry::init_telemetry;
 pub use server::start_metrics_server;
+pub use stream::{StreamEvent, StreamFilter};
 
 // コンテナモニタリングライブラリのバージョン情報
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-// ライブラリの初期化関数
+// ライブラリの初期化関数（標準出力に表示するだけで何も初期化しません）
+#[deprecated(note = "use MonitorBuilder to embed the collector")]
 pub fn init() {
     println!("Container Monitoring Library v{} initialized", VERSION);
 }
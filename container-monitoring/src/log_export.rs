@@ -0,0 +1,267 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use opentelemetry::logs::{AnyValue, LogRecord, LogResult, Logger as _, LoggerProvider as _, Severity};
+use opentelemetry::{Key, KeyValue};
+use opentelemetry_sdk::export::logs::{LogData, LogExporter};
+use opentelemetry_sdk::logs::{BatchLogProcessor, Logger, LoggerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::config::{LogExportConfig, TelemetryConfig};
+use crate::docker::{ContainerInfo, LogStream};
+use crate::resource::telemetry_resource;
+use crate::telemetry::build_log_exporter;
+
+/// リソース属性に含めるDocker Composeのラベル
+const COMPOSE_LABELS: [&str; 4] = [
+    "com.docker.compose.project",
+    "com.docker.compose.service",
+    "com.docker.compose.container-number",
+    "com.docker.compose.version",
+];
+
+/// JSON行の本文として扱うフィールド（先に見つかったもの）
+const MESSAGE_FIELDS: [&str; 3] = ["message", "msg", "log"];
+
+/// JSON行の重大度として扱うフィールド（先に見つかったもの）
+const LEVEL_FIELDS: [&str; 3] = ["level", "severity", "lvl"];
+
+// コンテナIDごとのリソース
+type ContainerResources = Arc<RwLock<HashMap<String, Resource>>>;
+
+/// コンテナのリソース属性（container.id, container.name, container.image.name, Composeラベル）
+pub fn container_resource(host: &str, container: &ContainerInfo) -> Resource {
+    let mut attributes = vec![
+        KeyValue::new("container.id", container.id.clone()),
+        KeyValue::new("container.name", container.name.clone()),
+        KeyValue::new("container.image.name", container.image.clone()),
+        KeyValue::new("docker.host", host.to_string()),
+    ];
+
+    for label in COMPOSE_LABELS {
+        if let Some(value) = container.labels.get(label) {
+            attributes.push(KeyValue::new(label, value.clone()));
+        }
+    }
+
+    Resource::new(attributes)
+}
+
+/// ログ行をログレコードに変換
+///
+/// `parse_json`が有効でJSONオブジェクトとして解析できる行は、message/msg/logを本文、
+/// level/severity/lvlを重大度とし、残りのフィールドを属性にします。
+pub fn log_record(container_id: &str, stream: LogStream, line: &str, parse_json: bool) -> LogRecord {
+    let mut attributes = vec![
+        (Key::new("container.id"), AnyValue::from(container_id.to_string())),
+        (Key::new("log.iostream"), AnyValue::from(stream.as_str())),
+    ];
+
+    let fields = if parse_json { parse_json_object(line) } else { None };
+    let Some(mut fields) = fields else {
+        return LogRecord::builder()
+            .with_body(AnyValue::from(line.to_string()))
+            .with_attributes(attributes)
+            .build();
+    };
+
+    let body = MESSAGE_FIELDS
+        .iter()
+        .find_map(|field| match fields.get(*field) {
+            Some(serde_json::Value::String(_)) => fields.remove(*field),
+            _ => None,
+        })
+        .map(json_to_any_value)
+        .unwrap_or_else(|| AnyValue::from(line.to_string()));
+
+    let level = LEVEL_FIELDS.iter().find_map(|field| match fields.get(*field) {
+        Some(serde_json::Value::String(level)) => Some(level.clone()),
+        _ => None,
+    });
+
+    attributes.extend(
+        fields
+            .into_iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| (Key::new(key), json_to_any_value(value))),
+    );
+
+    let mut builder = LogRecord::builder().with_body(body).with_attributes(attributes);
+    if let Some(level) = level {
+        if let Some(severity) = severity_from_level(&level) {
+            builder = builder.with_severity_number(severity);
+        }
+        builder = builder.with_severity_text(level);
+    }
+
+    builder.build()
+}
+
+// JSONオブジェクトの行を解析
+fn parse_json_object(line: &str) -> Option<serde_json::Map<String, serde_json::Value>> {
+    if !line.trim_start().starts_with('{') {
+        return None;
+    }
+
+    match serde_json::from_str(line) {
+        Ok(serde_json::Value::Object(fields)) => Some(fields),
+        _ => None,
+    }
+}
+
+// JSONの値をログの値に変換
+fn json_to_any_value(value: serde_json::Value) -> AnyValue {
+    match value {
+        serde_json::Value::Null => AnyValue::from(""),
+        serde_json::Value::Bool(value) => AnyValue::Boolean(value),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(value) => AnyValue::Int(value),
+            None => AnyValue::Double(number.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(value) => AnyValue::from(value),
+        serde_json::Value::Array(values) => AnyValue::ListAny(values.into_iter().map(json_to_any_value).collect()),
+        serde_json::Value::Object(fields) => AnyValue::Map(
+            fields
+                .into_iter()
+                .map(|(key, value)| (Key::new(key), json_to_any_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+// レベル文字列を重大度に変換
+fn severity_from_level(level: &str) -> Option<Severity> {
+    match level.to_ascii_lowercase().as_str() {
+        "trace" => Some(Severity::Trace),
+        "debug" => Some(Severity::Debug),
+        "info" | "information" | "notice" => Some(Severity::Info),
+        "warn" | "warning" => Some(Severity::Warn),
+        "error" | "err" => Some(Severity::Error),
+        "fatal" | "critical" | "crit" | "panic" | "emergency" | "alert" => Some(Severity::Fatal),
+        _ => None,
+    }
+}
+
+/// レコードの`container.id`属性に対応するコンテナのリソースを付与するエクスポーター
+///
+/// OTLPではレコードごとに`ResourceLogs`が作られるため、1つのバッチ処理を共有しつつ
+/// コンテナごとのリソース属性で送信できます。
+#[derive(Debug)]
+pub struct ContainerResourceExporter<E> {
+    inner: E,
+    resources: ContainerResources,
+}
+
+#[async_trait]
+impl<E: LogExporter> LogExporter for ContainerResourceExporter<E> {
+    async fn export(&mut self, mut batch: Vec<LogData>) -> LogResult<()> {
+        if let Ok(resources) = self.resources.read() {
+            for log_data in batch.iter_mut() {
+                let container_id = log_data.record.attributes.iter().flatten().find_map(|(key, value)| {
+                    match (key.as_str(), value) {
+                        ("container.id", AnyValue::String(id)) => Some(id.as_str()),
+                        _ => None,
+                    }
+                });
+
+                if let Some(resource) = container_id.and_then(|id| resources.get(id)) {
+                    log_data.resource = Cow::Owned(log_data.resource.merge(resource));
+                }
+            }
+        }
+
+        self.inner.export(batch).await
+    }
+
+    fn shutdown(&mut self) {
+        self.inner.shutdown();
+    }
+}
+
+/// ログフォワーダー - コンテナのログ行をOTLPのログレコードとしてバッチ送信
+///
+/// 送信待ちのキューが満杯の場合はレコードを破棄するため、ログの追跡がエクスポートの遅延で止まることはありません。
+#[derive(Clone)]
+pub struct LogForwarder {
+    provider: LoggerProvider,
+    logger: Arc<Logger>,
+    resources: ContainerResources,
+    parse_json: bool,
+}
+
+impl LogForwarder {
+    /// `telemetry.otlp`のプロトコル・ヘッダー・圧縮・TLS設定を使うOTLPエクスポーターでフォワーダーを作成
+    pub fn new(config: &LogExportConfig, telemetry: &TelemetryConfig) -> Result<Self> {
+        let endpoint = config.endpoint.as_deref().unwrap_or(&telemetry.otel_endpoint);
+        let exporter = build_log_exporter(telemetry, endpoint)?;
+
+        let resource = telemetry_resource(telemetry)?;
+        debug!(endpoint, "Forwarding container logs over OTLP");
+        Ok(Self::with_exporter(exporter, config, resource))
+    }
+
+    /// 任意のエクスポーターでフォワーダーを作成
+    pub fn with_exporter<E: LogExporter + 'static>(exporter: E, config: &LogExportConfig, resource: Resource) -> Self {
+        let resources = ContainerResources::default();
+        let exporter = ContainerResourceExporter {
+            inner: exporter,
+            resources: resources.clone(),
+        };
+
+        let processor = BatchLogProcessor::builder(exporter, runtime::Tokio)
+            .with_max_queue_size(config.max_queue_size)
+            .with_max_export_batch_size(config.max_export_batch_size)
+            .with_scheduled_delay(Duration::from_millis(config.batch_delay_ms))
+            .build();
+
+        let provider = LoggerProvider::builder()
+            .with_log_processor(processor)
+            .with_config(opentelemetry_sdk::logs::config().with_resource(resource))
+            .build();
+        let logger = Arc::new(provider.versioned_logger(
+            "container-monitoring",
+            Some(Cow::Borrowed(env!("CARGO_PKG_VERSION"))),
+            None,
+            None,
+        ));
+
+        Self {
+            provider,
+            logger,
+            resources,
+            parse_json: config.parse_json,
+        }
+    }
+
+    /// 追跡を開始したコンテナのリソースを登録
+    pub fn register_container(&self, host: &str, container: &ContainerInfo) {
+        if let Ok(mut resources) = self.resources.write() {
+            resources.insert(container.id.clone(), container_resource(host, container));
+        }
+    }
+
+    /// 存在しなくなったコンテナのリソースを削除
+    pub fn unregister_container(&self, container_id: &str) {
+        if let Ok(mut resources) = self.resources.write() {
+            resources.remove(container_id);
+        }
+    }
+
+    /// ログ行を送信キューへ追加
+    pub fn emit(&self, container_id: &str, stream: LogStream, line: &str) {
+        self.logger.emit(log_record(container_id, stream, line, self.parse_json));
+    }
+
+    /// 送信待ちのレコードをすべて送信（ブロッキング）
+    pub fn flush(&self) {
+        for result in self.provider.force_flush() {
+            if let Err(e) = result {
+                warn!("Failed to flush container logs: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use anyhow::{Context, Result};
+use futures::stream::StreamExt;
+use opentelemetry::metrics::{Counter, Meter, Unit};
+use opentelemetry::KeyValue;
+use regex::{Regex, RegexSet};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::cardinality::{self, ContainerLabels, SeriesLimiter};
+use crate::config::{CardinalityConfig, ContainerFilters, ContainerLogsConfig, LogPatternConfig};
+use crate::docker::{ContainerInfo, DockerClient, LogStream};
+use crate::log_export::LogForwarder;
+use crate::schedule::CollectionSchedule;
+
+/// 設定されたログパターンをまとめてコンパイルした正規表現セット
+pub struct LogPatterns {
+    names: Vec<String>,
+    set: RegexSet,
+}
+
+impl LogPatterns {
+    /// パターンをコンパイル（不正な正規表現はパターン名付きのエラー）
+    pub fn new(patterns: &[LogPatternConfig]) -> Result<Self> {
+        for pattern in patterns {
+            Regex::new(&pattern.regex)
+                .with_context(|| format!("Invalid regex for container log pattern '{}'", pattern.name))?;
+        }
+
+        let set = RegexSet::new(patterns.iter().map(|pattern| &pattern.regex))
+            .with_context(|| "Failed to compile container log patterns")?;
+
+        Ok(Self {
+            names: patterns.iter().map(|pattern| pattern.name.clone()).collect(),
+            set,
+        })
+    }
+
+    /// 行に一致したパターン名
+    pub fn matching<'a>(&'a self, line: &str) -> impl Iterator<Item = &'a str> + 'a {
+        self.set
+            .matches(line)
+            .into_iter()
+            .map(move |index| self.names[index].as_str())
+    }
+}
+
+/// チャンク単位で届くログ出力を行に分割するバッファ
+pub struct LineBuffer {
+    partial: Vec<u8>,
+    max_line_bytes: usize,
+}
+
+impl LineBuffer {
+    /// 改行のない出力を`max_line_bytes`で区切るバッファを作成
+    pub fn new(max_line_bytes: usize) -> Self {
+        Self {
+            partial: Vec::new(),
+            max_line_bytes: max_line_bytes.max(1),
+        }
+    }
+
+    /// チャンクを追加し、完成した行（改行を除く）を返す
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut rest = chunk;
+
+        while let Some(position) = rest.iter().position(|&byte| byte == b'\n') {
+            self.partial.extend_from_slice(&rest[..position]);
+            lines.push(self.take_line());
+            rest = &rest[position + 1..];
+        }
+
+        self.partial.extend_from_slice(rest);
+        while self.partial.len() >= self.max_line_bytes {
+            let remainder = self.partial.split_off(self.max_line_bytes);
+            lines.push(self.take_line());
+            self.partial = remainder;
+        }
+
+        lines
+    }
+
+    // バッファの内容を1行として取り出す
+    fn take_line(&mut self) -> String {
+        let line = std::mem::take(&mut self.partial);
+        String::from_utf8_lossy(&line).trim_end_matches('\r').to_string()
+    }
+}
+
+// ログメトリクスのインストゥルメントと、全コンテナの追跡タスクで共有する系列のラベル・系列数の制限
+#[derive(Clone)]
+struct LogMetrics {
+    lines: Counter<u64>,
+    bytes: Counter<u64>,
+    pattern_matches: Counter<u64>,
+    // 累計値のカウンターのため、metrics.cardinality.counter_labelsのラベルを付与
+    label_set: ContainerLabels,
+    limiter: Arc<Mutex<SeriesLimiter>>,
+}
+
+impl LogMetrics {
+    fn new(meter: &Meter, cardinality: &CardinalityConfig) -> Result<Self> {
+        let label_set = ContainerLabels::new(&cardinality.counter_labels)
+            .context("Invalid metrics.cardinality.counter_labels")?;
+        Ok(Self {
+            lines: meter
+                .u64_counter("container_log_lines_total")
+                .with_description("Log lines written by the container per stream")
+                .init(),
+            bytes: meter
+                .u64_counter("container_log_bytes_total")
+                .with_description("Log bytes written by the container per stream")
+                .with_unit(Unit::new("By"))
+                .init(),
+            pattern_matches: meter
+                .u64_counter("container_log_pattern_matches_total")
+                .with_description("Log lines matching each configured pattern")
+                .init(),
+            label_set,
+            limiter: Arc::new(Mutex::new(SeriesLimiter::new(cardinality.max_series_per_metric))),
+        })
+    }
+
+    // 系列数の上限を超えた系列はoverflow系列のラベルに置き換える
+    fn admit(&self, metric: &str, host: &str, labels: Vec<KeyValue>) -> Vec<KeyValue> {
+        let admitted = match self.limiter.lock() {
+            Ok(mut limiter) => limiter.admit(metric, &labels),
+            Err(_) => true,
+        };
+        if admitted {
+            labels
+        } else {
+            cardinality::overflow_labels(host)
+        }
+    }
+}
+
+/// ログコレクター - Dockerホストごとに監視対象コンテナのログを追跡してメトリクスを記録
+pub struct LogCollector {
+    docker_client: DockerClient,
+    host: String,
+    filters: ContainerFilters,
+    patterns: Arc<LogPatterns>,
+    max_line_bytes: usize,
+    metrics: LogMetrics,
+    forwarder: Option<LogForwarder>,
+
+    // コンテナIDごとのログ追跡タスク
+    followers: HashMap<String, JoinHandle<()>>,
+}
+
+impl LogCollector {
+    /// 新しいログコレクターを作成
+    ///
+    /// ログのカウンターには`cardinality`の`counter_labels`を付与し、`max_series_per_metric`で系列数を制限します。
+    pub fn new(
+        docker_client: DockerClient,
+        host: &str,
+        config: &ContainerLogsConfig,
+        filters: &ContainerFilters,
+        cardinality: &CardinalityConfig,
+    ) -> Result<Self> {
+        Self::new_with_meter(docker_client, host, config, filters, cardinality, &opentelemetry::global::meter("container-monitoring"))
+    }
+
+    /// メトリクスを記録するメーターを指定してログコレクターを作成
+    pub fn new_with_meter(
+        docker_client: DockerClient,
+        host: &str,
+        config: &ContainerLogsConfig,
+        filters: &ContainerFilters,
+        cardinality: &CardinalityConfig,
+        meter: &Meter,
+    ) -> Result<Self> {
+        Ok(Self {
+            docker_client,
+            host: host.to_string(),
+            filters: filters.clone(),
+            patterns: Arc::new(LogPatterns::new(&config.patterns)?),
+            max_line_bytes: config.max_line_bytes,
+            metrics: LogMetrics::new(meter, cardinality)?,
+            forwarder: None,
+            followers: HashMap::new(),
+        })
+    }
+
+    /// ログ行をOTLPのログレコードとして転送するフォワーダーを設定
+    pub fn with_forwarder(mut self, forwarder: Option<LogForwarder>) -> Self {
+        self.forwarder = forwarder;
+        self
+    }
+
+    /// スケジュールに従って追跡対象のコンテナを同期し続ける
+    pub async fn run(mut self, mut schedule: CollectionSchedule) {
+        loop {
+            schedule.tick().await;
+
+            let started = Instant::now();
+            if let Err(e) = self.sync_followers().await {
+                warn!(host = %self.host, "Error syncing container log followers: {:#}", e);
+            }
+            schedule.record_cycle(started.elapsed());
+        }
+    }
+
+    /// 実行中のコンテナの追跡を開始し、停止・削除されたコンテナの追跡を終了
+    ///
+    /// ログストリームが終了したコンテナ（再起動など）は次回の同期で追跡し直します。
+    pub async fn sync_followers(&mut self) -> Result<()> {
+        let containers = self.docker_client.list_filtered_containers(&self.filters).await?;
+        let running: HashMap<String, ContainerInfo> = containers
+            .into_iter()
+            .filter(|c| c.status == "running")
+            .map(|c| (c.id.clone(), c))
+            .collect();
+
+        let forwarder = self.forwarder.as_ref();
+        self.followers.retain(|id, handle| {
+            if running.contains_key(id) {
+                !handle.is_finished()
+            } else {
+                handle.abort();
+                if let Some(forwarder) = forwarder {
+                    forwarder.unregister_container(id);
+                }
+                false
+            }
+        });
+
+        for (id, container) in running {
+            if self.followers.contains_key(&id) {
+                continue;
+            }
+
+            debug!(host = %self.host, container_id = %id, "Following container logs");
+            let handle = tokio::spawn(follow_container_logs(
+                self.docker_client.clone(),
+                self.host.clone(),
+                container,
+                self.patterns.clone(),
+                self.metrics.clone(),
+                self.forwarder.clone(),
+                self.max_line_bytes,
+            ));
+            self.followers.insert(id, handle);
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for LogCollector {
+    fn drop(&mut self) {
+        for handle in self.followers.values() {
+            handle.abort();
+        }
+    }
+}
+
+// 1つのコンテナのログを追跡し、ストリームが終了するまでメトリクスを記録（フォワーダーがあれば転送）
+async fn follow_container_logs(
+    docker_client: DockerClient,
+    host: String,
+    container: ContainerInfo,
+    patterns: Arc<LogPatterns>,
+    metrics: LogMetrics,
+    forwarder: Option<LogForwarder>,
+    max_line_bytes: usize,
+) {
+    if let Some(forwarder) = &forwarder {
+        forwarder.register_container(&host, &container);
+    }
+
+    let container_labels = metrics.label_set.labels(&host, &container.id, &container.name, &container.image);
+
+    // ストリームごとの行バッファと、行数・バイト数のラベル
+    let mut streams: HashMap<LogStream, (LineBuffer, Vec<KeyValue>, Vec<KeyValue>)> = HashMap::new();
+    let since = chrono::Utc::now().timestamp();
+    let mut logs = Box::pin(docker_client.follow_logs(&container.id, since));
+
+    while let Some(result) = logs.next().await {
+        let (stream, chunk) = match result {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("{}", e);
+                break;
+            }
+        };
+
+        let (buffer, bytes_labels, lines_labels) = streams.entry(stream).or_insert_with(|| {
+            let mut labels = container_labels.clone();
+            labels.push(KeyValue::new("stream", stream.as_str()));
+            (
+                LineBuffer::new(max_line_bytes),
+                metrics.admit("container_log_bytes_total", &host, labels.clone()),
+                metrics.admit("container_log_lines_total", &host, labels),
+            )
+        });
+
+        metrics.bytes.add(chunk.len() as u64, bytes_labels);
+        let lines = buffer.push(&chunk);
+        if lines.is_empty() {
+            continue;
+        }
+        metrics.lines.add(lines.len() as u64, lines_labels);
+
+        for line in &lines {
+            for pattern in patterns.matching(line) {
+                let mut pattern_labels = container_labels.clone();
+                pattern_labels.push(KeyValue::new("stream", stream.as_str()));
+                pattern_labels.push(KeyValue::new("pattern", pattern.to_string()));
+                let pattern_labels = metrics.admit("container_log_pattern_matches_total", &host, pattern_labels);
+                metrics.pattern_matches.add(1, &pattern_labels);
+            }
+
+            if let Some(forwarder) = &forwarder {
+                forwarder.emit(&container.id, stream, line);
+            }
+        }
+    }
+
+    info!(container_id = %container.id, "Container log stream ended");
+}
@@ -1,25 +1,12 @@
-use std::sync::Arc;
-use std::time::Duration;
-
 use anyhow::Result;
 use clap::Parser;
 use tokio::signal;
-use tokio::sync::Mutex;
-use tokio::time;
 use tracing::{info, warn};
 
-// モジュールのインポート（mod.rsを使わない構造）
-mod config;
-mod docker;
-mod metrics;
-mod telemetry;
-mod server;
-
-use crate::config::{Config, load_config};
-use crate::docker::DockerClient;
-use crate::metrics::MetricsCollector;
-use crate::telemetry::init_telemetry;
-use crate::server::start_metrics_server;
+// The binary embeds the collector through the same public API as other services
+use container_monitoring::config::load_config;
+use container_monitoring::monitor::MonitorBuilder;
+use container_monitoring::telemetry::init_telemetry;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -37,7 +24,7 @@ async fn main() -> Result<()> {
     let config = load_config(&args.config)?;
     
     // Initialize OpenTelemetry and logging
-    let _telemetry_guard = init_telemetry(&config)?;
+    let telemetry_guard = init_telemetry(&config)?;
     
     // Log startup information
     info!(
@@ -46,38 +33,13 @@ async fn main() -> Result<()> {
     );
     info!("Interval set to {} seconds", config.general.interval);
     
-    // Create docker client
-    let docker_client = DockerClient::new(&config.docker)?;
-    info!("Connected to Docker daemon");
-    
-    // Create metrics collector
-    let metrics_collector = Arc::new(Mutex::new(
-        MetricsCollector::new(docker_client, &config.metrics)?
-    ));
-    
-    // Start metrics server for Prometheus scraping
-    let metrics_collector_clone = metrics_collector.clone();
-    let prometheus_handle = tokio::spawn(async move {
-        if let Err(e) = start_metrics_server(metrics_collector_clone, config.telemetry.prometheus_port).await {
-            warn!("Metrics server error: {}", e);
-        }
-    });
-    
-    // Start metrics collection loop
-    let collection_interval = Duration::from_secs(config.general.interval);
-    let collector_handle = tokio::spawn(async move {
-        let mut interval = time::interval(collection_interval);
-        
-        loop {
-            interval.tick().await;
-            
-            // Collect metrics
-            info!("Collecting container metrics...");
-            if let Err(e) = metrics_collector.lock().await.collect_metrics().await {
-                warn!("Error collecting metrics: {}", e);
-            }
-        }
-    });
+    // Metrics go through the global meter provider set up by init_telemetry
+    let mut monitor = MonitorBuilder::new(config)
+        .with_meter(opentelemetry::global::meter("container-monitoring"))
+        .with_counter_start_times(telemetry_guard.counter_start_times())
+        .with_metrics_server(true)
+        .build()?;
+    monitor.start().await?;
     
     // Wait for shutdown signal
     match signal::ctrl_c().await {
@@ -89,9 +51,8 @@ async fn main() -> Result<()> {
         }
     }
     
-    // Clean shutdown
-    prometheus_handle.abort();
-    collector_handle.abort();
+    // Clean shutdown: saves state and flushes buffered log records
+    monitor.shutdown().await?;
     
     info!("Container monitoring service stopped");
     Ok(())
@@ -1,177 +1,812 @@
-use anyhow::Result;
-use opentelemetry::metrics::{Counter, Histogram, MeterProvider, Unit, UpDownCounter};
+use anyhow::{Context, Result};
+use futures::stream::StreamExt;
+use opentelemetry::metrics::{Counter, Histogram, Meter, MeterProvider, ObservableCounter, ObservableGauge, Unit, UpDownCounter};
 use opentelemetry::KeyValue;
-use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::TryRecvError;
+use tokio::sync::{broadcast, Mutex};
+use tokio::task::JoinHandle;
 use tracing::{debug, info, instrument, warn};
 
+use crate::cardinality::{self, ContainerLabels, SeriesLimiter};
 use crate::config::MetricsConfig;
-use crate::docker::{ContainerInfo, DockerClient};
+use crate::docker::{self, ContainerDetails, ContainerInfo, DiskUsage, DockerClient, ProcessInfo};
+use crate::kubernetes::PodResolver;
+use crate::state::{Baseline, HostState, InventoryEntry};
+use crate::stream::{self, StreamEvent};
+
+// 観測型ゲージのコールバックが参照する、コンテナラベルとインスペクト結果の組
+type InspectSnapshot = Arc<RwLock<Vec<(Vec<KeyValue>, ContainerDetails)>>>;
+
+// 観測型ゲージが報告する（ラベル, 値）の一覧
+type Series<T = i64> = Arc<RwLock<Vec<(Vec<KeyValue>, T)>>>;
+
+// 観測型カウンターが報告する系列ごとの累計値。キーはキー順に並べたラベルの組
+// SDKが累計値の系列を削除できないため、存在しなくなったコンテナの系列も最後の値のまま報告する
+// （観測をやめた系列はSDKが0と過去の観測値の合計を交互に出力する。系列数はcounter_labelsと上限で抑える）
+type CounterTotals = Arc<RwLock<HashMap<String, CounterTotal>>>;
+
+// エクスポーターが参照する累計値（コレクターとSDKのコールバックが保持している間のみ参照できる）
+type WeakCounterTotals = Weak<RwLock<HashMap<String, CounterTotal>>>;
+
+// 観測型カウンターの系列の累計値
+struct CounterTotal {
+    labels: Vec<KeyValue>,
+    value: u64,
+    // 系列に加算しているコンテナごとの実行の開始時刻（キーはコンテナID）
+    // counter_labelsからcontainer_idを外した系列やoverflow系列は複数のコンテナを合計する
+    runs: HashMap<String, Option<i64>>,
+    // 累計を開始した時刻（CounterStartTimesを通じてエクスポーターが参照する）
+    start_time: SystemTime,
+}
+
+/// 観測型カウンターの系列ごとの累計の開始時刻
+///
+/// SDKは系列ごとの開始時刻を持たないため、同じMeterProviderのエクスポーターがデータポイントの開始時刻に使用します。
+/// コンテナの実行を開始から観測した場合（再起動の検出後）はその`StartedAt`、途中から観測した実行は最初に観測した時刻です。
+/// 開始時刻は各コレクターの系列の累計値と一緒に保持し、SDKが系列を報告しなくなると参照できなくなります。
+#[derive(Clone, Default)]
+pub struct CounterStartTimes {
+    // メトリクス名と、登録したコレクターの系列の累計値
+    totals: Arc<RwLock<Vec<(&'static str, WeakCounterTotals)>>>,
+}
+
+impl CounterStartTimes {
+    /// 空の開始時刻の一覧を作成
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// 系列が累計を開始した時刻
+    pub fn get(&self, metric: &str, labels: &[KeyValue]) -> Option<SystemTime> {
+        let key = total_key(labels);
+        let totals = self.totals.read().ok()?;
+        totals
+            .iter()
+            .filter(|(name, _)| *name == metric)
+            .filter_map(|(_, totals)| totals.upgrade())
+            .find_map(|totals| totals.read().ok()?.get(&key).map(|total| total.start_time))
+    }
+    
+    // コレクターの系列の累計値を登録（報告されなくなった累計値の登録は削除）
+    fn register(&self, metric: &'static str, totals: &CounterTotals) {
+        if let Ok(mut registered) = self.totals.write() {
+            registered.retain(|(_, totals)| totals.strong_count() > 0);
+            registered.push((metric, Arc::downgrade(totals)));
+        }
+    }
+}
+
+// 系列の累計値のキー（SDKのデータポイントの属性はキー順のため、ラベルをキー順に並べる）
+fn total_key(labels: &[KeyValue]) -> String {
+    let mut labels = labels.to_vec();
+    labels.sort_by(|a, b| a.key.cmp(&b.key));
+    cardinality::series_key(&labels)
+}
+
+// ディスク使用量ゲージが報告する（ラベル, 値）の一覧
+#[derive(Default)]
+struct DiskUsageSeries {
+    writable_layer: Vec<(Vec<KeyValue>, i64)>,
+    root_fs: Vec<(Vec<KeyValue>, i64)>,
+    volume_size: Vec<(Vec<KeyValue>, i64)>,
+    volume_ref_count: Vec<(Vec<KeyValue>, i64)>,
+}
+
+// ディスク使用量ゲージが報告する系列を選ぶ関数
+type DiskUsageSelector = fn(&DiskUsageSeries) -> &[(Vec<KeyValue>, i64)];
+
+impl DiskUsageSeries {
+    // 監視対象のコンテナ（IDごとのラベル）とすべてのボリュームの系列を作成
+    fn new(host: &str, container_labels: &HashMap<String, Vec<KeyValue>>, usage: DiskUsage) -> Self {
+        let mut series = Self::default();
+        
+        for container in usage.containers {
+            let Some(labels) = container_labels.get(&container.id).cloned() else { continue };
+            series.writable_layer.push((labels.clone(), container.writable_layer_bytes as i64));
+            series.root_fs.push((labels, container.root_fs_bytes as i64));
+        }
+        
+        for volume in usage.volumes {
+            let labels = vec![
+                KeyValue::new("host", host.to_string()),
+                KeyValue::new("volume", volume.name),
+                KeyValue::new("driver", volume.driver),
+            ];
+            series.volume_size.push((labels.clone(), volume.size_bytes as i64));
+            series.volume_ref_count.push((labels, volume.ref_count as i64));
+        }
+        
+        series
+    }
+}
+
+/// Dockerの累計値からカウンターの増加分を計算するトラッカー
+///
+/// 前回値はキー（先頭はコンテナID）ごとに、観測したコンテナの実行（開始時刻）と組で保持します。
+/// - 初回の観測: 基準値として0（エージェントの起動前後に関わらず、それまでの値は増加分にしない）
+/// - 開始時刻が変わった場合（同じコンテナIDでの再起動）: 新しい実行の値全体
+/// - 値が減少した場合（開始時刻が分からない場合のリセット）: 現在値
+#[derive(Debug, Clone, Default)]
+pub struct DeltaTracker {
+    previous: HashMap<String, (Option<i64>, u64)>,
+}
+
+impl DeltaTracker {
+    /// 空のトラッカーを作成
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// 前回値からの増加分を計算し、前回値を更新
+    pub fn delta(&mut self, key: &str, run_started_at: Option<i64>, value: u64) -> u64 {
+        let previous = self.previous.insert(key.to_string(), (run_started_at, value));
+        
+        match previous {
+            None => 0,
+            Some((Some(previous_run), _)) if run_started_at.is_some_and(|run| run != previous_run) => {
+                debug!("Container restart detected for {}", key);
+                value
+            }
+            Some((_, previous_value)) if value >= previous_value => value - previous_value,
+            Some(_) => {
+                debug!("Counter reset detected for {}", key);
+                value
+            }
+        }
+    }
+    
+    /// 状態ファイルに保存する前回値
+    pub fn baselines(&self) -> BTreeMap<String, Baseline> {
+        self.previous
+            .iter()
+            .map(|(key, (started_at, value))| (key.clone(), Baseline { started_at: *started_at, value: *value }))
+            .collect()
+    }
+    
+    /// 状態ファイルから読み込んだ前回値を復元（復元したキーは初回の観測として扱わない）
+    pub fn restore(&mut self, baselines: BTreeMap<String, Baseline>) {
+        self.previous.extend(
+            baselines
+                .into_iter()
+                .map(|(key, baseline)| (key, (baseline.started_at, baseline.value))),
+        );
+    }
+    
+    /// 条件を満たさないキーの前回値を削除し、削除した件数を返す
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) -> usize {
+        let before_count = self.previous.len();
+        self.previous.retain(|key, _| keep(key));
+        before_count - self.previous.len()
+    }
+}
+
+// 収集サイクルごとに置き換えるコンテナごとのゲージの系列
+#[derive(Default)]
+struct ContainerSeries {
+    pids: Vec<(Vec<KeyValue>, i64)>,
+    cpu_usage: Vec<(Vec<KeyValue>, f64)>,
+    memory_usage: Vec<(Vec<KeyValue>, i64)>,
+    memory_limit: Vec<(Vec<KeyValue>, i64)>,
+    memory_usage_percent: Vec<(Vec<KeyValue>, f64)>,
+}
 
 /// メトリクスコレクター - Dockerメトリクスの収集とOpenTelemetryへの変換を担当
 pub struct MetricsCollector {
     docker_client: DockerClient,
     config: MetricsConfig,
     
+    // 監視対象のDockerホスト名（すべてのメトリクスの`host`属性）
+    host: String,
+    
     // OpenTelemetryメーター
-    meter: opentelemetry::metrics::Meter,
+    meter: Meter,
     
     // メトリクスインストゥルメント
-    cpu_usage: Histogram<f64>,
-    memory_usage: UpDownCounter<u64>,
-    memory_limit: UpDownCounter<u64>,
-    memory_usage_percent: Histogram<f64>,
-    network_receive_bytes: Counter<u64>,
-    network_transmit_bytes: Counter<u64>,
-    fs_reads_bytes: Counter<u64>,
-    fs_writes_bytes: Counter<u64>,
     container_count: UpDownCounter<i64>,
     
+    // コンテナごとのメトリクスのラベルと、メトリクスごとの系列数の制限
+    label_set: ContainerLabels,
+    counter_label_set: ContainerLabels,
+    limiter: Arc<Mutex<SeriesLimiter>>,
+    
+    // コンテナごとのゲージ（収集サイクルごとに置き換え、存在しなくなったコンテナの系列は報告しない）
+    cpu_usage: Series<f64>,
+    memory_usage: Series,
+    memory_limit: Series,
+    memory_usage_percent: Series<f64>,
+    _container_gauges: (Vec<ObservableGauge<i64>>, Vec<ObservableGauge<f64>>),
+    
+    // Dockerの累計値から計算した、エージェント起動後の増加分（観測型カウンターで報告）
+    network_receive_bytes: CounterTotals,
+    network_transmit_bytes: CounterTotals,
+    fs_reads_bytes: CounterTotals,
+    fs_writes_bytes: CounterTotals,
+    blkio_device_bytes: CounterTotals,
+    blkio_device_operations: CounterTotals,
+    _counters: Vec<ObservableCounter<u64>>,
+    
+    // セルフメトリクス（ホストごとの収集状態）
+    host_up: UpDownCounter<i64>,
+    collection_duration: Histogram<f64>,
+    collection_errors: Counter<u64>,
+    stats_timeouts: Counter<u64>,
+    host_healthy: Option<bool>,
+    
     // カウンター型メトリクスのための前回値（デルタ計算用）
-    prev_network_rx: Arc<Mutex<HashMap<String, u64>>>,
-    prev_network_tx: Arc<Mutex<HashMap<String, u64>>>,
-    prev_fs_reads: Arc<Mutex<HashMap<String, u64>>>,
-    prev_fs_writes: Arc<Mutex<HashMap<String, u64>>>,
+    // キーは "<container_id>/<種別>" または "<container_id>/<major>:<minor>/<操作>/<種別>"
+    deltas: Mutex<DeltaTracker>,
+    
+    // Kubernetesのポッド情報（metrics.kubernetes.enabledの場合のみ）
+    kubernetes: Option<PodResolver>,
+    
+    // 前回の収集サイクルで観測したコンテナ（状態ファイルに保存）と、状態ファイルから復元した一覧
+    inventory: Vec<InventoryEntry>,
+    restored_inventory: Option<Vec<InventoryEntry>>,
+    
+    // major:minorからブロックデバイス名へのキャッシュ
+    device_names: Arc<Mutex<HashMap<(u64, u64), String>>>,
+    
+    // 収集サイクルのライブ配信用チャネル
+    events: broadcast::Sender<StreamEvent>,
+    
+    // インスペクト結果のキャッシュ（ライフサイクルイベントを受信したコンテナは再取得）
+    inspect_cache: HashMap<String, (ContainerDetails, Instant)>,
+    event_receiver: broadcast::Receiver<StreamEvent>,
+    inspect_snapshot: InspectSnapshot,
+    _inspect_gauges: Vec<ObservableGauge<i64>>,
+    
+    // ディスク使用量（低頻度でバックグラウンド更新し、観測型ゲージで報告）
+    disk_usage_series: Arc<RwLock<DiskUsageSeries>>,
+    disk_usage_refresh: Option<(Instant, JoinHandle<()>)>,
+    _disk_usage_gauges: Vec<ObservableGauge<i64>>,
+    
+    // Dockerデーモン情報（イメージ数、ストレージドライバーなど）
+    daemon_images: Series,
+    daemon_info: Series,
+    _daemon_gauges: Vec<ObservableGauge<i64>>,
+    
+    // プロセス数と、CPU使用率上位のプロセス
+    pids: Series,
+    process_cpu_usage: Series<f64>,
+    process_memory_rss: Series,
+    // (コンテナID, PID)ごとの前回の累計CPU時間と取得時刻
+    prev_process_cpu: HashMap<(String, u64), (f64, Instant)>,
+    _process_gauges: (Vec<ObservableGauge<i64>>, ObservableGauge<f64>),
+}
+
+/// Dockerホストの稼働状態（接続できないホストの報告にも使用）
+pub fn host_up_counter(meter: &Meter) -> UpDownCounter<i64> {
+    meter
+        .i64_up_down_counter("container_monitoring_host_up")
+        .with_description("Whether the last collection cycle for the Docker host succeeded (1) or failed (0)")
+        .init()
 }
 
 impl MetricsCollector {
     /// 新しいメトリクスコレクターを作成
     pub fn new(docker_client: DockerClient, config: &MetricsConfig) -> Result<Self> {
+        Self::new_with_meter(docker_client, config, &opentelemetry::global::meter("container-monitoring"))
+    }
+    
+    /// メトリクスを記録するメーターを指定してメトリクスコレクターを作成
+    pub fn new_with_meter(docker_client: DockerClient, config: &MetricsConfig, meter: &Meter) -> Result<Self> {
         debug!("Initializing metrics collector");
-        let meter = opentelemetry::global::meter("container-monitoring");
+        let meter = meter.clone();
+        
+        let label_set = ContainerLabels::new(&config.cardinality.container_labels)
+            .context("Invalid metrics.cardinality.container_labels")?;
+        let counter_label_set = ContainerLabels::new(&config.cardinality.counter_labels)
+            .context("Invalid metrics.cardinality.counter_labels")?;
+        let kubernetes = if config.kubernetes.enabled {
+            Some(PodResolver::new(&config.kubernetes)?)
+        } else {
+            None
+        };
         
         // メトリクスインストゥルメントの初期化
-        let cpu_usage = Self::init_cpu_metric(&meter);
-        let (memory_usage, memory_limit, memory_usage_percent) = Self::init_memory_metrics(&meter);
-        let (network_receive_bytes, network_transmit_bytes) = Self::init_network_metrics(&meter);
-        let (fs_reads_bytes, fs_writes_bytes) = Self::init_fs_metrics(&meter);
+        let cpu_usage: Series<f64> = Arc::new(RwLock::new(Vec::new()));
+        let memory_usage: Series = Arc::new(RwLock::new(Vec::new()));
+        let memory_limit: Series = Arc::new(RwLock::new(Vec::new()));
+        let memory_usage_percent: Series<f64> = Arc::new(RwLock::new(Vec::new()));
+        let container_gauges = (
+            vec![
+                Self::init_series_gauge(&meter, "container_memory_usage_bytes", "Memory usage in bytes", Some("By"), &memory_usage),
+                Self::init_series_gauge(&meter, "container_memory_limit_bytes", "Memory limit in bytes", Some("By"), &memory_limit),
+            ],
+            vec![
+                Self::init_f64_series_gauge(&meter, "container_cpu_usage_percent", "CPU usage in percent", Some("%"), &cpu_usage),
+                Self::init_f64_series_gauge(&meter, "container_memory_usage_percent", "Memory usage in percent", Some("%"), &memory_usage_percent),
+            ],
+        );
+        let network_receive_bytes = CounterTotals::default();
+        let network_transmit_bytes = CounterTotals::default();
+        let fs_reads_bytes = CounterTotals::default();
+        let fs_writes_bytes = CounterTotals::default();
+        let blkio_device_bytes = CounterTotals::default();
+        let blkio_device_operations = CounterTotals::default();
+        let counters = vec![
+            Self::init_total_counter(&meter, &network_receive_bytes, "container_network_receive_bytes_total", "Network bytes received", Some("By")),
+            Self::init_total_counter(&meter, &network_transmit_bytes, "container_network_transmit_bytes_total", "Network bytes transmitted", Some("By")),
+            Self::init_total_counter(&meter, &fs_reads_bytes, "container_fs_reads_bytes_total", "Filesystem bytes read", Some("By")),
+            Self::init_total_counter(&meter, &fs_writes_bytes, "container_fs_writes_bytes_total", "Filesystem bytes written", Some("By")),
+            Self::init_total_counter(&meter, &blkio_device_bytes, "container_blkio_device_bytes_total", "Block I/O bytes per device and operation", Some("By")),
+            Self::init_total_counter(&meter, &blkio_device_operations, "container_blkio_device_operations_total", "Block I/O operations per device and operation", None),
+        ];
         let container_count = Self::init_container_count_metric(&meter);
+        let (host_up, collection_duration, collection_errors, stats_timeouts) = Self::init_self_metrics(&meter);
+        let inspect_snapshot: InspectSnapshot = Arc::new(RwLock::new(Vec::new()));
+        let inspect_gauges = Self::init_inspect_metrics(&meter, &inspect_snapshot);
+        let disk_usage_series = Arc::new(RwLock::new(DiskUsageSeries::default()));
+        let disk_usage_gauges = Self::init_disk_usage_metrics(&meter, &disk_usage_series);
+        let daemon_images: Series = Arc::new(RwLock::new(Vec::new()));
+        let daemon_info: Series = Arc::new(RwLock::new(Vec::new()));
+        let daemon_gauges = vec![
+            Self::init_series_gauge(&meter, "docker_images", "Number of images on the Docker host", None, &daemon_images),
+            Self::init_series_gauge(&meter, "docker_daemon_info", "Docker daemon information (storage driver, version) with a constant value of 1", None, &daemon_info),
+        ];
+        let pids: Series = Arc::new(RwLock::new(Vec::new()));
+        let process_cpu_usage: Series<f64> = Arc::new(RwLock::new(Vec::new()));
+        let process_memory_rss: Series = Arc::new(RwLock::new(Vec::new()));
+        let process_gauges = (
+            vec![
+                Self::init_series_gauge(&meter, "container_pids", "Number of processes and threads in the container", None, &pids),
+                Self::init_series_gauge(&meter, "container_process_memory_rss_bytes", "Resident set size of the top processes in the container", None, &process_memory_rss),
+            ],
+            Self::init_f64_series_gauge(&meter, "container_process_cpu_usage_percent", "CPU usage of the top processes in the container", None, &process_cpu_usage),
+        );
+        let events = stream::channel();
         
         Ok(Self {
             docker_client,
             config: config.clone(),
+            host: "local".to_string(),
             meter,
+            container_count,
+            label_set,
+            counter_label_set,
+            limiter: Arc::new(Mutex::new(SeriesLimiter::new(config.cardinality.max_series_per_metric))),
             cpu_usage,
             memory_usage,
             memory_limit,
             memory_usage_percent,
+            _container_gauges: container_gauges,
             network_receive_bytes,
             network_transmit_bytes,
             fs_reads_bytes,
             fs_writes_bytes,
-            container_count,
-            prev_network_rx: Arc::new(Mutex::new(HashMap::new())),
-            prev_network_tx: Arc::new(Mutex::new(HashMap::new())),
-            prev_fs_reads: Arc::new(Mutex::new(HashMap::new())),
-            prev_fs_writes: Arc::new(Mutex::new(HashMap::new())),
+            blkio_device_bytes,
+            blkio_device_operations,
+            _counters: counters,
+            host_up,
+            collection_duration,
+            collection_errors,
+            stats_timeouts,
+            host_healthy: None,
+            deltas: Mutex::new(DeltaTracker::new()),
+            kubernetes,
+            inventory: Vec::new(),
+            restored_inventory: None,
+            device_names: Arc::new(Mutex::new(HashMap::new())),
+            event_receiver: events.subscribe(),
+            events,
+            inspect_cache: HashMap::new(),
+            inspect_snapshot,
+            _inspect_gauges: inspect_gauges,
+            disk_usage_series,
+            disk_usage_refresh: None,
+            _disk_usage_gauges: disk_usage_gauges,
+            daemon_images,
+            daemon_info,
+            _daemon_gauges: daemon_gauges,
+            pids,
+            process_cpu_usage,
+            process_memory_rss,
+            prev_process_cpu: HashMap::new(),
+            _process_gauges: process_gauges,
         })
     }
     
-    // CPUメトリクスのインストゥルメントを初期化
-    fn init_cpu_metric(meter: &opentelemetry::metrics::Meter) -> Histogram<f64> {
+    /// 監視対象ホスト名を設定（複数デーモン監視時に`host`属性として付与）
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+    
+    /// 複数のコレクターで共有するストリームチャネルを設定
+    pub fn with_events(mut self, events: broadcast::Sender<StreamEvent>) -> Self {
+        self.event_receiver = events.subscribe();
+        self.events = events;
+        self
+    }
+    
+    /// 観測型カウンターの系列の開始時刻を、メーターのMeterProviderのエクスポーターに渡した一覧に登録
+    ///
+    /// 一覧は`build_meter_provider_with_start_times`で作成したMeterProviderと共有します。
+    pub fn with_counter_start_times(self, start_times: &CounterStartTimes) -> Self {
+        for (metric, totals) in self.counter_totals() {
+            start_times.register(metric, totals);
+        }
+        self
+    }
+    
+    /// 状態ファイルから読み込んだカウンターの前回値とコンテナ一覧を復元
+    pub fn with_state(mut self, state: HostState) -> Self {
+        // 共有される前のため、ロックせずに前回値を復元できる
+        self.deltas.get_mut().restore(state.baselines);
+        self.restored_inventory = Some(state.inventory);
+        self
+    }
+    
+    /// 状態ファイルに保存するカウンターの前回値とコンテナ一覧
+    pub async fn state(&self) -> HostState {
+        HostState {
+            baselines: self.deltas.lock().await.baselines(),
+            inventory: self.inventory.clone(),
+        }
+    }
+    
+    /// 監視対象ホスト名を取得
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+    
+    /// 収集サイクルとライフサイクルイベントを配信するチャネルを取得
+    pub fn event_sender(&self) -> broadcast::Sender<StreamEvent> {
+        self.events.clone()
+    }
+    
+    /// バックグラウンドで実行中のディスク使用量の更新を中断
+    pub fn abort(&mut self) {
+        if let Some((_, handle)) = self.disk_usage_refresh.take() {
+            handle.abort();
+        }
+    }
+    
+    // 累計値を報告する観測型カウンターを作成
+    fn init_total_counter(
+        meter: &opentelemetry::metrics::Meter,
+        totals: &CounterTotals,
+        name: &'static str,
+        description: &'static str,
+        unit: Option<&'static str>,
+    ) -> ObservableCounter<u64> {
+        let totals = totals.clone();
+        let mut builder = meter
+            .u64_observable_counter(name)
+            .with_description(description);
+        if let Some(unit) = unit {
+            builder = builder.with_unit(Unit::new(unit));
+        }
+        
+        builder
+            .with_callback(move |instrument| {
+                let Ok(totals) = totals.read() else { return };
+                for total in totals.values() {
+                    instrument.observe(total.value, &total.labels);
+                }
+            })
+            .init()
+    }
+    
+    // コンテナ数メトリクスのインストゥルメントを初期化
+    fn init_container_count_metric(meter: &opentelemetry::metrics::Meter) -> UpDownCounter<i64> {
         meter
-            .f64_histogram("container_cpu_usage_percent")
-            .with_description("CPU usage in percent")
-            .with_unit(Unit::new("%"))
+            .i64_up_down_counter("container_count")
+            .with_description("Number of containers")
             .init()
     }
     
-    // メモリメトリクスのインストゥルメントを初期化
-    fn init_memory_metrics(meter: &opentelemetry::metrics::Meter) -> (UpDownCounter<u64>, UpDownCounter<u64>, Histogram<f64>) {
-        let memory_usage = meter
-            .u64_up_down_counter("container_memory_usage_bytes")
-            .with_description("Memory usage in bytes")
-            .with_unit(Unit::new("By"))
+    // セルフメトリクスのインストゥルメントを初期化
+    fn init_self_metrics(meter: &opentelemetry::metrics::Meter) -> (UpDownCounter<i64>, Histogram<f64>, Counter<u64>, Counter<u64>) {
+        let host_up = host_up_counter(meter);
+            
+        let collection_duration = meter
+            .f64_histogram("container_monitoring_collection_duration_seconds")
+            .with_description("Duration of a collection cycle per Docker host")
+            .with_unit(Unit::new("s"))
             .init();
             
-        let memory_limit = meter
-            .u64_up_down_counter("container_memory_limit_bytes")
-            .with_description("Memory limit in bytes")
-            .with_unit(Unit::new("By"))
+        let collection_errors = meter
+            .u64_counter("container_monitoring_collection_errors_total")
+            .with_description("Failed collection cycles per Docker host")
             .init();
             
-        let memory_usage_percent = meter
-            .f64_histogram("container_memory_usage_percent")
-            .with_description("Memory usage in percent")
-            .with_unit(Unit::new("%"))
+        let stats_timeouts = meter
+            .u64_counter("container_monitoring_stats_timeouts_total")
+            .with_description("Containers whose stats request timed out and were reported as stale")
             .init();
             
-        (memory_usage, memory_limit, memory_usage_percent)
+        (host_up, collection_duration, collection_errors, stats_timeouts)
     }
     
-    // ネットワークメトリクスのインストゥルメントを初期化
-    fn init_network_metrics(meter: &opentelemetry::metrics::Meter) -> (Counter<u64>, Counter<u64>) {
-        let network_receive_bytes = meter
-            .u64_counter("container_network_receive_bytes_total")
-            .with_description("Network bytes received")
-            .with_unit(Unit::new("By"))
-            .init();
-            
-        let network_transmit_bytes = meter
-            .u64_counter("container_network_transmit_bytes_total")
-            .with_description("Network bytes transmitted")
-            .with_unit(Unit::new("By"))
+    // インスペクト結果のメトリクスを初期化（収集時点の最新スナップショットをコールバックで報告）
+    fn init_inspect_metrics(meter: &opentelemetry::metrics::Meter, snapshot: &InspectSnapshot) -> Vec<ObservableGauge<i64>> {
+        let health_snapshot = snapshot.clone();
+        let health_status = meter
+            .i64_observable_gauge("container_health_status")
+            .with_description("Healthcheck status of the container (1 for the current status)")
+            .with_callback(move |instrument| {
+                let Ok(snapshot) = health_snapshot.read() else { return };
+                for (labels, details) in snapshot.iter() {
+                    if let Some(status) = &details.health_status {
+                        let mut labels = labels.clone();
+                        labels.push(KeyValue::new("status", status.clone()));
+                        instrument.observe(1, &labels);
+                    }
+                }
+            })
             .init();
-            
-        (network_receive_bytes, network_transmit_bytes)
+        
+        vec![
+            health_status,
+            Self::init_inspect_gauge(meter, snapshot, "container_health_failing_streak", "Consecutive failed healthchecks", None, |details| {
+                details.health_status.as_ref().map(|_| details.failing_streak)
+            }),
+            Self::init_inspect_gauge(meter, snapshot, "container_restart_count", "Number of times the container has been restarted", None, |details| {
+                Some(details.restart_count)
+            }),
+            Self::init_inspect_gauge(meter, snapshot, "container_uptime_seconds", "Time since the container was last started", Some("s"), |details| {
+                details.started_at.map(|started_at| (chrono::Utc::now().timestamp() - started_at).max(0))
+            }),
+            Self::init_inspect_gauge(meter, snapshot, "container_last_exit_code", "Exit code of the last run of the container", None, |details| {
+                Some(details.exit_code)
+            }),
+            Self::init_inspect_gauge(meter, snapshot, "container_oom_killed", "Whether the last run of the container was OOM killed (1) or not (0)", None, |details| {
+                Some(i64::from(details.oom_killed))
+            }),
+        ]
     }
     
-    // ファイルシステムメトリクスのインストゥルメントを初期化
-    fn init_fs_metrics(meter: &opentelemetry::metrics::Meter) -> (Counter<u64>, Counter<u64>) {
-        let fs_reads_bytes = meter
-            .u64_counter("container_fs_reads_bytes_total")
-            .with_description("Filesystem bytes read")
-            .with_unit(Unit::new("By"))
-            .init();
-            
-        let fs_writes_bytes = meter
-            .u64_counter("container_fs_writes_bytes_total")
-            .with_description("Filesystem bytes written")
-            .with_unit(Unit::new("By"))
-            .init();
-            
-        (fs_reads_bytes, fs_writes_bytes)
+    // ディスク使用量のメトリクスを初期化
+    fn init_disk_usage_metrics(meter: &opentelemetry::metrics::Meter, series: &Arc<RwLock<DiskUsageSeries>>) -> Vec<ObservableGauge<i64>> {
+        let gauges: [(&'static str, &'static str, DiskUsageSelector); 4] = [
+            ("container_fs_writable_layer_bytes", "Size of the container's writable layer", |s| &s.writable_layer),
+            ("container_fs_root_bytes", "Total size of the container's root filesystem including the image", |s| &s.root_fs),
+            ("container_volume_usage_bytes", "Disk space used by the volume", |s| &s.volume_size),
+            ("container_volume_ref_count", "Number of containers referencing the volume", |s| &s.volume_ref_count),
+        ];
+        
+        gauges
+            .into_iter()
+            .map(|(name, description, select)| {
+                let series = series.clone();
+                let builder = meter
+                    .i64_observable_gauge(name)
+                    .with_description(description);
+                let builder = if name.ends_with("_bytes") {
+                    builder.with_unit(Unit::new("By"))
+                } else {
+                    builder
+                };
+                
+                builder
+                    .with_callback(move |instrument| {
+                        let Ok(series) = series.read() else { return };
+                        for (labels, value) in select(&series) {
+                            instrument.observe(*value, labels);
+                        }
+                    })
+                    .init()
+            })
+            .collect()
     }
     
-    // コンテナ数メトリクスのインストゥルメントを初期化
-    fn init_container_count_metric(meter: &opentelemetry::metrics::Meter) -> UpDownCounter<i64> {
-        meter
-            .i64_up_down_counter("container_count")
-            .with_description("Number of containers")
+    // 系列の一覧をそのまま報告する観測型ゲージを作成
+    fn init_series_gauge(
+        meter: &opentelemetry::metrics::Meter,
+        name: &'static str,
+        description: &'static str,
+        unit: Option<&'static str>,
+        series: &Series,
+    ) -> ObservableGauge<i64> {
+        let series = series.clone();
+        let mut builder = meter
+            .i64_observable_gauge(name)
+            .with_description(description);
+        if let Some(unit) = unit {
+            builder = builder.with_unit(Unit::new(unit));
+        }
+        
+        builder
+            .with_callback(move |instrument| {
+                let Ok(series) = series.read() else { return };
+                for (labels, value) in series.iter() {
+                    instrument.observe(*value, labels);
+                }
+            })
+            .init()
+    }
+    
+    // 系列の一覧をそのまま報告する観測型ゲージを作成（浮動小数点数）
+    fn init_f64_series_gauge(
+        meter: &opentelemetry::metrics::Meter,
+        name: &'static str,
+        description: &'static str,
+        unit: Option<&'static str>,
+        series: &Series<f64>,
+    ) -> ObservableGauge<f64> {
+        let series = series.clone();
+        let mut builder = meter
+            .f64_observable_gauge(name)
+            .with_description(description);
+        if let Some(unit) = unit {
+            builder = builder.with_unit(Unit::new(unit));
+        }
+        
+        builder
+            .with_callback(move |instrument| {
+                let Ok(series) = series.read() else { return };
+                for (labels, value) in series.iter() {
+                    instrument.observe(*value, labels);
+                }
+            })
+            .init()
+    }
+    
+    // インスペクト結果の1項目を報告する観測型ゲージを作成
+    fn init_inspect_gauge<F>(
+        meter: &opentelemetry::metrics::Meter,
+        snapshot: &InspectSnapshot,
+        name: &'static str,
+        description: &'static str,
+        unit: Option<&'static str>,
+        value: F,
+    ) -> ObservableGauge<i64>
+    where
+        F: Fn(&ContainerDetails) -> Option<i64> + Send + Sync + 'static,
+    {
+        let snapshot = snapshot.clone();
+        let mut builder = meter
+            .i64_observable_gauge(name)
+            .with_description(description);
+        if let Some(unit) = unit {
+            builder = builder.with_unit(Unit::new(unit));
+        }
+        
+        builder
+            .with_callback(move |instrument| {
+                let Ok(snapshot) = snapshot.read() else { return };
+                for (labels, details) in snapshot.iter() {
+                    if let Some(value) = value(details) {
+                        instrument.observe(value, labels);
+                    }
+                }
+            })
             .init()
     }
     
-    /// メトリクスを収集
-    #[instrument(skip(self), level = "debug")]
+    /// メトリクスを収集し、ホストの収集状態をセルフメトリクスとして記録
+    #[instrument(skip(self), fields(host = %self.host), level = "debug")]
     pub async fn collect_metrics(&mut self) -> Result<()> {
+        let started = Instant::now();
+        let result = self.collect_cycle().await;
+        
+        let host_labels = [KeyValue::new("host", self.host.clone())];
+        self.collection_duration.record(started.elapsed().as_secs_f64(), &host_labels);
+        if result.is_err() {
+            self.collection_errors.add(1, &host_labels);
+        }
+        self.update_host_health(result.is_ok(), &host_labels);
+        
+        result
+    }
+    
+    // ホストの稼働状態を更新（UpDownCounterには前回値との差分を加算）
+    fn update_host_health(&mut self, healthy: bool, host_labels: &[KeyValue]) {
+        let previous = self.host_healthy.map_or(0, i64::from);
+        self.host_up.add(i64::from(healthy) - previous, host_labels);
+        
+        if self.host_healthy != Some(healthy) {
+            info!(host = %self.host, healthy, "Docker host health changed");
+        }
+        self.host_healthy = Some(healthy);
+    }
+    
+    // 1回の収集サイクル
+    async fn collect_cycle(&mut self) -> Result<()> {
         debug!("Starting metrics collection cycle");
         // フィルタに従ってコンテナのリストを取得
         let mut containers = self.docker_client.list_filtered_containers(&self.config.container_filters).await?;
         
+        // Kubernetesのポッド情報を付与し、pauseコンテナを除外
+        if let Some(kubernetes) = &mut self.kubernetes {
+            kubernetes.enrich(&mut containers).await;
+        }
+        
         // コンテナ数メトリクスを更新
         self.update_container_count_metrics(&containers);
         
-        // 実行中のコンテナの統計情報を収集
-        self.docker_client.collect_container_stats(&mut containers).await?;
+        // ヘルスチェックや再起動回数などのインスペクト結果を更新
+        if self.config.enable_inspect {
+            self.refresh_container_details(&mut containers).await;
+        }
+        
+        // Dockerデーモン情報を更新
+        if self.config.enable_host {
+            self.refresh_daemon_info().await;
+        }
+        
+        // ディスク使用量は収集間隔ごとにバックグラウンドで更新
+        if self.config.enable_disk_usage {
+            self.schedule_disk_usage_refresh(&containers);
+        }
+        
+        // 実行中のコンテナの統計情報を収集（遅いコンテナはstaleとして残りを先に処理）
+        self.docker_client.collect_container_stats(
+            &mut containers,
+            self.config.stats_concurrency,
+            Duration::from_secs(self.config.stats_timeout),
+        ).await?;
+        
+        let stale_count = containers.iter().filter(|c| c.stale).count() as u64;
+        if stale_count > 0 {
+            self.stats_timeouts.add(stale_count, &[KeyValue::new("host", self.host.clone())]);
+        }
+        
+        // CPU使用率上位のプロセスを取得
+        if self.config.top_processes > 0 {
+            self.collect_top_processes(&mut containers).await;
+        }
         
         // メトリクスを処理して記録
         self.process_metrics(&containers).await?;
         
         // 古い値を削除（存在しなくなったコンテナ）
         self.cleanup_previous_values(&containers).await;
+        self.update_inventory(&containers);
+        
+        // 購読者へ収集結果を配信（購読者がいない場合の送信エラーは無視）
+        let _ = self.events.send(StreamEvent::Cycle {
+            host: self.host.clone(),
+            timestamp: chrono::Utc::now().timestamp(),
+            containers,
+        });
         
         debug!("Metrics collection cycle completed");
         Ok(())
     }
     
+    // 観測したコンテナの一覧を更新（復元した一覧があれば、停止中に消えたコンテナを記録）
+    fn update_inventory(&mut self, containers: &[ContainerInfo]) {
+        self.inventory = containers.iter()
+            .map(|c| InventoryEntry {
+                id: c.id.clone(),
+                name: c.name.clone(),
+                image: c.image.clone(),
+                status: c.status.clone(),
+                started_at: Self::container_started_at(c),
+            })
+            .collect();
+        
+        if let Some(restored) = self.restored_inventory.take() {
+            let current_ids: HashSet<&str> = self.inventory.iter().map(|entry| entry.id.as_str()).collect();
+            let removed: Vec<&str> = restored.iter()
+                .filter(|entry| !current_ids.contains(entry.id.as_str()))
+                .map(|entry| entry.name.as_str())
+                .collect();
+            if !removed.is_empty() {
+                info!(host = %self.host, "{} containers disappeared while the agent was stopped: {}", removed.len(), removed.join(", "));
+            }
+        }
+    }
+    
     // コンテナ数メトリクスを更新
     fn update_container_count_metrics(&self, containers: &[ContainerInfo]) {
         let running_containers = containers.iter().filter(|c| c.status == "running").count() as i64;
         let total_containers = containers.len() as i64;
         
-        self.container_count.add(running_containers, &[KeyValue::new("status", "running")]);
-        self.container_count.add(total_containers - running_containers, &[KeyValue::new("status", "not_running")]);
+        self.container_count.add(running_containers, &[
+            KeyValue::new("host", self.host.clone()),
+            KeyValue::new("status", "running"),
+        ]);
+        self.container_count.add(total_containers - running_containers, &[
+            KeyValue::new("host", self.host.clone()),
+            KeyValue::new("status", "not_running"),
+        ]);
         
         debug!(
             running = running_containers,
@@ -180,46 +815,298 @@ impl MetricsCollector {
         );
     }
     
+    // コンテナごとのメトリクスに付与するラベル（metrics.cardinality.container_labelsで選択）
+    fn container_labels(&self, container: &ContainerInfo) -> Vec<KeyValue> {
+        self.labels_with(&self.label_set, container)
+    }
+    
+    // 累計値のカウンターに付与するラベル（metrics.cardinality.counter_labelsで選択）
+    fn counter_labels(&self, container: &ContainerInfo) -> Vec<KeyValue> {
+        self.labels_with(&self.counter_label_set, container)
+    }
+    
+    // 選択したラベルを作成し、Kubernetesのポッド情報があればpod、namespace、workload、nodeを追加
+    fn labels_with(&self, label_set: &ContainerLabels, container: &ContainerInfo) -> Vec<KeyValue> {
+        let mut labels = label_set.labels(&self.host, &container.id, &container.name, &container.image);
+        if let Some(pod) = &container.pod {
+            labels.extend(pod.labels());
+        }
+        labels
+    }
+    
+    // Dockerデーモン情報を取得してゲージの系列を更新（失敗しても収集サイクルは継続）
+    async fn refresh_daemon_info(&self) {
+        let timeout = Duration::from_secs(self.config.stats_timeout);
+        let info = match tokio::time::timeout(timeout, self.docker_client.get_info()).await {
+            Ok(Ok(info)) => info,
+            Ok(Err(e)) => {
+                warn!(host = %self.host, "{}", e);
+                return;
+            }
+            Err(_) => {
+                warn!(host = %self.host, "Timed out getting Docker system info");
+                return;
+            }
+        };
+        
+        let host = KeyValue::new("host", self.host.clone());
+        if let Ok(mut images) = self.daemon_images.write() {
+            *images = vec![(vec![host.clone()], info.images.unwrap_or(0))];
+        }
+        if let Ok(mut daemon_info) = self.daemon_info.write() {
+            *daemon_info = vec![(
+                vec![
+                    host,
+                    KeyValue::new("storage_driver", info.driver.unwrap_or_default()),
+                    KeyValue::new("server_version", info.server_version.unwrap_or_default()),
+                    KeyValue::new("operating_system", info.operating_system.unwrap_or_default()),
+                ],
+                1,
+            )];
+        }
+    }
+    
+    // 前回の更新から収集間隔が経過し、実行中の更新がなければディスク使用量の更新を開始
+    fn schedule_disk_usage_refresh(&mut self, containers: &[ContainerInfo]) {
+        let interval = Duration::from_secs(self.config.disk_usage_interval);
+        if let Some((started_at, handle)) = &self.disk_usage_refresh {
+            if !handle.is_finished() || started_at.elapsed() < interval {
+                return;
+            }
+        }
+        
+        let docker_client = self.docker_client.clone();
+        let host = self.host.clone();
+        let series = self.disk_usage_series.clone();
+        let container_labels: HashMap<String, Vec<KeyValue>> = containers.iter()
+            .map(|c| (c.id.clone(), self.container_labels(c)))
+            .collect();
+        
+        let handle = tokio::spawn(async move {
+            let started = Instant::now();
+            match docker_client.disk_usage().await {
+                Ok(usage) => {
+                    debug!(host = %host, elapsed_ms = started.elapsed().as_millis() as u64, "Disk usage refreshed");
+                    let new_series = DiskUsageSeries::new(&host, &container_labels, usage);
+                    if let Ok(mut series) = series.write() {
+                        *series = new_series;
+                    }
+                }
+                Err(e) => warn!(host = %host, "{}", e),
+            }
+        });
+        self.disk_usage_refresh = Some((Instant::now(), handle));
+    }
+    
+    // 受信済みのライフサイクルイベントに対応するインスペクトキャッシュを無効化
+    fn apply_container_events(&mut self) {
+        loop {
+            match self.event_receiver.try_recv() {
+                Ok(StreamEvent::Lifecycle(event)) if event.host == self.host => {
+                    self.inspect_cache.remove(&event.container_id);
+                }
+                Ok(_) => {}
+                Err(TryRecvError::Lagged(skipped)) => {
+                    // 取りこぼしたイベントがあるため、すべて再取得する
+                    debug!("Missed {} container events, invalidating inspect cache", skipped);
+                    self.inspect_cache.clear();
+                }
+                Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => break,
+            }
+        }
+    }
+    
+    // コンテナのインスペクト結果を更新（キャッシュが有効な間はAPIを呼ばない）
+    // 停止したコンテナは終了コードやOOMが変わらないため、die/oomイベントでキャッシュが消えたときだけ再取得する
+    async fn refresh_container_details(&mut self, containers: &mut [ContainerInfo]) {
+        self.apply_container_events();
+        
+        let max_age = Duration::from_secs(self.config.inspect_interval);
+        let expired_ids: Vec<String> = containers.iter()
+            .filter(|c| self.inspect_cache.get(&c.id).is_none_or(|(_, fetched_at)| {
+                (c.status == "running" || c.status == "restarting") && fetched_at.elapsed() >= max_age
+            }))
+            .map(|c| c.id.clone())
+            .collect();
+        
+        let docker_client = &self.docker_client;
+        let timeout = Duration::from_secs(self.config.stats_timeout);
+        let results: Vec<(String, Option<Result<ContainerDetails>>)> = futures::stream::iter(expired_ids)
+            .map(|container_id| async move {
+                let result = tokio::time::timeout(timeout, docker_client.inspect_container(&container_id))
+                    .await
+                    .ok();
+                (container_id, result)
+            })
+            .buffer_unordered(self.config.stats_concurrency.max(1))
+            .collect()
+            .await;
+        
+        for (container_id, result) in results {
+            match result {
+                Some(Ok(details)) => {
+                    self.inspect_cache.insert(container_id, (details, Instant::now()));
+                }
+                Some(Err(e)) => warn!("{}", e),
+                None => warn!("Timed out inspecting container {}", container_id),
+            }
+        }
+        
+        for container in containers.iter_mut() {
+            container.details = self.inspect_cache.get(&container.id).map(|(details, _)| details.clone());
+        }
+        
+        // インスペクト結果は合計できないため、上限を超えた系列は報告しない
+        let snapshot: Vec<(Vec<KeyValue>, ContainerDetails)> = containers.iter()
+            .filter_map(|c| c.details.clone().map(|details| (self.container_labels(c), details)))
+            .collect();
+        let keys: Vec<String> = snapshot.iter().map(|(labels, _)| cardinality::series_key(labels)).collect();
+        let selected = self.limiter.lock().await.select("container_inspect", &keys);
+        let snapshot = snapshot.into_iter()
+            .zip(selected)
+            .filter_map(|(series, selected)| selected.then_some(series))
+            .collect();
+        if let Ok(mut inspect_snapshot) = self.inspect_snapshot.write() {
+            *inspect_snapshot = snapshot;
+        }
+    }
+    
+    // 実行中のコンテナのプロセス一覧を取得し、CPU使用率上位のプロセスを残す
+    async fn collect_top_processes(&mut self, containers: &mut [ContainerInfo]) {
+        let running_ids: Vec<String> = containers.iter()
+            .filter(|c| c.status == "running")
+            .map(|c| c.id.clone())
+            .collect();
+        
+        let docker_client = &self.docker_client;
+        let timeout = Duration::from_secs(self.config.stats_timeout);
+        let results: Vec<(String, Option<Result<Vec<ProcessInfo>>>)> = futures::stream::iter(running_ids)
+            .map(|container_id| async move {
+                let result = tokio::time::timeout(timeout, docker_client.top_processes(&container_id))
+                    .await
+                    .ok();
+                (container_id, result)
+            })
+            .buffer_unordered(self.config.stats_concurrency.max(1))
+            .collect()
+            .await;
+        
+        // 終了したプロセスの前回値が残らないよう、今回観測したプロセスだけで置き換える
+        let now = Instant::now();
+        let mut prev_process_cpu = HashMap::new();
+        let mut cpu_series = Vec::new();
+        let mut rss_series = Vec::new();
+        
+        for (container_id, result) in results {
+            let mut processes = match result {
+                Some(Ok(processes)) => processes,
+                Some(Err(e)) => {
+                    warn!("{}", e);
+                    continue;
+                }
+                None => {
+                    warn!("Timed out listing processes of container {}", container_id);
+                    continue;
+                }
+            };
+            
+            for process in processes.iter_mut() {
+                let key = (container_id.clone(), process.pid);
+                if let Some((prev_cpu_seconds, prev_at)) = self.prev_process_cpu.get(&key) {
+                    let elapsed = now.duration_since(*prev_at).as_secs_f64();
+                    if elapsed > 0.0 && process.cpu_seconds >= *prev_cpu_seconds {
+                        process.cpu_usage_percent = (process.cpu_seconds - prev_cpu_seconds) / elapsed * 100.0;
+                    }
+                }
+                prev_process_cpu.insert(key, (process.cpu_seconds, now));
+            }
+            
+            processes.sort_by(|a, b| b.cpu_usage_percent.total_cmp(&a.cpu_usage_percent));
+            processes.truncate(self.config.top_processes);
+            
+            // PIDはプロセスが入れ替わるたびに系列が増えるため、ラベルには順位とコマンド名を使う
+            if let Some(container) = containers.iter_mut().find(|c| c.id == container_id) {
+                let container_labels = self.container_labels(container);
+                for (rank, process) in processes.iter().enumerate() {
+                    let mut labels = container_labels.clone();
+                    labels.push(KeyValue::new("rank", rank as i64 + 1));
+                    labels.push(KeyValue::new("command", process.command.clone()));
+                    cpu_series.push((labels.clone(), process.cpu_usage_percent));
+                    rss_series.push((labels, process.rss_bytes as i64));
+                }
+                container.processes = processes;
+            }
+        }
+        
+        self.prev_process_cpu = prev_process_cpu;
+        if let Ok(mut series) = self.process_cpu_usage.write() {
+            *series = cpu_series;
+        }
+        if let Ok(mut series) = self.process_memory_rss.write() {
+            *series = rss_series;
+        }
+    }
+    
     // 収集したメトリクスを処理して記録
     async fn process_metrics(&self, containers: &[ContainerInfo]) -> Result<()> {
+        let mut series = ContainerSeries::default();
+        
         for container in containers.iter().filter(|c| c.status == "running") {
             if let Some(stats) = &container.stats {
-                let labels = &[
-                    KeyValue::new("container_id", container.id.clone()),
-                    KeyValue::new("container_name", container.name.clone()),
-                    KeyValue::new("image", container.image.clone()),
-                ];
+                let labels = &self.container_labels(container);
+                series.pids.push((labels.clone(), stats.pids as i64));
                 
                 // 有効化されたメトリクスを処理
-                self.process_enabled_metrics(container, stats, labels).await?;
+                self.process_enabled_metrics(container, stats, labels, &mut series).await?;
             }
         }
         
+        // メトリクスごとの系列数を制限して、前回の系列を置き換える
+        let mut limiter = self.limiter.lock().await;
+        Self::replace_series(&self.pids, limiter.limit("container_pids", &self.host, series.pids));
+        Self::replace_series(&self.cpu_usage, limiter.limit("container_cpu_usage_percent", &self.host, series.cpu_usage));
+        Self::replace_series(&self.memory_usage, limiter.limit("container_memory_usage_bytes", &self.host, series.memory_usage));
+        Self::replace_series(&self.memory_limit, limiter.limit("container_memory_limit_bytes", &self.host, series.memory_limit));
+        Self::replace_series(&self.memory_usage_percent, limiter.limit("container_memory_usage_percent", &self.host, series.memory_usage_percent));
+        
         Ok(())
     }
     
+    // ゲージが報告する系列を置き換え
+    fn replace_series<T>(series: &Series<T>, new_series: Vec<(Vec<KeyValue>, T)>) {
+        if let Ok(mut series) = series.write() {
+            *series = new_series;
+        }
+    }
+    
     // 有効化されたメトリクスのみを処理
-    async fn process_enabled_metrics(&self, container: &ContainerInfo, stats: &crate::docker::ContainerStats, labels: &[KeyValue]) -> Result<()> {
+    async fn process_enabled_metrics(
+        &self,
+        container: &ContainerInfo,
+        stats: &crate::docker::ContainerStats,
+        labels: &[KeyValue],
+        series: &mut ContainerSeries,
+    ) -> Result<()> {
         // CPU メトリクス
         if self.config.enable_cpu {
-            self.cpu_usage.record(stats.cpu_usage_percent, labels);
+            series.cpu_usage.push((labels.to_vec(), stats.cpu_usage_percent));
         }
         
         // メモリ メトリクス
         if self.config.enable_memory {
-            self.memory_usage.add(stats.memory_usage_bytes, labels);
-            self.memory_limit.add(stats.memory_limit_bytes, labels);
-            self.memory_usage_percent.record(stats.memory_usage_percent, labels);
+            series.memory_usage.push((labels.to_vec(), stats.memory_usage_bytes as i64));
+            series.memory_limit.push((labels.to_vec(), stats.memory_limit_bytes as i64));
+            series.memory_usage_percent.push((labels.to_vec(), stats.memory_usage_percent));
         }
         
-        // ネットワーク メトリクス
+        // ネットワーク・ディスク I/O メトリクス（累計値はカウンター用のラベルで記録）
+        let counter_labels = &self.counter_labels(container);
         if self.config.enable_network {
-            self.process_network_metrics(container, stats, labels).await?;
+            self.process_network_metrics(container, stats, counter_labels).await?;
         }
         
-        // ディスク I/O メトリクス
         if self.config.enable_disk {
-            self.process_disk_metrics(container, stats, labels).await?;
+            self.process_disk_metrics(container, stats, counter_labels).await?;
         }
         
         Ok(())
@@ -227,63 +1114,123 @@ impl MetricsCollector {
     
     // ネットワークメトリクスを処理
     async fn process_network_metrics(&self, container: &ContainerInfo, stats: &crate::docker::ContainerStats, labels: &[KeyValue]) -> Result<()> {
-        let mut prev_rx = self.prev_network_rx.lock().await;
-        let mut prev_tx = self.prev_network_tx.lock().await;
-        
-        let rx_delta = self.calculate_delta(&mut prev_rx, &container.id, stats.network_rx_bytes);
-        let tx_delta = self.calculate_delta(&mut prev_tx, &container.id, stats.network_tx_bytes);
+        let started_at = Self::container_started_at(container);
+        let mut deltas = self.deltas.lock().await;
+        let mut limiter = self.limiter.lock().await;
         
-        if rx_delta > 0 {
-            self.network_receive_bytes.add(rx_delta, labels);
-        }
+        let rx_delta = deltas.delta(&format!("{}/network_rx", container.id), started_at, stats.network_rx_bytes);
+        let tx_delta = deltas.delta(&format!("{}/network_tx", container.id), started_at, stats.network_tx_bytes);
         
-        if tx_delta > 0 {
-            self.network_transmit_bytes.add(tx_delta, labels);
-        }
+        self.add_total(&mut limiter, &self.network_receive_bytes, "container_network_receive_bytes_total", labels, container, rx_delta);
+        self.add_total(&mut limiter, &self.network_transmit_bytes, "container_network_transmit_bytes_total", labels, container, tx_delta);
         
         Ok(())
     }
     
     // ディスクメトリクスを処理
     async fn process_disk_metrics(&self, container: &ContainerInfo, stats: &crate::docker::ContainerStats, labels: &[KeyValue]) -> Result<()> {
-        let mut prev_reads = self.prev_fs_reads.lock().await;
-        let mut prev_writes = self.prev_fs_writes.lock().await;
+        let started_at = Self::container_started_at(container);
+        let mut deltas = self.deltas.lock().await;
+        let mut limiter = self.limiter.lock().await;
         
-        let reads_delta = self.calculate_delta(&mut prev_reads, &container.id, stats.block_read_bytes);
-        let writes_delta = self.calculate_delta(&mut prev_writes, &container.id, stats.block_write_bytes);
+        let reads_delta = deltas.delta(&format!("{}/fs_reads", container.id), started_at, stats.block_read_bytes);
+        let writes_delta = deltas.delta(&format!("{}/fs_writes", container.id), started_at, stats.block_write_bytes);
         
-        if reads_delta > 0 {
-            self.fs_reads_bytes.add(reads_delta, labels);
-        }
+        self.add_total(&mut limiter, &self.fs_reads_bytes, "container_fs_reads_bytes_total", labels, container, reads_delta);
+        self.add_total(&mut limiter, &self.fs_writes_bytes, "container_fs_writes_bytes_total", labels, container, writes_delta);
         
-        if writes_delta > 0 {
-            self.fs_writes_bytes.add(writes_delta, labels);
+        // デバイスごとのバイト数と操作回数
+        for device in &stats.block_devices {
+            let device_name = self.block_device_name(device.major, device.minor).await;
+            let counters = [
+                ("read", device.read_bytes, device.read_ops),
+                ("write", device.write_bytes, device.write_ops),
+                ("discard", device.discard_bytes, device.discard_ops),
+            ];
+            
+            for (operation, bytes, ops) in counters {
+                // 使われていない操作（discardなど）の系列は作らない
+                if bytes == 0 && ops == 0 {
+                    continue;
+                }
+                
+                let key = format!("{}/{}:{}/{}", container.id, device.major, device.minor, operation);
+                let bytes_delta = deltas.delta(&format!("{}/bytes", key), started_at, bytes);
+                let ops_delta = deltas.delta(&format!("{}/ops", key), started_at, ops);
+                
+                let mut device_labels = labels.to_vec();
+                device_labels.push(KeyValue::new("device", device_name.clone()));
+                device_labels.push(KeyValue::new("operation", operation));
+                
+                self.add_total(&mut limiter, &self.blkio_device_bytes, "container_blkio_device_bytes_total", &device_labels, container, bytes_delta);
+                self.add_total(&mut limiter, &self.blkio_device_operations, "container_blkio_device_operations_total", &device_labels, container, ops_delta);
+            }
         }
         
         Ok(())
     }
     
-    /// カウンター型メトリクスのためのデルタ値を計算
-    #[instrument(skip(self, prev_values), fields(container_id = container_id, current_value = current_value), level = "debug")]
-    fn calculate_delta(&self, prev_values: &mut HashMap<String, u64>, container_id: &str, current_value: u64) -> u64 {
-        let prev_value = prev_values.get(container_id).copied().unwrap_or(0);
-        
-        // 前回値を更新
-        prev_values.insert(container_id.to_string(), current_value);
+    // コンテナの開始時刻（inspectの結果がない場合はNone）
+    fn container_started_at(container: &ContainerInfo) -> Option<i64> {
+        container.details.as_ref().and_then(|details| details.started_at)
+    }
+    
+    // 系列の累計値に増加分を加算（初回は増加分が0でも系列を作成し、上限を超えた系列はoverflow系列に加算）
+    // 系列が1つのコンテナのみを累計していて、そのコンテナで新しい実行が始まった場合は、
+    // その実行の値から累計し直し、開始時刻をStartedAtにする（複数のコンテナの系列は加算を続ける）
+    fn add_total(&self, limiter: &mut SeriesLimiter, totals: &CounterTotals, metric: &str, labels: &[KeyValue], container: &ContainerInfo, delta: u64) {
+        let run_started_at = Self::container_started_at(container);
+        let admitted = limiter.admit(metric, labels);
+        let labels = if admitted { labels.to_vec() } else { cardinality::overflow_labels(&self.host) };
         
-        // デルタを計算（カウンターリセット対応）
-        if current_value >= prev_value {
-            current_value - prev_value
-        } else {
-            // カウンターリセットの場合は現在値をそのまま返す
-            debug!("Counter reset detected for {}", container_id);
-            current_value
+        let Ok(mut totals) = totals.write() else { return };
+        let key = total_key(&labels);
+        match totals.get_mut(&key) {
+            Some(total) => {
+                let previous_run = total.runs.insert(container.id.clone(), run_started_at);
+                let restarted = matches!((previous_run, run_started_at), (Some(Some(previous)), Some(run)) if previous != run);
+                match run_started_at {
+                    Some(run) if restarted && admitted && total.runs.len() == 1 => {
+                        total.value = delta;
+                        total.start_time = UNIX_EPOCH + Duration::from_secs(run.max(0) as u64);
+                    }
+                    _ => total.value += delta,
+                }
+            }
+            None => {
+                let runs = HashMap::from([(container.id.clone(), run_started_at)]);
+                totals.insert(key, CounterTotal { labels, value: delta, runs, start_time: SystemTime::now() });
+            }
         }
     }
     
+    // 観測型カウンターの系列の累計値
+    fn counter_totals(&self) -> [(&'static str, &CounterTotals); 6] {
+        [
+            ("container_network_receive_bytes_total", &self.network_receive_bytes),
+            ("container_network_transmit_bytes_total", &self.network_transmit_bytes),
+            ("container_fs_reads_bytes_total", &self.fs_reads_bytes),
+            ("container_fs_writes_bytes_total", &self.fs_writes_bytes),
+            ("container_blkio_device_bytes_total", &self.blkio_device_bytes),
+            ("container_blkio_device_operations_total", &self.blkio_device_operations),
+        ]
+    }
+    
+    // major:minorをデバイス名に解決（解決できない場合は"major:minor"）
+    async fn block_device_name(&self, major: u64, minor: u64) -> String {
+        let mut device_names = self.device_names.lock().await;
+        device_names
+            .entry((major, minor))
+            .or_insert_with(|| {
+                docker::block_device_name(Path::new(&self.config.sysfs_path), major, minor)
+                    .unwrap_or_else(|| format!("{}:{}", major, minor))
+            })
+            .clone()
+    }
+    
     /// 存在しなくなったコンテナの前回値を削除
     #[instrument(skip(self, containers), level = "debug")]
-    async fn cleanup_previous_values(&self, containers: &[ContainerInfo]) {
+    async fn cleanup_previous_values(&mut self, containers: &[ContainerInfo]) {
         let container_ids: HashSet<String> = containers
             .iter()
             .map(|c| c.id.clone())
@@ -291,36 +1238,30 @@ impl MetricsCollector {
         
         let mut cleaned_up = 0;
         
-        // 各前回値マップをクリーンアップ
-        {
-            let mut prev_rx = self.prev_network_rx.lock().await;
-            cleaned_up += Self::cleanup_previous_map(&mut prev_rx, &container_ids);
-        }
+        // 前回値のキーは先頭がコンテナID
+        cleaned_up += self.deltas.lock().await.retain(|key| {
+            key.split('/').next().is_some_and(|id| container_ids.contains(id))
+        });
         
-        {
-            let mut prev_tx = self.prev_network_tx.lock().await;
-            cleaned_up += Self::cleanup_previous_map(&mut prev_tx, &container_ids);
-        }
+        self.inspect_cache.retain(|id, _| container_ids.contains(id));
         
-        {
-            let mut prev_reads = self.prev_fs_reads.lock().await;
-            cleaned_up += Self::cleanup_previous_map(&mut prev_reads, &container_ids);
-        }
-        
-        {
-            let mut prev_writes = self.prev_fs_writes.lock().await;
-            cleaned_up += Self::cleanup_previous_map(&mut prev_writes, &container_ids);
+        // 累計値の系列は残し、加算していたコンテナの実行のみを削除
+        for (_, totals) in self.counter_totals() {
+            if let Ok(mut totals) = totals.write() {
+                for total in totals.values_mut() {
+                    total.runs.retain(|id, _| container_ids.contains(id));
+                }
+            }
         }
         
         if cleaned_up > 0 {
             debug!("Cleaned up {} previous value entries", cleaned_up);
         }
     }
-    
-    // 前回値マップのクリーンアップヘルパー
-    fn cleanup_previous_map(map: &mut HashMap<String, u64>, valid_ids: &HashSet<String>) -> usize {
-        let before_count = map.len();
-        map.retain(|id, _| valid_ids.contains(id));
-        before_count - map.len()
+}
+
+impl Drop for MetricsCollector {
+    fn drop(&mut self) {
+        self.abort();
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,534 @@
+use anyhow::{bail, Result};
+use opentelemetry::metrics::{Meter, MeterProvider as _, UpDownCounter};
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::metrics::MeterProvider;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::agent::AgentCollector;
+use crate::config::{load_config, Config, DockerConfig, DockerHostConfig, SinkConfig};
+use crate::docker::{ContainerInfo, DockerClient};
+use crate::host::HostCollector;
+use crate::log_export::LogForwarder;
+use crate::logs::LogCollector;
+use crate::metrics::{host_up_counter, CounterStartTimes, MetricsCollector};
+use crate::remote_write::RemoteWriter;
+use crate::resource::telemetry_resource;
+use crate::schedule::CollectionSchedule;
+use crate::server::start_metrics_server;
+use crate::state::{HostState, StateStore};
+use crate::stream::{self, forward_container_events, StreamEvent};
+use crate::telemetry::build_meter_provider_with_start_times;
+
+/// 計装スコープ名（メーターを指定しない場合に使用）
+const METER_NAME: &str = "container-monitoring";
+
+/// 接続できないDockerホストへの再接続間隔（失敗するたびに倍にし、上限で止める）
+const INITIAL_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(60);
+
+// 接続済みのホストのメトリクスコレクター（終了時に状態を保存する）
+type Collectors = Arc<RwLock<Vec<Arc<Mutex<MetricsCollector>>>>>;
+
+/// コレクターを他のRustサービスに組み込むためのビルダー
+///
+/// メーターを指定した場合、メトリクスは呼び出し側のMeterProviderに記録されます。
+/// 指定しない場合は`telemetry`の設定（OTLP、シンク）からMeterProviderを作成し、
+/// `shutdown`で送信しきってから終了します。
+pub struct MonitorBuilder {
+    config: Config,
+    meter: Option<Meter>,
+    counter_start_times: Option<CounterStartTimes>,
+    sinks: Option<Vec<SinkConfig>>,
+    metrics_server: bool,
+}
+
+impl MonitorBuilder {
+    /// 設定を指定してビルダーを作成
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            meter: None,
+            counter_start_times: None,
+            sinks: None,
+            metrics_server: false,
+        }
+    }
+
+    /// 設定ファイルを読み込んでビルダーを作成
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(load_config(path)?))
+    }
+
+    /// メトリクスを記録するメーターを指定
+    pub fn with_meter(mut self, meter: Meter) -> Self {
+        self.meter = Some(meter);
+        self
+    }
+
+    /// 累計値のカウンターの開始時刻を記録する一覧を指定
+    ///
+    /// `with_meter`のメーターのMeterProviderを`build_meter_provider_with_start_times`
+    /// （または`init_telemetry`）で作成した場合に、その一覧を指定します。
+    pub fn with_counter_start_times(mut self, start_times: CounterStartTimes) -> Self {
+        self.counter_start_times = Some(start_times);
+        self
+    }
+
+    /// メトリクスの送信先のシンクを指定（`telemetry.sinks`を置き換え）
+    ///
+    /// シンクはモニターが作成するMeterProviderに追加されるため、`with_meter`とは併用できません。
+    pub fn with_sinks(mut self, sinks: Vec<SinkConfig>) -> Self {
+        self.sinks = Some(sinks);
+        self
+    }
+
+    /// `/metrics`などを提供するHTTPサーバー（`telemetry.prometheus_port`）を起動するか（デフォルトは起動しない）
+    pub fn with_metrics_server(mut self, enabled: bool) -> Self {
+        self.metrics_server = enabled;
+        self
+    }
+
+    /// モニターを作成（Dockerへの接続は`start`で行います）
+    pub fn build(self) -> Result<Monitor> {
+        let mut config = self.config;
+        if let Some(sinks) = self.sinks {
+            if self.meter.is_some() {
+                bail!("Sinks cannot be combined with a custom meter; add the exporters to the meter's provider instead");
+            }
+            config.telemetry.sinks = sinks;
+        }
+
+        // モニターごとの一覧のため、同じプロセスの他のモニターの系列の開始時刻とは混ざらない
+        let counter_start_times = self.counter_start_times.unwrap_or_default();
+        let (meter, meter_provider) = match self.meter {
+            Some(meter) => (meter, None),
+            None => {
+                // /metricsと同じくデフォルトのPrometheusレジストリにも記録する
+                let resource = telemetry_resource(&config.telemetry)?;
+                let meter_provider = build_meter_provider_with_start_times(
+                    &config.telemetry,
+                    resource,
+                    prometheus::default_registry(),
+                    &counter_start_times,
+                )?;
+                (meter_provider.meter(METER_NAME), Some(meter_provider))
+            }
+        };
+
+        Ok(Monitor {
+            config,
+            meter,
+            meter_provider,
+            counter_start_times,
+            metrics_server: self.metrics_server,
+            events: stream::channel(),
+            snapshots: Arc::new(RwLock::new(BTreeMap::new())),
+            collectors: Arc::new(RwLock::new(Vec::new())),
+            handles: Vec::new(),
+            state_store: None,
+            log_forwarder: None,
+        })
+    }
+}
+
+/// ホストごとの直近の収集サイクルの結果
+#[derive(Debug, Clone, Serialize)]
+pub struct HostSnapshot {
+    pub host: String,
+    /// 収集時刻（UNIX時間、秒）
+    pub timestamp: i64,
+    pub containers: Vec<ContainerInfo>,
+}
+
+/// 全ホストの直近の収集結果（まだ収集していないホストは含まれません）
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Snapshot {
+    pub hosts: Vec<HostSnapshot>,
+}
+
+/// 組み込んだコレクターのハンドル
+pub struct Monitor {
+    config: Config,
+    meter: Meter,
+    // モニターが作成した場合のみ保持し、shutdownで送信しきる
+    meter_provider: Option<MeterProvider>,
+    counter_start_times: CounterStartTimes,
+    metrics_server: bool,
+    events: broadcast::Sender<StreamEvent>,
+    snapshots: Arc<RwLock<BTreeMap<String, HostSnapshot>>>,
+    collectors: Collectors,
+    handles: Vec<JoinHandle<()>>,
+    state_store: Option<Arc<StateStore>>,
+    log_forwarder: Option<LogForwarder>,
+}
+
+impl Monitor {
+    /// Dockerホストに接続し、収集ループを開始
+    ///
+    /// 接続できないホストは`container_monitoring_host_up`を0として報告し、間隔を空けながら再接続します。
+    pub async fn start(&mut self) -> Result<()> {
+        if !self.handles.is_empty() {
+            bail!("Monitor is already running");
+        }
+
+        let result = self.spawn_tasks().await;
+        if result.is_err() {
+            // 途中まで開始したタスクを止め、もう一度`start`を呼び出せるようにする
+            self.abort();
+            self.state_store = None;
+            self.log_forwarder = None;
+            if let Ok(mut collectors) = self.collectors.write() {
+                collectors.clear();
+            }
+        }
+        result
+    }
+
+    // 失敗しうる準備（スケジュール、状態ファイル、送信先）をすべて終えてから各タスクを開始
+    async fn spawn_tasks(&mut self) -> Result<()> {
+        let config = self.config.clone();
+        let general = &config.general;
+        let schedule = |name: &str, interval: Option<u64>| {
+            CollectionSchedule::new(name, Duration::from_secs(interval.unwrap_or(general.interval)), general)
+        };
+
+        // カウンターの基準値と前回のインベントリを状態ファイルから復元
+        let mut restored_state = match &config.state.path {
+            Some(path) => {
+                let (state_store, restored_state) = StateStore::open(path, &config.state)?;
+                self.state_store = Some(Arc::new(state_store));
+                restored_state
+            }
+            None => Default::default(),
+        };
+
+        // 全ホストのログ追跡で1つのOTLPログパイプラインを共有
+        if config.container_logs.enabled && config.container_logs.otlp.enabled {
+            self.log_forwarder = Some(LogForwarder::new(&config.container_logs.otlp, &config.telemetry)?);
+        }
+
+        let hosts = config.docker.resolved_hosts();
+        let mut host_schedules = Vec::with_capacity(hosts.len());
+        for host in &hosts {
+            host_schedules.push(HostSchedules {
+                containers: schedule(&format!("containers/{}", host.name), general.intervals.containers)?,
+                logs: if config.container_logs.enabled {
+                    Some(schedule(&format!("logs/{}", host.name), general.intervals.logs)?)
+                } else {
+                    None
+                },
+            });
+        }
+        let remote_writer = if config.remote_write.enabled {
+            Some(RemoteWriter::new(&config.remote_write)?)
+        } else {
+            None
+        };
+        let host_schedule = if config.metrics.enable_host {
+            Some(schedule("host", general.intervals.host)?)
+        } else {
+            None
+        };
+        let agent_schedule = if config.metrics.enable_agent {
+            Some(schedule("agent", general.intervals.agent)?)
+        } else {
+            None
+        };
+
+        let host_context = HostContext {
+            config: Arc::new(config.clone()),
+            meter: self.meter.clone(),
+            counter_start_times: self.counter_start_times.clone(),
+            host_up: host_up_counter(&self.meter),
+            events: self.events.clone(),
+            log_forwarder: self.log_forwarder.clone(),
+            state_store: self.state_store.clone(),
+            collectors: self.collectors.clone(),
+        };
+
+        // 全ホストへの初回の接続は並行して行う
+        let connections: Vec<_> = hosts.iter().map(|host| tokio::spawn(connect(host.connection.clone()))).collect();
+
+        // 遅いホストや停止中のホストが他のホストを止めないよう、ホストごとのタスクで収集する
+        for ((host, connection), schedules) in hosts.into_iter().zip(connections).zip(host_schedules) {
+            let host_state = restored_state.hosts.remove(&host.name);
+
+            match connection.await? {
+                Ok(docker_client) => {
+                    info!(host = %host.name, "Connected to Docker daemon");
+                    let run = host_context.attach(&host.name, docker_client, host_state, schedules)?;
+                    self.handles.push(tokio::spawn(run));
+                }
+                Err(e) => {
+                    error!(host = %host.name, "Failed to connect to Docker daemon: {:#}", e);
+                    // 再接続を待たずに、開始した時点でダウンとして報告する
+                    host_context.host_up.add(0, &[KeyValue::new("host", host.name.clone())]);
+                    let host_context = host_context.clone();
+                    self.handles.push(tokio::spawn(async move {
+                        let docker_client = host_context.reconnect(&host).await;
+                        match host_context.attach(&host.name, docker_client, host_state, schedules) {
+                            Ok(run) => run.await,
+                            Err(e) => error!(host = %host.name, "Failed to start collecting from Docker host: {:#}", e),
+                        }
+                    }));
+                }
+            }
+        }
+
+        // 収集サイクルの結果をスナップショットとして保持
+        let mut receiver = self.events.subscribe();
+        let snapshots = self.snapshots.clone();
+        self.handles.push(tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(StreamEvent::Cycle { host, timestamp, containers }) => {
+                        if let Ok(mut snapshots) = snapshots.write() {
+                            snapshots.insert(host.clone(), HostSnapshot { host, timestamp, containers });
+                        }
+                    }
+                    Ok(StreamEvent::Lifecycle(_)) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        }));
+
+        if self.metrics_server {
+            let events = self.events.clone();
+            let port = config.telemetry.prometheus_port;
+            let server_config = config.server.clone();
+            self.handles.push(tokio::spawn(async move {
+                if let Err(e) = start_metrics_server(events, port, &server_config).await {
+                    warn!("Metrics server error: {}", e);
+                }
+            }));
+        }
+
+        if let Some(remote_writer) = remote_writer {
+            self.handles.push(tokio::spawn(remote_writer.run()));
+        }
+
+        // ホストのメトリクスはエージェントが動作するマシンのprocfsから読み取る
+        if let Some(mut host_schedule) = host_schedule {
+            let mut host_collector = HostCollector::new_with_meter(&config.metrics.procfs_path, &self.meter);
+            self.handles.push(tokio::spawn(async move {
+                loop {
+                    host_schedule.tick().await;
+
+                    let started = Instant::now();
+                    if let Err(e) = host_collector.collect().await {
+                        warn!("Error collecting host metrics: {:#}", e);
+                    }
+                    host_schedule.record_cycle(started.elapsed());
+                }
+            }));
+        }
+
+        // エージェント（組み込んだ場合は呼び出し側のプロセス）自身のリソース使用量
+        if let Some(mut agent_schedule) = agent_schedule {
+            let mut agent_collector = AgentCollector::new_with_meter(&self.meter);
+            self.handles.push(tokio::spawn(async move {
+                loop {
+                    agent_schedule.tick().await;
+
+                    let started = Instant::now();
+                    if let Err(e) = agent_collector.collect() {
+                        warn!("Error collecting agent metrics: {:#}", e);
+                    }
+                    agent_schedule.record_cycle(started.elapsed());
+                }
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// 各ホストの直近の収集結果
+    pub fn snapshot(&self) -> Snapshot {
+        let hosts = self.snapshots.read().map(|snapshots| snapshots.values().cloned().collect()).unwrap_or_default();
+        Snapshot { hosts }
+    }
+
+    /// 収集サイクルの完了とコンテナのライフサイクルイベントを購読
+    pub fn subscribe(&self) -> broadcast::Receiver<StreamEvent> {
+        self.events.subscribe()
+    }
+
+    /// 収集ループを停止し、状態の保存とバッファ済みのログ・メトリクスの送信を行う
+    pub async fn shutdown(mut self) -> Result<()> {
+        self.abort();
+
+        // 中断したループはロックを解放しているため、全ホストのバックグラウンド処理の停止と最終状態の保存ができる
+        let collectors = self.collectors.read().map(|collectors| collectors.clone()).unwrap_or_default();
+        for metrics_collector in &collectors {
+            metrics_collector.lock().await.abort();
+        }
+        if let Some(state_store) = self.state_store.take() {
+            for metrics_collector in &collectors {
+                let collector = metrics_collector.lock().await;
+                state_store.update(collector.host(), collector.state().await);
+            }
+            match tokio::task::spawn_blocking(move || state_store.save()).await? {
+                Ok(()) => info!("State saved"),
+                Err(e) => warn!("Error saving state: {:#}", e),
+            }
+        }
+
+        // SDKのflushはエクスポートが終わるまでブロックする
+        if let Some(log_forwarder) = self.log_forwarder.take() {
+            let _ = tokio::task::spawn_blocking(move || log_forwarder.flush()).await;
+        }
+        if let Some(meter_provider) = self.meter_provider.take() {
+            tokio::task::spawn_blocking(move || meter_provider.shutdown()).await??;
+        }
+
+        Ok(())
+    }
+
+    fn abort(&mut self) {
+        for handle in self.handles.drain(..) {
+            handle.abort();
+        }
+    }
+}
+
+// shutdownを呼び出さずに破棄した場合も収集ループを止める（状態の保存と送信は行わない）
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        self.abort();
+    }
+}
+
+// ホストごとのタスクが共有する状態
+#[derive(Clone)]
+struct HostContext {
+    config: Arc<Config>,
+    meter: Meter,
+    counter_start_times: CounterStartTimes,
+    host_up: UpDownCounter<i64>,
+    events: broadcast::Sender<StreamEvent>,
+    log_forwarder: Option<LogForwarder>,
+    state_store: Option<Arc<StateStore>>,
+    collectors: Collectors,
+}
+
+// ホストごとの収集スケジュール
+struct HostSchedules {
+    containers: CollectionSchedule,
+    logs: Option<CollectionSchedule>,
+}
+
+impl HostContext {
+    // 接続できるまで間隔を倍にしながら再接続
+    async fn reconnect(&self, host: &DockerHostConfig) -> DockerClient {
+        let mut backoff = INITIAL_RECONNECT_BACKOFF;
+        loop {
+            tokio::time::sleep(backoff).await;
+            match connect(host.connection.clone()).await {
+                Ok(docker_client) => {
+                    info!(host = %host.name, "Connected to Docker daemon");
+                    return docker_client;
+                }
+                Err(e) => {
+                    backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+                    warn!(host = %host.name, retry_in = ?backoff, "Failed to connect to Docker daemon: {:#}", e);
+                }
+            }
+        }
+    }
+
+    // ホストのコレクターを作成し、イベント転送・ログ追跡・メトリクス収集を実行するFutureを返す
+    fn attach(
+        &self,
+        host: &str,
+        docker_client: DockerClient,
+        host_state: Option<HostState>,
+        schedules: HostSchedules,
+    ) -> Result<impl Future<Output = ()> + Send + 'static> {
+        let config = &self.config;
+        let log_collector = match schedules.logs {
+            Some(log_schedule) => {
+                let log_collector = LogCollector::new_with_meter(
+                    docker_client.clone(),
+                    host,
+                    &config.container_logs,
+                    &config.metrics.container_filters,
+                    &config.metrics.cardinality,
+                    &self.meter,
+                )?
+                .with_forwarder(self.log_forwarder.clone());
+                Some((log_collector, log_schedule))
+            }
+            None => None,
+        };
+
+        let mut metrics_collector = MetricsCollector::new_with_meter(docker_client.clone(), &config.metrics, &self.meter)?
+            .with_host(host)
+            .with_events(self.events.clone())
+            .with_counter_start_times(&self.counter_start_times);
+        if let Some(host_state) = host_state {
+            metrics_collector = metrics_collector.with_state(host_state);
+        }
+        let metrics_collector = Arc::new(Mutex::new(metrics_collector));
+        if let Ok(mut collectors) = self.collectors.write() {
+            collectors.push(metrics_collector.clone());
+        }
+
+        let events = forward_container_events(docker_client, host.to_string(), self.events.clone());
+        let logs = async move {
+            if let Some((log_collector, log_schedule)) = log_collector {
+                log_collector.run(log_schedule).await;
+            }
+        };
+        let metrics = collect_containers(metrics_collector, host.to_string(), schedules.containers, self.state_store.clone());
+        Ok(async move {
+            tokio::join!(events, logs, metrics);
+        })
+    }
+}
+
+// sshトンネルの確立はブロックするため、ブロッキングスレッドで接続する
+async fn connect(connection: DockerConfig) -> Result<DockerClient> {
+    tokio::task::spawn_blocking(move || DockerClient::new(&connection)).await?
+}
+
+// ホストのコンテナメトリクスの収集ループ
+async fn collect_containers(
+    metrics_collector: Arc<Mutex<MetricsCollector>>,
+    host: String,
+    mut schedule: CollectionSchedule,
+    state_store: Option<Arc<StateStore>>,
+) {
+    loop {
+        schedule.tick().await;
+
+        info!(host = %host, "Collecting container metrics...");
+        let started = Instant::now();
+        let mut collector = metrics_collector.lock().await;
+        if let Err(e) = collector.collect_metrics().await {
+            warn!(host = %host, "Error collecting metrics: {}", e);
+        }
+        let host_state = match &state_store {
+            Some(_) => Some(collector.state().await),
+            None => None,
+        };
+        drop(collector);
+        // 状態ファイルの書き込みはブロックするため、コレクターのロックを解放してからブロッキングスレッドで行う
+        if let (Some(state_store), Some(host_state)) = (state_store.clone(), host_state) {
+            let host_name = host.clone();
+            let saved = tokio::task::spawn_blocking(move || state_store.record_cycle(&host_name, host_state)).await;
+            if let Err(e) = saved.unwrap_or_else(|e| Err(e.into())) {
+                warn!(host = %host, "Error saving state: {:#}", e);
+            }
+        }
+        schedule.record_cycle(started.elapsed());
+    }
+}
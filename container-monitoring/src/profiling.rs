@@ -1,10 +1,81 @@
+use anyhow::{Context, Result};
+use pprof::protos::Message;
 use pprof::ProfilerGuard;
+use serde::Serialize;
 use std::fs::File;
-use std::io::Write;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use tracing::{info, warn};
 
+/// CPUプロファイラーはプロセスに1つだけのため、同時に1つのプロファイルのみ取得します
+static CPU_PROFILE_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// CPUプロファイルの出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileFormat {
+    /// フレームグラフ（SVG）
+    Flamegraph,
+    /// pprof形式（gzipなしのprotobuf、`go tool pprof`で読み込み可能）
+    Pprof,
+}
+
+impl ProfileFormat {
+    /// クエリの値（"flamegraph"/"svg"、"pprof"/"proto"）から変換
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "flamegraph" | "svg" => Some(ProfileFormat::Flamegraph),
+            "pprof" | "proto" => Some(ProfileFormat::Pprof),
+            _ => None,
+        }
+    }
+    
+    /// レスポンスのContent-Type
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ProfileFormat::Flamegraph => "image/svg+xml",
+            ProfileFormat::Pprof => "application/octet-stream",
+        }
+    }
+}
+
+/// プロセスのメモリ使用量（/proc/self/statusの値）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MemorySummary {
+    /// 常駐メモリ（VmRSS）
+    pub rss_bytes: u64,
+    /// 常駐メモリの最大値（VmHWM）
+    pub peak_rss_bytes: u64,
+    /// 仮想メモリ（VmSize）
+    pub virtual_bytes: u64,
+    /// データ・ヒープ・スタック領域（VmData）
+    pub data_bytes: u64,
+    /// 匿名ページの常駐メモリ（RssAnon）
+    pub anon_rss_bytes: u64,
+    /// ファイルマップの常駐メモリ（RssFile）
+    pub file_rss_bytes: u64,
+    /// スレッド数
+    pub threads: u64,
+}
+
+// 実行中フラグを解放するガード（リクエストが中断された場合も解放されます）
+struct CpuProfileSlot;
+
+impl CpuProfileSlot {
+    fn acquire() -> Option<Self> {
+        CPU_PROFILE_RUNNING
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .ok()
+            .map(|_| CpuProfileSlot)
+    }
+}
+
+impl Drop for CpuProfileSlot {
+    fn drop(&mut self) {
+        CPU_PROFILE_RUNNING.store(false, Ordering::Release);
+    }
+}
+
 /// CPUプロファイリングを開始し、ProfilerGuardを返します。
 /// このGuardがドロップされたとき、プロファイルが生成されます。
 pub fn start_cpu_profiling() -> Option<ProfilerGuard<'static>> {
@@ -28,7 +99,7 @@ pub async fn profile_for_duration(duration: Duration, output_path: impl AsRef<Pa
     tokio::time::sleep(duration).await;
     
     // プロファイリング結果を取得
-    let report = guard.report().map_err(|e| format!("Failed to generate report: {}", e))?;
+    let report = guard.report().build().map_err(|e| format!("Failed to generate report: {}", e))?;
     
     // フレームグラフをSVGで保存
     let file = File::create(output_path.as_ref()).map_err(|e| format!("Failed to create file: {}", e))?;
@@ -38,26 +109,81 @@ pub async fn profile_for_duration(duration: Duration, output_path: impl AsRef<Pa
     Ok(())
 }
 
-/// 現在のメモリ使用量をログに記録します。
-pub fn log_memory_usage() {
-    #[cfg(target_os = "linux")]
-    {
-        use std::fs::read_to_string;
+/// 指定した期間だけCPUプロファイリングを実行し、指定した形式でエンコードして返します。
+/// 別のCPUプロファイルを取得中の場合は`None`を返します。
+pub async fn capture_cpu_profile(duration: Duration, frequency: i32, format: ProfileFormat) -> Result<Option<Vec<u8>>> {
+    let Some(slot) = CpuProfileSlot::acquire() else {
+        return Ok(None);
+    };
+    
+    // シンボル解決とエンコードは重いため、プロファイル全体をブロッキングスレッドで実行
+    let profile = tokio::task::spawn_blocking(move || -> Result<Vec<u8>> {
+        let _slot = slot;
+        let guard = ProfilerGuard::new(frequency).context("Failed to start CPU profiling")?;
+        std::thread::sleep(duration);
         
-        // LinuxのプロセスメモリStatをパース
-        if let Ok(stat) = read_to_string("/proc/self/status") {
-            if let Some(vm_line) = stat.lines().find(|line| line.starts_with("VmRSS:")) {
-                if let Some(kb_str) = vm_line.split_whitespace().nth(1) {
-                    if let Ok(kb) = kb_str.parse::<u64>() {
-                        let mb = kb / 1024;
-                        info!("Current memory usage: {} MB", mb);
-                        return;
-                    }
-                }
+        let report = guard.report().build().context("Failed to generate CPU profile report")?;
+        let mut output = Vec::new();
+        match format {
+            ProfileFormat::Flamegraph => report.flamegraph(&mut output).context("Failed to write flamegraph")?,
+            ProfileFormat::Pprof => {
+                let profile = report.pprof().context("Failed to build pprof profile")?;
+                profile.encode(&mut output).context("Failed to encode pprof profile")?;
             }
         }
+        
+        Ok(output)
+    })
+    .await
+    .context("CPU profiling task failed")??;
+    
+    info!("CPU profile captured ({} bytes, {:?})", profile.len(), format);
+    Ok(Some(profile))
+}
+
+/// /proc/<pid>/statusの内容からメモリ使用量を取得
+pub fn memory_summary_from_status(status: &str) -> MemorySummary {
+    let mut summary = MemorySummary::default();
+    
+    for line in status.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let mut fields = value.split_whitespace();
+        let Some(number) = fields.next().and_then(|number| number.parse::<u64>().ok()) else {
+            continue;
+        };
+        // メモリの値はkB単位
+        let bytes = if fields.next() == Some("kB") { number * 1024 } else { number };
+        
+        match key {
+            "VmRSS" => summary.rss_bytes = bytes,
+            "VmHWM" => summary.peak_rss_bytes = bytes,
+            "VmSize" => summary.virtual_bytes = bytes,
+            "VmData" => summary.data_bytes = bytes,
+            "RssAnon" => summary.anon_rss_bytes = bytes,
+            "RssFile" => summary.file_rss_bytes = bytes,
+            "Threads" => summary.threads = number,
+            _ => {}
+        }
     }
     
-    // 非Linuxプラットフォームまたはエラー時
-    info!("Memory usage tracking not available on this platform");
+    summary
+}
+
+/// 現在のプロセスのメモリ使用量を取得します。
+pub fn memory_summary() -> Result<MemorySummary> {
+    let status = std::fs::read_to_string("/proc/self/status")
+        .context("Failed to read /proc/self/status")?;
+    
+    Ok(memory_summary_from_status(&status))
+}
+
+/// 現在のメモリ使用量をログに記録します。
+pub fn log_memory_usage() {
+    match memory_summary() {
+        Ok(summary) => info!("Current memory usage: {} MB", summary.rss_bytes / 1024 / 1024),
+        // 非Linuxプラットフォームまたはエラー時
+        Err(_) => info!("Memory usage tracking not available on this platform"),
+    }
 }
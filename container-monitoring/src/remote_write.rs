@@ -0,0 +1,405 @@
+use anyhow::{anyhow, bail, Context, Result};
+use prometheus::proto::{Metric, MetricFamily, MetricType};
+use prost::Message;
+use reqwest::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use reqwest::StatusCode;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, warn};
+
+use crate::config::RemoteWriteConfig;
+
+/// remote_writeのプロトコルバージョン
+const REMOTE_WRITE_VERSION: &str = "0.1.0";
+
+/// ディスクのキューに保存するバッチの拡張子
+const BATCH_EXTENSION: &str = "batch";
+
+/// remote_writeのリクエスト（prompb/remote.protoのWriteRequest）
+#[derive(Clone, PartialEq, Message)]
+pub struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub timeseries: Vec<TimeSeries>,
+}
+
+/// 1つの系列（ラベルは名前順）
+#[derive(Clone, PartialEq, Message)]
+pub struct TimeSeries {
+    #[prost(message, repeated, tag = "1")]
+    pub labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    pub samples: Vec<Sample>,
+}
+
+/// 系列のラベル
+#[derive(Clone, PartialEq, Message)]
+pub struct Label {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+/// 系列の値（タイムスタンプはミリ秒）
+#[derive(Clone, PartialEq, Message)]
+pub struct Sample {
+    #[prost(double, tag = "1")]
+    pub value: f64,
+    #[prost(int64, tag = "2")]
+    pub timestamp: i64,
+}
+
+/// Prometheusレジストリから収集したメトリクスをremote_writeのリクエストに変換
+///
+/// `/metrics`と同じ系列を作成します。ヒストグラムは`_bucket`（`le`ラベル）、`_sum`、`_count`、
+/// サマリーは`quantile`ラベル付きの系列と`_sum`、`_count`に展開します。
+/// `external_labels`は同じ名前のラベルを持たない系列にのみ付与します。
+pub fn write_request(families: &[MetricFamily], external_labels: &HashMap<String, String>, timestamp_ms: i64) -> WriteRequest {
+    let mut timeseries = Vec::new();
+    for family in families {
+        let name = family.get_name();
+        for metric in family.get_metric() {
+            let timestamp = match metric.get_timestamp_ms() {
+                0 => timestamp_ms,
+                timestamp => timestamp,
+            };
+            let mut push = |name: String, extra: Option<(&str, String)>, value: f64| {
+                timeseries.push(time_series(name, metric, extra, external_labels, value, timestamp));
+            };
+
+            match family.get_field_type() {
+                MetricType::COUNTER => push(name.to_string(), None, metric.get_counter().get_value()),
+                MetricType::GAUGE => push(name.to_string(), None, metric.get_gauge().get_value()),
+                MetricType::UNTYPED => push(name.to_string(), None, metric.get_untyped().get_value()),
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        push(name.to_string(), Some(("quantile", quantile.get_quantile().to_string())), quantile.get_value());
+                    }
+                    push(format!("{}_sum", name), None, summary.get_sample_sum());
+                    push(format!("{}_count", name), None, summary.get_sample_count() as f64);
+                }
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    let mut has_inf = false;
+                    for bucket in histogram.get_bucket() {
+                        let upper_bound = bucket.get_upper_bound();
+                        has_inf |= upper_bound.is_infinite();
+                        push(format!("{}_bucket", name), Some(("le", le_value(upper_bound))), bucket.get_cumulative_count() as f64);
+                    }
+                    // テキスト形式と同じく+Infのバケットを補う
+                    if !has_inf {
+                        push(format!("{}_bucket", name), Some(("le", le_value(f64::INFINITY))), histogram.get_sample_count() as f64);
+                    }
+                    push(format!("{}_sum", name), None, histogram.get_sample_sum());
+                    push(format!("{}_count", name), None, histogram.get_sample_count() as f64);
+                }
+            }
+        }
+    }
+    WriteRequest { timeseries }
+}
+
+// ラベルを名前順に並べた系列を作成
+fn time_series(
+    name: String,
+    metric: &Metric,
+    extra: Option<(&str, String)>,
+    external_labels: &HashMap<String, String>,
+    value: f64,
+    timestamp: i64,
+) -> TimeSeries {
+    let mut labels: Vec<Label> = metric
+        .get_label()
+        .iter()
+        .map(|pair| Label {
+            name: pair.get_name().to_string(),
+            value: pair.get_value().to_string(),
+        })
+        .collect();
+    if let Some((label, value)) = extra {
+        labels.push(Label { name: label.to_string(), value });
+    }
+    for (label, value) in external_labels {
+        if !labels.iter().any(|existing| &existing.name == label) {
+            labels.push(Label { name: label.clone(), value: value.clone() });
+        }
+    }
+    labels.push(Label { name: "__name__".to_string(), value: name });
+    labels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    TimeSeries {
+        labels,
+        samples: vec![Sample { value, timestamp }],
+    }
+}
+
+// バケットの上限をleラベルの値に変換
+fn le_value(upper_bound: f64) -> String {
+    if upper_bound.is_infinite() {
+        "+Inf".to_string()
+    } else {
+        upper_bound.to_string()
+    }
+}
+
+/// リクエストをprotobufにエンコードしてsnappy（ブロック形式）で圧縮
+pub fn encode(request: &WriteRequest) -> Result<Vec<u8>> {
+    snap::raw::Encoder::new()
+        .compress_vec(&request.encode_to_vec())
+        .context("Failed to compress remote_write request")
+}
+
+/// 送信待ちのバッチ（圧縮済みのリクエスト）のキュー
+///
+/// 送信先の障害中もバッチを保持し、上限を超えた場合は古いバッチから破棄します。
+/// ディスクのキューは1バッチを1ファイルとして保存し、再起動後も送信を続けます。
+pub struct BatchQueue {
+    dir: Option<PathBuf>,
+    max_batches: usize,
+    next_id: u64,
+    batches: VecDeque<(u64, Vec<u8>)>,
+}
+
+impl BatchQueue {
+    /// メモリ上のキューを作成
+    pub fn memory(max_batches: usize) -> Self {
+        Self {
+            dir: None,
+            max_batches: max_batches.max(1),
+            next_id: 0,
+            batches: VecDeque::new(),
+        }
+    }
+
+    /// ディレクトリに保存するキューを作成し、保存されていたバッチを読み込む
+    pub fn disk(dir: impl Into<PathBuf>, max_batches: usize) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create remote_write queue directory {}", dir.display()))?;
+
+        let mut ids: Vec<u64> = fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read remote_write queue directory {}", dir.display()))?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension()? != BATCH_EXTENSION {
+                    return None;
+                }
+                path.file_stem()?.to_str()?.parse().ok()
+            })
+            .collect();
+        ids.sort_unstable();
+
+        let mut queue = Self {
+            dir: Some(dir.clone()),
+            max_batches: max_batches.max(1),
+            next_id: ids.last().map_or(0, |id| id + 1),
+            batches: VecDeque::new(),
+        };
+        for id in ids {
+            let path = batch_path(&dir, id);
+            match fs::read(&path) {
+                Ok(body) => queue.batches.push_back((id, body)),
+                Err(e) => warn!("Skipping unreadable remote_write batch {}: {}", path.display(), e),
+            }
+        }
+        queue.evict();
+        if !queue.is_empty() {
+            info!(batches = queue.len(), "Restored queued remote_write batches");
+        }
+
+        Ok(queue)
+    }
+
+    /// バッチを末尾に追加
+    pub fn push(&mut self, body: Vec<u8>) -> Result<()> {
+        let id = self.next_id;
+        self.next_id += 1;
+        if let Some(dir) = &self.dir {
+            let path = batch_path(dir, id);
+            fs::write(&path, &body)
+                .with_context(|| format!("Failed to write remote_write batch {}", path.display()))?;
+        }
+        self.batches.push_back((id, body));
+        self.evict();
+        Ok(())
+    }
+
+    /// 先頭のバッチ
+    pub fn front(&self) -> Option<&[u8]> {
+        self.batches.front().map(|(_, body)| body.as_slice())
+    }
+
+    /// 先頭のバッチを削除
+    pub fn pop(&mut self) {
+        if let Some((id, _)) = self.batches.pop_front() {
+            self.remove_file(id);
+        }
+    }
+
+    /// 保持しているバッチ数
+    pub fn len(&self) -> usize {
+        self.batches.len()
+    }
+
+    /// キューが空か
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    // 上限を超えたバッチを古いものから破棄
+    fn evict(&mut self) {
+        while self.batches.len() > self.max_batches {
+            if let Some((id, _)) = self.batches.pop_front() {
+                warn!(max_batches = self.max_batches, "remote_write queue is full, dropping the oldest batch");
+                self.remove_file(id);
+            }
+        }
+    }
+
+    fn remove_file(&self, id: u64) {
+        if let Some(dir) = &self.dir {
+            let path = batch_path(dir, id);
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("Failed to remove remote_write batch {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+// バッチのファイルパス（ファイル名の順序が送信順になるよう0埋め）
+fn batch_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{:020}.{}", id, BATCH_EXTENSION))
+}
+
+/// Prometheus remote_writeでメトリクスを送信
+///
+/// 送信間隔ごとにレジストリの内容を1バッチとしてキューに追加し、古い順に送信します。
+/// 5xx、429、接続エラーは再試行し、それでも失敗した場合は以降のバッチを次の送信まで保持します。
+/// それ以外の4xxは送信先が受け付けないバッチとして破棄します。
+pub struct RemoteWriter {
+    client: reqwest::Client,
+    config: RemoteWriteConfig,
+    queue: BatchQueue,
+}
+
+impl RemoteWriter {
+    /// 設定からライターを作成（ディスクのキューは保存されていたバッチを読み込む）
+    pub fn new(config: &RemoteWriteConfig) -> Result<Self> {
+        if config.url.is_empty() {
+            bail!("remote_write.url is required when remote_write is enabled");
+        }
+        let queue = match config.queue.as_str() {
+            "memory" => BatchQueue::memory(config.max_queue_batches),
+            "disk" => {
+                let Some(path) = &config.queue_path else {
+                    bail!("remote_write.queue_path is required when queue is \"disk\"");
+                };
+                BatchQueue::disk(path, config.max_queue_batches)?
+            }
+            other => bail!("Unsupported remote_write queue '{}' (expected memory or disk)", other),
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout.max(1)))
+            .build()
+            .context("Failed to build remote_write HTTP client")?;
+
+        Ok(Self {
+            client,
+            config: config.clone(),
+            queue,
+        })
+    }
+
+    /// 送信待ちのバッチ数
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// メトリクスをキューに追加し、キューのバッチを送信
+    pub async fn write(&mut self, families: &[MetricFamily]) -> Result<()> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as i64)
+            .unwrap_or_default();
+        let request = write_request(families, &self.config.external_labels, timestamp_ms);
+        if !request.timeseries.is_empty() {
+            self.queue.push(encode(&request)?)?;
+        }
+        self.flush().await
+    }
+
+    /// キューのバッチを古い順に送信（送信できないバッチがあればそこで止める）
+    pub async fn flush(&mut self) -> Result<()> {
+        while let Some(body) = self.queue.front() {
+            let accepted = self.deliver(body).await?;
+            if !accepted {
+                warn!("remote_write endpoint rejected a batch, dropping it");
+            }
+            self.queue.pop();
+        }
+        Ok(())
+    }
+
+    /// 送信間隔ごとにデフォルトのPrometheusレジストリ（`/metrics`と同じ内容）を送信
+    pub async fn run(mut self) {
+        info!(url = %self.config.url, "Pushing metrics via Prometheus remote_write");
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval.max(1)));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.write(&prometheus::gather()).await {
+                warn!(queued = self.queued(), "Error pushing metrics via remote_write: {:#}", e);
+            }
+        }
+    }
+
+    // 1バッチを送信（送信先が拒否した場合はOk(false)、再試行しても送信できない場合はErr）
+    async fn deliver(&self, body: &[u8]) -> Result<bool> {
+        let mut backoff = Duration::from_millis(self.config.retry_backoff_ms);
+        let mut attempt = 0;
+        loop {
+            let error = match self.post(body).await {
+                Ok(status) if status.is_success() => return Ok(true),
+                Ok(status) if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS => {
+                    anyhow!("{} responded with {}", self.config.url, status)
+                }
+                Ok(status) => {
+                    debug!(%status, "remote_write batch rejected");
+                    return Ok(false);
+                }
+                Err(e) => e,
+            };
+
+            if attempt >= self.config.max_retries {
+                return Err(error);
+            }
+            attempt += 1;
+            debug!(attempt, "Retrying remote_write in {:?}: {:#}", backoff, error);
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+
+    async fn post(&self, body: &[u8]) -> Result<StatusCode> {
+        let mut request = self.client
+            .post(&self.config.url)
+            .header(CONTENT_ENCODING, "snappy")
+            .header(CONTENT_TYPE, "application/x-protobuf")
+            .header("X-Prometheus-Remote-Write-Version", REMOTE_WRITE_VERSION)
+            .body(body.to_vec());
+        if let Some(basic) = &self.config.basic {
+            request = request.basic_auth(&basic.username, Some(&basic.password));
+        }
+
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to send remote_write request to {}", self.config.url))?;
+        Ok(response.status())
+    }
+}
@@ -0,0 +1,149 @@
+use anyhow::{bail, Result};
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::resource::{EnvResourceDetector, OsResourceDetector, ResourceDetector, TelemetryResourceDetector};
+use opentelemetry_sdk::Resource;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::TelemetryConfig;
+
+/// サービス名を上書きする環境変数
+const OTEL_SERVICE_NAME: &str = "OTEL_SERVICE_NAME";
+
+/// コンテナIDの前後に付くcgroup/systemdの装飾
+const CONTAINER_ID_PREFIXES: [&str; 4] = ["docker-", "cri-containerd-", "crio-", "libpod-"];
+const CONTAINER_ID_SUFFIX: &str = ".scope";
+
+/// ホスト名とアーキテクチャ（host.name, host.arch）
+#[derive(Debug)]
+pub struct HostResourceDetector;
+
+impl ResourceDetector for HostResourceDetector {
+    fn detect(&self, _timeout: Duration) -> Resource {
+        let mut attributes = vec![KeyValue::new("host.arch", std::env::consts::ARCH)];
+
+        let host_name = std::fs::read_to_string("/proc/sys/kernel/hostname")
+            .ok()
+            .map(|name| name.trim().to_string())
+            .or_else(|| std::env::var("HOSTNAME").ok())
+            .filter(|name| !name.is_empty());
+        if let Some(host_name) = host_name {
+            attributes.push(KeyValue::new("host.name", host_name));
+        }
+
+        Resource::new(attributes)
+    }
+}
+
+/// エージェント自身のプロセス（process.pid, process.executable.name）
+///
+/// SDKの検出器と違いコマンドライン引数は含めません（設定ファイルのパス以外に秘密情報が渡される可能性があるため）。
+#[derive(Debug)]
+pub struct ProcessResourceDetector;
+
+impl ResourceDetector for ProcessResourceDetector {
+    fn detect(&self, _timeout: Duration) -> Resource {
+        let mut attributes = vec![KeyValue::new("process.pid", std::process::id() as i64)];
+
+        let executable = std::env::current_exe()
+            .ok()
+            .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()));
+        if let Some(executable) = executable {
+            attributes.push(KeyValue::new("process.executable.name", executable));
+        }
+
+        Resource::new(attributes)
+    }
+}
+
+/// エージェントがコンテナ内で動作している場合のコンテナID（container.id）
+#[derive(Debug)]
+pub struct ContainerResourceDetector;
+
+impl ResourceDetector for ContainerResourceDetector {
+    fn detect(&self, _timeout: Duration) -> Resource {
+        match detect_container_id(Path::new("/proc/self")) {
+            Some(id) => Resource::new(vec![KeyValue::new("container.id", id)]),
+            None => Resource::empty(),
+        }
+    }
+}
+
+/// procfsのプロセスディレクトリからコンテナIDを検出
+///
+/// cgroup v1では`cgroup`のパスに、cgroup v2ではcgroup名前空間で隠れるため`mountinfo`の
+/// `/containers/<id>/`（/etc/hostnameなどのバインドマウント元）から取得します。
+pub fn detect_container_id(process_dir: &Path) -> Option<String> {
+    let from_cgroup = std::fs::read_to_string(process_dir.join("cgroup"))
+        .ok()
+        .and_then(|cgroup| container_id_from_cgroup(&cgroup));
+
+    from_cgroup.or_else(|| {
+        std::fs::read_to_string(process_dir.join("mountinfo"))
+            .ok()
+            .and_then(|mountinfo| container_id_from_mountinfo(&mountinfo))
+    })
+}
+
+/// /proc/<pid>/cgroupの内容からコンテナIDを取得
+pub fn container_id_from_cgroup(cgroup: &str) -> Option<String> {
+    cgroup
+        .lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .find_map(|path| path.split('/').rev().find_map(container_id_segment))
+}
+
+/// /proc/<pid>/mountinfoの内容からコンテナIDを取得
+pub fn container_id_from_mountinfo(mountinfo: &str) -> Option<String> {
+    mountinfo
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(3))
+        .find_map(|root| {
+            let segments: Vec<&str> = root.split('/').collect();
+            segments
+                .windows(2)
+                .find(|pair| pair[0] == "containers" || pair[0] == "overlay-containers")
+                .and_then(|pair| container_id_segment(pair[1]))
+        })
+}
+
+// パスの要素がコンテナID（64桁の16進数）であれば取り出す
+fn container_id_segment(segment: &str) -> Option<String> {
+    let id = CONTAINER_ID_PREFIXES
+        .iter()
+        .find_map(|prefix| segment.strip_prefix(prefix))
+        .unwrap_or(segment);
+    let id = id.strip_suffix(CONTAINER_ID_SUFFIX).unwrap_or(id);
+
+    (id.len() == 64 && id.bytes().all(|byte| byte.is_ascii_hexdigit())).then(|| id.to_string())
+}
+
+/// テレメトリーのリソースを作成
+///
+/// 優先順位は低い順に、検出器、設定の`service_name`、`OTEL_RESOURCE_ATTRIBUTES`、`OTEL_SERVICE_NAME`です。
+pub fn telemetry_resource(telemetry: &TelemetryConfig) -> Result<Resource> {
+    let mut detectors: Vec<Box<dyn ResourceDetector>> = vec![Box::new(TelemetryResourceDetector)];
+    for name in &telemetry.resource_detectors {
+        detectors.push(match name.as_str() {
+            "host" => Box::new(HostResourceDetector),
+            "os" => Box::new(OsResourceDetector),
+            "process" => Box::new(ProcessResourceDetector),
+            "container" => Box::new(ContainerResourceDetector),
+            other => bail!("Unknown resource detector '{}' (expected host, os, process or container)", other),
+        });
+    }
+
+    let configured = Resource::new(vec![
+        KeyValue::new("service.name", telemetry.service_name.clone()),
+        KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+    ]);
+    let mut resource = Resource::from_detectors(Duration::from_secs(0), detectors)
+        .merge(&configured)
+        .merge(&EnvResourceDetector::new().detect(Duration::from_secs(0)));
+
+    if let Some(service_name) = std::env::var(OTEL_SERVICE_NAME).ok().filter(|name| !name.is_empty()) {
+        resource = resource.merge(&Resource::new(vec![KeyValue::new("service.name", service_name)]));
+    }
+
+    Ok(resource)
+}
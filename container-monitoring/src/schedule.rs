@@ -0,0 +1,119 @@
+use anyhow::{bail, Result};
+use std::time::Duration;
+use tokio::time::{self, Instant, Interval, MissedTickBehavior};
+use tracing::{info, warn};
+
+use crate::config::GeneralConfig;
+
+/// 適応モードで間隔を縮める条件（サイクルが間隔のこの割合以下で完了した場合）
+const SHRINK_RATIO: u32 = 2;
+
+/// 適応モードで間隔を縮めるまでに必要な、余裕のあるサイクルの連続回数
+const SHRINK_AFTER_CYCLES: u32 = 3;
+
+/// 収集グループのスケジュール
+///
+/// 収集が間隔に間に合わなかった場合の動作（`missed_tick_behavior`）を設定でき、
+/// 適応モードではサイクルの所要時間に応じて間隔を`interval`から`interval * max_interval_factor`の範囲で調整します。
+pub struct CollectionSchedule {
+    name: String,
+    base: Duration,
+    max: Duration,
+    adaptive: bool,
+    behavior: MissedTickBehavior,
+    interval: Interval,
+    fast_cycles: u32,
+}
+
+impl CollectionSchedule {
+    /// グループの間隔と`general`の適応モード・missed tickの設定からスケジュールを作成
+    pub fn new(name: impl Into<String>, interval: Duration, general: &GeneralConfig) -> Result<Self> {
+        let name = name.into();
+        if interval.is_zero() {
+            bail!("Collection interval for {} must be greater than zero", name);
+        }
+
+        let behavior = missed_tick_behavior(&general.missed_tick_behavior)?;
+        let max = interval * general.max_interval_factor.max(1);
+
+        Ok(Self {
+            name,
+            base: interval,
+            max,
+            adaptive: general.adaptive,
+            behavior,
+            interval: new_interval(Instant::now(), interval, behavior),
+            fast_cycles: 0,
+        })
+    }
+
+    /// 現在の間隔
+    pub fn period(&self) -> Duration {
+        self.interval.period()
+    }
+
+    /// 次の収集時刻まで待機（初回は即座に完了）
+    pub async fn tick(&mut self) {
+        self.interval.tick().await;
+    }
+
+    /// サイクルの所要時間を記録し、適応モードでは次回以降の間隔を調整
+    pub fn record_cycle(&mut self, elapsed: Duration) {
+        if !self.adaptive {
+            return;
+        }
+
+        let current = self.period();
+        self.fast_cycles = if elapsed * SHRINK_RATIO <= current { self.fast_cycles + 1 } else { 0 };
+        let next = adapt_interval(current, self.base, self.max, elapsed, self.fast_cycles);
+        if next == current {
+            return;
+        }
+        self.fast_cycles = 0;
+
+        if next > current {
+            warn!(
+                group = %self.name,
+                elapsed_ms = elapsed.as_millis() as u64,
+                "Collection cycle exceeded its {:?} budget, stretching interval to {:?}",
+                current,
+                next
+            );
+        } else {
+            info!(group = %self.name, "Collection cycle is within budget again, interval set to {:?}", next);
+        }
+        self.interval = new_interval(Instant::now() + next, next, self.behavior);
+    }
+}
+
+/// 適応モードの次の間隔を計算
+///
+/// サイクルが現在の間隔を超えた場合は2倍（所要時間の方が長ければ所要時間）に延長します。
+/// `fast_cycles`は間隔の半分以下で完了したサイクルの連続回数（今回を含む）で、
+/// 3回続いた場合に間隔を4分の3（基本の間隔まで）に縮めます。延長と縮小を繰り返さないよう、少しずつ戻します。
+pub fn adapt_interval(current: Duration, base: Duration, max: Duration, elapsed: Duration, fast_cycles: u32) -> Duration {
+    if elapsed > current {
+        (current * 2).max(elapsed).min(max)
+    } else if fast_cycles >= SHRINK_AFTER_CYCLES && current > base {
+        (current * 3 / 4).max(base)
+    } else {
+        current
+    }
+}
+
+/// 設定値をtokioのMissedTickBehaviorに変換
+pub fn missed_tick_behavior(value: &str) -> Result<MissedTickBehavior> {
+    match value {
+        "burst" => Ok(MissedTickBehavior::Burst),
+        "delay" => Ok(MissedTickBehavior::Delay),
+        "skip" => Ok(MissedTickBehavior::Skip),
+        other => bail!("Unsupported missed_tick_behavior '{}' (expected burst, delay or skip)", other),
+    }
+}
+
+// 指定した時刻から始まるインターバルを作成
+fn new_interval(start: Instant, period: Duration, behavior: MissedTickBehavior) -> Interval {
+    let mut interval = time::interval_at(start, period);
+    interval.set_missed_tick_behavior(behavior);
+    interval
+}
@@ -1,31 +1,61 @@
-use anyhow::Result;
-use prometheus::{Encoder, Registry, TextEncoder};
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use futures::{SinkExt, StreamExt};
+use prometheus::{Encoder, TextEncoder};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
 use tracing::{debug, info, instrument, warn};
-use warp::Filter;
+use warp::http::StatusCode;
+use warp::ws::{Message, WebSocket};
+use warp::{Filter, Rejection, Reply};
 
-use crate::metrics::MetricsCollector;
+use crate::config::{AuthConfig, ServerConfig, ServerTlsConfig};
+use crate::stream::{StreamEvent, StreamFilter};
+#[cfg(feature = "profiling")]
+use crate::config::PprofConfig;
+#[cfg(feature = "profiling")]
+use crate::profiling::{capture_cpu_profile, memory_summary, ProfileFormat};
 
-#[instrument(skip(metrics_collector), level = "info")]
+// CPU profile length when the request does not pass ?seconds= (same as Go's net/http/pprof)
+#[cfg(feature = "profiling")]
+const DEFAULT_PROFILE_SECONDS: u64 = 30;
+
+// Rejection raised when a protected route is called without valid credentials
+#[derive(Debug)]
+struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+#[instrument(skip(events, server_config), level = "info")]
 pub async fn start_metrics_server(
-    metrics_collector: Arc<Mutex<MetricsCollector>>,
+    events: broadcast::Sender<StreamEvent>,
     port: u16,
+    server_config: &ServerConfig,
 ) -> Result<()> {
-    info!("Starting metrics server on port {}", port);
+    let ip: IpAddr = server_config.bind_address.parse()
+        .with_context(|| format!("Invalid bind address: {}", server_config.bind_address))?;
+    let addr = SocketAddr::new(ip, port);
+    info!("Starting metrics server on {}", addr);
+    
+    let auth = Arc::new(server_config.auth.clone());
     
     // Define routes
     let metrics_route = warp::path!("metrics")
         .and(warp::get())
+        .and(authorize(auth.clone(), "metrics"))
         .map(move || {
-            let registry = Registry::new();
-            
-            // The OpenTelemetry metrics are automatically exported via Prometheus
-            // Just return the metrics endpoint content
+            // OpenTelemetry metrics are exported into the default Prometheus registry
+            // regardless of otel_exporter, so /metrics works without an OTLP endpoint
             let mut buffer = Vec::new();
             let encoder = TextEncoder::new();
             
-            // This collects from default Prometheus registry which OpenTelemetry integrates with
+            // This collects from the default Prometheus registry
             let metric_families = prometheus::gather();
             if let Err(e) = encoder.encode(&metric_families, &mut buffer) {
                 warn!("Could not encode metrics: {}", e);
@@ -36,16 +66,364 @@ pub async fn start_metrics_server(
     
     let health_route = warp::path!("health")
         .and(warp::get())
-        .map(|| "ok");
+        .and(authorize(auth.clone(), "health"))
+        // An owned body keeps the route future `Send` when spawned from main
+        .map(|| "ok".to_string());
+    
+    // Live stats stream: WebSocket when an upgrade is requested, SSE otherwise
+    let ws_events = events.clone();
+    let ws_route = warp::path!("api" / "stream")
+        .and(warp::get())
+        .and(authorize(auth.clone(), "api/stream"))
+        .and(warp::ws())
+        .and(warp::query::<StreamFilter>())
+        .map(move |ws: warp::ws::Ws, filter: StreamFilter| {
+            let receiver = ws_events.subscribe();
+            ws.on_upgrade(move |socket| stream_websocket(socket, receiver, filter))
+        });
+    
+    let sse_events = events;
+    let sse_route = warp::path!("api" / "stream")
+        .and(warp::get())
+        .and(authorize(auth.clone(), "api/stream"))
+        .and(warp::query::<StreamFilter>())
+        .map(move |filter: StreamFilter| {
+            let receiver = sse_events.subscribe();
+            warp::sse::reply(warp::sse::keep_alive().stream(sse_stream(receiver, filter)))
+        });
+    
+    // Advertise the configured scheme on 401 responses
+    let challenge = if auth.basic.is_some() {
+        "Basic realm=\"container-monitoring\""
+    } else {
+        "Bearer"
+    };
     
     let routes = metrics_route
         .or(health_route)
+        .or(ws_route)
+        .or(sse_route);
+    
+    // CPU and memory profiling is only compiled in with the "profiling" feature
+    #[cfg(feature = "profiling")]
+    let routes = routes.or(pprof_routes(auth.clone(), server_config.pprof.clone()));
+    #[cfg(not(feature = "profiling"))]
+    if server_config.pprof.enabled {
+        warn!("server.pprof.enabled is set but this build does not include the \"profiling\" feature");
+    }
+    
+    let routes = routes
+        .recover(move |rejection| handle_rejection(rejection, challenge))
         .with(warp::log("metrics_server"));
     
     // Start the server
-    warp::serve(routes)
-        .run(([0, 0, 0, 0], port))
-        .await;
+    match &server_config.tls {
+        Some(tls) => {
+            // warp panics on unreadable key material, so check it up front
+            validate_tls_files(tls)?;
+            
+            let server = warp::serve(routes)
+                .tls()
+                .cert_path(&tls.cert_path)
+                .key_path(&tls.key_path);
+            
+            match &tls.client_ca_path {
+                Some(client_ca_path) => {
+                    info!("Metrics server requires client certificates (mTLS)");
+                    server.client_auth_required_path(client_ca_path).run(addr).await
+                }
+                None => server.run(addr).await,
+            }
+        }
+        None => {
+            warp::serve(routes)
+                .run(addr)
+                .await
+        }
+    }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+// Reject requests to protected routes without valid basic-auth or bearer credentials
+fn authorize(auth: Arc<AuthConfig>, route: &'static str) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and_then(move |header: Option<String>| {
+            let auth = auth.clone();
+            async move {
+                if is_authorized(&auth, route, header.as_deref()) {
+                    Ok(())
+                } else {
+                    debug!("Unauthorized request to {}", route);
+                    Err(warp::reject::custom(Unauthorized))
+                }
+            }
+        })
+        .untuple_one()
+}
+
+fn is_authorized(auth: &AuthConfig, route: &str, header: Option<&str>) -> bool {
+    // No credentials configured, or the route is explicitly left open
+    if auth.basic.is_none() && auth.bearer_token.is_none() {
+        return true;
+    }
+    if auth.public_routes.iter().any(|public| public.trim_matches('/') == route) {
+        return true;
+    }
+    
+    let Some(header) = header else {
+        return false;
+    };
+    
+    if let (Some(basic), Some(encoded)) = (&auth.basic, header.strip_prefix("Basic ")) {
+        let expected = format!("{}:{}", basic.username, basic.password);
+        if let Ok(decoded) = BASE64.decode(encoded.trim()) {
+            if constant_time_eq(&decoded, expected.as_bytes()) {
+                return true;
+            }
+        }
+    }
+    
+    if let (Some(token), Some(provided)) = (&auth.bearer_token, header.strip_prefix("Bearer ")) {
+        if constant_time_eq(provided.trim().as_bytes(), token.as_bytes()) {
+            return true;
+        }
+    }
+    
+    false
+}
+
+// Compare credentials without leaking the position of the first mismatch
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(feature = "profiling")]
+#[derive(Debug, serde::Deserialize)]
+struct ProfileQuery {
+    seconds: Option<u64>,
+    format: Option<String>,
+}
+
+// /debug/pprof/profile and /debug/pprof/heap; both answer 404 unless server.pprof.enabled is set
+#[cfg(feature = "profiling")]
+fn pprof_routes(auth: Arc<AuthConfig>, config: PprofConfig) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    let enabled = config.enabled;
+    let pprof_enabled = warp::any()
+        .and_then(move || async move {
+            if enabled {
+                Ok(())
+            } else {
+                Err(warp::reject::not_found())
+            }
+        })
+        .untuple_one();
+    
+    let config = Arc::new(config);
+    let profile_route = warp::path!("debug" / "pprof" / "profile")
+        .and(warp::get())
+        .and(pprof_enabled)
+        .and(authorize(auth.clone(), "debug/pprof/profile"))
+        .and(warp::query::<ProfileQuery>())
+        .and_then(move |query: ProfileQuery| handle_cpu_profile(query, config.clone()));
+    
+    let heap_route = warp::path!("debug" / "pprof" / "heap")
+        .and(warp::get())
+        .and(pprof_enabled)
+        .and(authorize(auth, "debug/pprof/heap"))
+        .map(|| -> Box<dyn Reply> {
+            match memory_summary() {
+                Ok(summary) => Box::new(warp::reply::json(&summary)),
+                Err(e) => {
+                    warn!("Could not read memory usage: {:#}", e);
+                    Box::new(warp::reply::with_status("memory usage unavailable", StatusCode::INTERNAL_SERVER_ERROR))
+                }
+            }
+        });
+    
+    profile_route.or(heap_route).unify()
+}
+
+#[cfg(feature = "profiling")]
+async fn handle_cpu_profile(query: ProfileQuery, config: Arc<PprofConfig>) -> Result<Box<dyn Reply>, Rejection> {
+    let Some(format) = ProfileFormat::parse(query.format.as_deref().unwrap_or("flamegraph")) else {
+        let reply = warp::reply::with_status("unsupported format (expected flamegraph or pprof)", StatusCode::BAD_REQUEST);
+        return Ok(Box::new(reply));
+    };
+    let seconds = query.seconds.unwrap_or(DEFAULT_PROFILE_SECONDS).min(config.max_seconds);
+    if seconds == 0 {
+        return Ok(Box::new(warp::reply::with_status("seconds must be at least 1", StatusCode::BAD_REQUEST)));
+    }
+    
+    match capture_cpu_profile(std::time::Duration::from_secs(seconds), config.frequency, format).await {
+        Ok(Some(profile)) => Ok(Box::new(warp::reply::with_header(profile, "Content-Type", format.content_type()))),
+        Ok(None) => Ok(Box::new(warp::reply::with_status("a CPU profile is already running", StatusCode::CONFLICT))),
+        Err(e) => {
+            warn!("CPU profiling failed: {:#}", e);
+            Ok(Box::new(warp::reply::with_status("CPU profiling failed", StatusCode::INTERNAL_SERVER_ERROR)))
+        }
+    }
+}
+
+async fn handle_rejection(rejection: Rejection, challenge: &'static str) -> Result<Box<dyn Reply>, Rejection> {
+    if rejection.find::<Unauthorized>().is_some() {
+        let reply = warp::reply::with_status("unauthorized", StatusCode::UNAUTHORIZED);
+        return Ok(Box::new(warp::reply::with_header(reply, "WWW-Authenticate", challenge)));
+    }
+    
+    Err(rejection)
+}
+
+// Convert broadcast events into SSE events, applying the subscriber's filter
+fn sse_stream(
+    receiver: broadcast::Receiver<StreamEvent>,
+    filter: StreamFilter,
+) -> impl futures::Stream<Item = Result<warp::sse::Event, Infallible>> {
+    BroadcastStream::new(receiver).filter_map(move |message| {
+        let event = match message {
+            Ok(event) => filter.apply(&event),
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                warn!("SSE subscriber lagged, skipped {} events", skipped);
+                None
+            }
+        };
+        
+        let sse_event = event.and_then(|event| {
+            warp::sse::Event::default()
+                .event(event.kind())
+                .json_data(&event)
+                .map_err(|e| warn!("Could not encode stream event: {}", e))
+                .ok()
+        });
+        
+        futures::future::ready(sse_event.map(Ok))
+    })
+}
+
+// Push filtered events to a WebSocket client until either side closes
+async fn stream_websocket(
+    socket: WebSocket,
+    mut receiver: broadcast::Receiver<StreamEvent>,
+    filter: StreamFilter,
+) {
+    debug!("WebSocket stream subscriber connected");
+    let (mut sink, mut incoming) = socket.split();
+    
+    loop {
+        tokio::select! {
+            message = receiver.recv() => match message {
+                Ok(event) => {
+                    let Some(event) = filter.apply(&event) else { continue };
+                    let text = match serde_json::to_string(&event) {
+                        Ok(text) => text,
+                        Err(e) => {
+                            warn!("Could not encode stream event: {}", e);
+                            continue;
+                        }
+                    };
+                    if sink.send(Message::text(text)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("WebSocket subscriber lagged, skipped {} events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            message = incoming.next() => match message {
+                Some(Ok(message)) if !message.is_close() => {}
+                _ => break,
+            },
+        }
+    }
+    
+    debug!("WebSocket stream subscriber disconnected");
+}
+
+// Load the certificate, key and client CA the way warp does, so bad files are
+// reported as errors instead of panicking inside warp's TLS setup
+fn validate_tls_files(tls: &ServerTlsConfig) -> Result<()> {
+    let certs = read_certificates(&tls.cert_path)?;
+    let key = read_private_key(&tls.key_path)?;
+
+    let builder = rustls::ServerConfig::builder();
+    let builder = match &tls.client_ca_path {
+        Some(client_ca_path) => {
+            let mut roots = rustls::RootCertStore::empty();
+            let (added, _) = roots.add_parsable_certificates(read_certificates(client_ca_path)?);
+            if added == 0 {
+                anyhow::bail!("No usable CA certificate in {}", client_ca_path);
+            }
+            let verifier = rustls::server::WebPkiClientVerifier::builder(Arc::new(roots))
+                .build()
+                .with_context(|| format!("Invalid client CA: {}", client_ca_path))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    builder
+        .with_single_cert(certs.clone(), key.clone_key())
+        .with_context(|| format!("Invalid TLS key: {}", tls.key_path))?;
+
+    // rustls accepts a key that belongs to another certificate, so sign with the
+    // key and verify the signature against the certificate's public key
+    if !key_matches_certificate(&certs[0], &key)? {
+        anyhow::bail!("TLS key {} does not match certificate {}", tls.key_path, tls.cert_path);
+    }
+
+    Ok(())
+}
+
+fn read_certificates(path: &str) -> Result<Vec<rustls::pki_types::CertificateDer<'static>>> {
+    let pem = std::fs::read(Path::new(path))
+        .with_context(|| format!("Failed to read TLS file: {}", path))?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to parse certificates: {}", path))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificate found in {}", path);
+    }
+    Ok(certs)
+}
+
+fn read_private_key(path: &str) -> Result<rustls::pki_types::PrivateKeyDer<'static>> {
+    let pem = std::fs::read(Path::new(path))
+        .with_context(|| format!("Failed to read TLS file: {}", path))?;
+    let mut key = None;
+    for item in rustls_pemfile::read_all(&mut pem.as_slice()) {
+        // warp rejects anything other than a private key in the key file
+        match item.with_context(|| format!("Failed to parse private key: {}", path))? {
+            rustls_pemfile::Item::Pkcs1Key(k) => key = Some(k.into()),
+            rustls_pemfile::Item::Pkcs8Key(k) => key = Some(k.into()),
+            rustls_pemfile::Item::Sec1Key(k) => key = Some(k.into()),
+            _ => anyhow::bail!("Unsupported item in private key file: {}", path),
+        }
+    }
+    key.with_context(|| format!("No private key found in {}", path))
+}
+
+fn key_matches_certificate(
+    cert: &rustls::pki_types::CertificateDer<'_>,
+    key: &rustls::pki_types::PrivateKeyDer<'_>,
+) -> Result<bool> {
+    const MESSAGE: &[u8] = b"container-monitoring key check";
+
+    let algorithms = rustls::crypto::ring::default_provider().signature_verification_algorithms;
+    let signer = rustls::crypto::ring::sign::any_supported_type(key)
+        .context("Unsupported TLS key type")?
+        .choose_scheme(&algorithms.supported_schemes())
+        .context("No signature scheme supported for the TLS key")?;
+    let signature = signer.sign(MESSAGE).context("Failed to sign with the TLS key")?;
+
+    let cert = webpki::EndEntityCert::try_from(cert)
+        .map_err(|e| anyhow::anyhow!("Failed to parse certificate: {:?}", e))?;
+    Ok(algorithms
+        .mapping
+        .iter()
+        .filter(|(scheme, _)| *scheme == signer.scheme())
+        .flat_map(|(_, verifiers)| verifiers.iter())
+        .any(|verifier| cert.verify_signature(*verifier, MESSAGE, &signature).is_ok()))
+}
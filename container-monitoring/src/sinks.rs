@@ -0,0 +1,378 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use opentelemetry::metrics::MetricsError;
+use opentelemetry_sdk::metrics::data::{self, ResourceMetrics, Temporality};
+use opentelemetry_sdk::metrics::exporter::PushMetricsExporter;
+use opentelemetry_sdk::metrics::reader::{AggregationSelector, DefaultAggregationSelector, TemporalitySelector};
+use opentelemetry_sdk::metrics::{Aggregation, InstrumentKind};
+use opentelemetry_sdk::AttributeSet;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::UdpSocket;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::SinkConfig;
+
+/// DogStatsDの1パケットの最大サイズ（一般的なMTUに収まる大きさ）
+const MAX_DATAGRAM_BYTES: usize = 1432;
+
+/// シンクの形式に依存しないメトリクスの1データポイント
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub labels: Vec<(String, String)>,
+    pub value: SampleValue,
+    pub timestamp: SystemTime,
+}
+
+/// データポイントの値
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleValue {
+    /// 現在値（ゲージとUpDownCounter）
+    Gauge(f64),
+    /// 単調増加の合計（DogStatsDでは前回の送信からの増加分、InfluxDBでは累計）
+    Counter(f64),
+    /// ヒストグラムの件数と合計
+    Histogram { count: u64, sum: f64 },
+}
+
+// 数値型のデータポイントを浮動小数点数に変換
+trait SampleNumber: Copy + 'static {
+    fn to_f64(self) -> f64;
+}
+
+impl SampleNumber for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+impl SampleNumber for i64 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl SampleNumber for u64 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+/// SDKが集計したメトリクスをデータポイントの一覧に変換
+pub fn samples(metrics: &ResourceMetrics) -> Vec<Sample> {
+    let mut samples = Vec::new();
+    for metric in metrics.scope_metrics.iter().flat_map(|scope| scope.metrics.iter()) {
+        let data = metric.data.as_any();
+        let name = metric.name.as_ref();
+        push_gauge::<f64>(&mut samples, name, data);
+        push_gauge::<i64>(&mut samples, name, data);
+        push_gauge::<u64>(&mut samples, name, data);
+        push_sum::<f64>(&mut samples, name, data);
+        push_sum::<i64>(&mut samples, name, data);
+        push_sum::<u64>(&mut samples, name, data);
+        push_histogram::<f64>(&mut samples, name, data);
+        push_histogram::<i64>(&mut samples, name, data);
+        push_histogram::<u64>(&mut samples, name, data);
+    }
+    samples
+}
+
+// 属性を（キー, 値）の一覧に変換
+fn labels(attributes: &AttributeSet) -> Vec<(String, String)> {
+    attributes
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn push_gauge<T: SampleNumber>(samples: &mut Vec<Sample>, name: &str, data: &dyn std::any::Any) {
+    let Some(gauge) = data.downcast_ref::<data::Gauge<T>>() else { return };
+    for point in &gauge.data_points {
+        samples.push(Sample {
+            name: name.to_string(),
+            labels: labels(&point.attributes),
+            value: SampleValue::Gauge(point.value.to_f64()),
+            timestamp: point.time.unwrap_or_else(SystemTime::now),
+        });
+    }
+}
+
+fn push_sum<T: SampleNumber>(samples: &mut Vec<Sample>, name: &str, data: &dyn std::any::Any) {
+    let Some(sum) = data.downcast_ref::<data::Sum<T>>() else { return };
+    for point in &sum.data_points {
+        let value = point.value.to_f64();
+        samples.push(Sample {
+            name: name.to_string(),
+            labels: labels(&point.attributes),
+            // UpDownCounterは増減するため現在値として扱う
+            value: if sum.is_monotonic { SampleValue::Counter(value) } else { SampleValue::Gauge(value) },
+            timestamp: point.time.unwrap_or_else(SystemTime::now),
+        });
+    }
+}
+
+fn push_histogram<T: SampleNumber>(samples: &mut Vec<Sample>, name: &str, data: &dyn std::any::Any) {
+    let Some(histogram) = data.downcast_ref::<data::Histogram<T>>() else { return };
+    for point in &histogram.data_points {
+        samples.push(Sample {
+            name: name.to_string(),
+            labels: labels(&point.attributes),
+            value: SampleValue::Histogram {
+                count: point.count,
+                sum: point.sum.to_f64(),
+            },
+            timestamp: point.time,
+        });
+    }
+}
+
+/// DogStatsDの行に変換（`name:value|type|#tag:value,...`）
+///
+/// ヒストグラムは`.count`と`.sum`のカウンターとして送信します。
+pub fn dogstatsd_lines(samples: &[Sample], prefix: &str, tags: &[String]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for sample in samples {
+        let name = format!("{}{}", prefix, sample.name).replace([':', '|', '@', '#'], "_");
+        let sample_tags: Vec<String> = tags.iter()
+            .cloned()
+            .chain(sample.labels.iter().map(|(key, value)| format!("{}:{}", key, value).replace([',', '|', '#'], "_")))
+            .collect();
+        let tag_suffix = if sample_tags.is_empty() {
+            String::new()
+        } else {
+            format!("|#{}", sample_tags.join(","))
+        };
+
+        match sample.value {
+            SampleValue::Gauge(value) => lines.push(format!("{}:{}|g{}", name, value, tag_suffix)),
+            SampleValue::Counter(value) => lines.push(format!("{}:{}|c{}", name, value, tag_suffix)),
+            SampleValue::Histogram { count, sum } => {
+                lines.push(format!("{}.count:{}|c{}", name, count, tag_suffix));
+                lines.push(format!("{}.sum:{}|c{}", name, sum, tag_suffix));
+            }
+        }
+    }
+    lines
+}
+
+/// InfluxDBのラインプロトコルに変換（`measurement,tag=value field=value timestamp`）
+///
+/// メトリクス名をmeasurement、ラベルと`tags`（"key:value"）をタグとし、
+/// 値は`value`フィールド、ヒストグラムは`count`と`sum`フィールドに書き込みます。タイムスタンプはナノ秒です。
+pub fn influx_lines(samples: &[Sample], prefix: &str, tags: &[String]) -> Vec<String> {
+    let global_tags: Vec<(&str, &str)> = tags.iter()
+        .map(|tag| tag.split_once(':').unwrap_or((tag.as_str(), "")))
+        .collect();
+
+    samples
+        .iter()
+        .map(|sample| {
+            let mut line = escape_influx(&format!("{}{}", prefix, sample.name), &[',', ' ']);
+            let sample_tags = global_tags.iter()
+                .copied()
+                .chain(sample.labels.iter().map(|(key, value)| (key.as_str(), value.as_str())))
+                // InfluxDBは空の値のタグを受け付けない
+                .filter(|(_, value)| !value.is_empty());
+            for (key, value) in sample_tags {
+                line.push(',');
+                line.push_str(&escape_influx(key, &[',', '=', ' ']));
+                line.push('=');
+                line.push_str(&escape_influx(value, &[',', '=', ' ']));
+            }
+
+            let fields = match sample.value {
+                SampleValue::Gauge(value) | SampleValue::Counter(value) => format!("value={}", influx_float(value)),
+                SampleValue::Histogram { count, sum } => format!("count={}i,sum={}", count, influx_float(sum)),
+            };
+            let timestamp = sample.timestamp
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_nanos())
+                .unwrap_or_default();
+
+            format!("{} {} {}", line, fields, timestamp)
+        })
+        .collect()
+}
+
+// ラインプロトコルの特殊文字をエスケープ
+fn escape_influx(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// 整数値でも浮動小数点数のフィールドとして書き込む（フィールドの型を一定にするため）
+fn influx_float(value: f64) -> String {
+    if value.is_finite() && value.fract() == 0.0 {
+        format!("{:.1}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+// 行を最大サイズ以下のパケットにまとめる（DogStatsDは改行区切りの複数メトリクスを受け付ける）
+fn datagrams(lines: &[String]) -> Vec<String> {
+    let mut datagrams: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for line in lines {
+        if !current.is_empty() && current.len() + 1 + line.len() > MAX_DATAGRAM_BYTES {
+            datagrams.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        datagrams.push(current);
+    }
+    datagrams
+}
+
+// シンクの送信先
+enum SinkOutput {
+    DogStatsd(UdpSocket),
+    InfluxHttp {
+        client: reqwest::Client,
+        url: String,
+        token: Option<String>,
+    },
+    InfluxFile(Mutex<File>),
+}
+
+/// DogStatsDまたはInfluxDBラインプロトコルでメトリクスを送信するエクスポーター
+///
+/// PeriodicReaderに登録して使用します。DogStatsDはカウンターとヒストグラムを前回の送信からの増加分、
+/// InfluxDBは累計値で送信します。
+pub struct SinkExporter {
+    output: SinkOutput,
+    prefix: String,
+    tags: Vec<String>,
+}
+
+impl SinkExporter {
+    /// シンクの設定からエクスポーターを作成
+    pub fn new(config: &SinkConfig) -> Result<Self> {
+        let output = match config.kind.as_str() {
+            "dogstatsd" => {
+                let Some(address) = &config.address else {
+                    bail!("A dogstatsd sink requires an address");
+                };
+                let socket = UdpSocket::bind("0.0.0.0:0").context("Failed to bind a UDP socket for DogStatsD")?;
+                socket
+                    .connect(address)
+                    .with_context(|| format!("Failed to resolve DogStatsD address {}", address))?;
+                socket.set_nonblocking(true).context("Failed to configure the DogStatsD socket")?;
+                SinkOutput::DogStatsd(socket)
+            }
+            "influxdb" => match (&config.url, &config.path) {
+                (Some(url), None) => SinkOutput::InfluxHttp {
+                    client: reqwest::Client::new(),
+                    url: url.clone(),
+                    token: config.token.clone(),
+                },
+                (None, Some(path)) => {
+                    let file = OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(path)
+                        .with_context(|| format!("Failed to open InfluxDB line protocol file: {}", path))?;
+                    SinkOutput::InfluxFile(Mutex::new(file))
+                }
+                _ => bail!("An influxdb sink requires exactly one of url or path"),
+            },
+            other => bail!("Unsupported metrics sink '{}' (expected dogstatsd or influxdb)", other),
+        };
+
+        Ok(Self {
+            output,
+            prefix: config.prefix.clone(),
+            tags: config.tags.clone(),
+        })
+    }
+
+    // 変換した行を送信先に書き込む
+    async fn send(&self, samples: &[Sample]) -> Result<()> {
+        match &self.output {
+            SinkOutput::DogStatsd(socket) => {
+                // 送信バッファが満杯のパケットは破棄して残りの送信を続ける（UDPのため再送はしない）
+                let datagrams = datagrams(&dogstatsd_lines(samples, &self.prefix, &self.tags));
+                let mut dropped = 0;
+                for datagram in &datagrams {
+                    match socket.send(datagram.as_bytes()) {
+                        Ok(_) => {}
+                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => dropped += 1,
+                        Err(e) => return Err(e).context("Failed to send DogStatsD packet"),
+                    }
+                }
+                if dropped > 0 {
+                    bail!("Dropped {} of {} DogStatsD packets because the socket buffer was full", dropped, datagrams.len());
+                }
+            }
+            SinkOutput::InfluxHttp { client, url, token } => {
+                let lines = influx_lines(samples, &self.prefix, &self.tags);
+                let mut request = client.post(url).body(lines.join("\n"));
+                if let Some(token) = token {
+                    request = request.header(reqwest::header::AUTHORIZATION, format!("Token {}", token));
+                }
+                request
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .with_context(|| format!("Failed to write metrics to {}", url))?;
+            }
+            SinkOutput::InfluxFile(file) => {
+                let mut content = influx_lines(samples, &self.prefix, &self.tags).join("\n");
+                content.push('\n');
+                let mut file = file.lock().map_err(|_| anyhow::anyhow!("InfluxDB line protocol file lock poisoned"))?;
+                file.write_all(content.as_bytes()).context("Failed to write InfluxDB line protocol file")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TemporalitySelector for SinkExporter {
+    fn temporality(&self, kind: InstrumentKind) -> Temporality {
+        match (&self.output, kind) {
+            (SinkOutput::DogStatsd(_), InstrumentKind::Counter | InstrumentKind::ObservableCounter | InstrumentKind::Histogram) => {
+                Temporality::Delta
+            }
+            _ => Temporality::Cumulative,
+        }
+    }
+}
+
+impl AggregationSelector for SinkExporter {
+    fn aggregation(&self, kind: InstrumentKind) -> Aggregation {
+        DefaultAggregationSelector::new().aggregation(kind)
+    }
+}
+
+#[async_trait]
+impl PushMetricsExporter for SinkExporter {
+    async fn export(&self, metrics: &mut ResourceMetrics) -> opentelemetry::metrics::Result<()> {
+        let samples = samples(metrics);
+        if samples.is_empty() {
+            return Ok(());
+        }
+        self.send(&samples)
+            .await
+            .map_err(|e| MetricsError::Other(format!("{:#}", e)))
+    }
+
+    async fn force_flush(&self) -> opentelemetry::metrics::Result<()> {
+        Ok(())
+    }
+
+    fn shutdown(&self) -> opentelemetry::metrics::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::{bail, Context, Result};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStderr, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::config::DockerSshConfig;
+
+// 同一プロセス内で複数のトンネルを開くためのソケット名の連番
+static TUNNEL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// エラーメッセージ用に保持する標準エラー出力の行数
+const STDERR_TAIL_LINES: usize = 5;
+
+/// sshのUNIXソケット転送によるリモートDockerデーモンへのトンネル
+///
+/// `ssh -L <local.sock>:<remote.sock>` を子プロセスとして起動し、
+/// ドロップ時にプロセスを終了してローカルソケットを削除します。
+pub struct SshTunnel {
+    child: Child,
+    local_socket: PathBuf,
+    stderr_reader: Option<JoinHandle<()>>,
+    // エラーメッセージに含める標準エラー出力の末尾の行
+    stderr_tail: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl SshTunnel {
+    /// `user@host[:port]` へのトンネルを開き、ローカルソケットが使えるまで待機
+    ///
+    /// 待機中はスレッドをブロックするため、非同期のコンテキストからは`spawn_blocking`で呼び出してください。
+    pub fn open(destination: &str, config: &DockerSshConfig) -> Result<Self> {
+        let local_socket = std::env::temp_dir().join(format!(
+            "container-monitoring-ssh-{}-{}.sock",
+            std::process::id(),
+            TUNNEL_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let mut child = Self::command(destination, &local_socket, config)?
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to spawn {} for {}", config.command, destination))?;
+
+        // パイプが埋まるとsshが書き込みで止まりトンネルも停止するため、標準エラー出力は読み続ける
+        let stderr_tail = Arc::new(Mutex::new(VecDeque::new()));
+        let stderr_reader = child.stderr.take().map(|stderr| {
+            let destination = destination.to_string();
+            let stderr_tail = stderr_tail.clone();
+            std::thread::spawn(move || drain_stderr(stderr, &destination, &stderr_tail))
+        });
+
+        let mut tunnel = Self { child, local_socket, stderr_reader, stderr_tail };
+        tunnel.wait_ready(Duration::from_secs(config.connect_timeout))
+            .with_context(|| format!("Failed to open SSH tunnel to {}", destination))?;
+
+        info!(destination, socket = %tunnel.local_socket.display(), "SSH tunnel to Docker daemon established");
+        Ok(tunnel)
+    }
+
+    /// `local_socket`を`remote_socket_path`へ転送するsshコマンドを作成
+    pub fn command(destination: &str, local_socket: &Path, config: &DockerSshConfig) -> Result<Command> {
+        let (destination, port) = Self::split_port(destination)?;
+
+        let mut command = Command::new(&config.command);
+        command
+            .args(["-nNT", "-o", "BatchMode=yes", "-o", "ExitOnForwardFailure=yes"])
+            .args(["-o", "StreamLocalBindUnlink=yes"])
+            .arg("-L")
+            .arg(format!("{}:{}", local_socket.display(), config.remote_socket_path));
+
+        if let Some(port) = port {
+            command.args(["-p", port]);
+        }
+        if let Some(identity_file) = &config.identity_file {
+            command.arg("-i").arg(identity_file);
+        }
+        command.arg(destination);
+
+        Ok(command)
+    }
+
+    /// トンネルのローカル側UNIXソケット
+    pub fn local_socket(&self) -> &Path {
+        &self.local_socket
+    }
+
+    // `host:port` 形式からポートを分離（IPv6アドレスは未サポート）
+    fn split_port(destination: &str) -> Result<(&str, Option<&str>)> {
+        let destination = destination.trim_end_matches('/');
+        if destination.is_empty() {
+            bail!("Empty SSH destination");
+        }
+
+        match destination.rsplit_once(':') {
+            Some((host, port)) if port.parse::<u16>().is_ok() => Ok((host, Some(port))),
+            _ => Ok((destination, None)),
+        }
+    }
+
+    // ローカルソケットが作成されるか、sshが終了するまで待機
+    fn wait_ready(&mut self, timeout: Duration) -> Result<()> {
+        let started = Instant::now();
+
+        while started.elapsed() < timeout {
+            if self.local_socket.exists() {
+                return Ok(());
+            }
+
+            if let Some(status) = self.child.try_wait()? {
+                // 終了したsshの出力を読み切ってからエラーに含める
+                if let Some(reader) = self.stderr_reader.take() {
+                    let _ = reader.join();
+                }
+                let stderr = self.stderr_tail.lock().map(|tail| Vec::from(tail.clone()).join("\n")).unwrap_or_default();
+                bail!("ssh exited with {}: {}", status, stderr.trim());
+            }
+
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        bail!("Timed out after {:?} waiting for {}", timeout, self.local_socket.display())
+    }
+}
+
+// sshの標準エラー出力をログに記録し、末尾の行を保持（sshが終了するまで読み続ける）
+fn drain_stderr(stderr: ChildStderr, destination: &str, tail: &Mutex<VecDeque<String>>) {
+    for line in BufReader::new(stderr).lines() {
+        let Ok(line) = line else { break };
+        let line = line.trim_end().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        warn!(destination, "ssh: {}", line);
+        if let Ok(mut tail) = tail.lock() {
+            if tail.len() == STDERR_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line);
+        }
+    }
+}
+
+impl Drop for SshTunnel {
+    fn drop(&mut self) {
+        debug!(socket = %self.local_socket.display(), "Closing SSH tunnel");
+        if let Err(e) = self.child.kill() {
+            warn!("Failed to stop ssh process: {}", e);
+        }
+        let _ = self.child.wait();
+        let _ = std::fs::remove_file(&self.local_socket);
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use crate::config::StateConfig;
+
+/// 状態ファイルの形式のバージョン
+const STATE_VERSION: u32 = 1;
+
+/// カウンターの前回値（コンテナの実行の開始時刻と組）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub started_at: Option<i64>,
+    pub value: u64,
+}
+
+/// 前回の収集サイクルで観測したコンテナ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InventoryEntry {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    pub status: String,
+    pub started_at: Option<i64>,
+}
+
+/// Dockerホストごとの状態
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HostState {
+    /// キーは前回値のキー（"<container_id>/<種別>"など）
+    pub baselines: BTreeMap<String, Baseline>,
+    pub inventory: Vec<InventoryEntry>,
+}
+
+/// エージェント全体の状態（キーはDockerホスト名）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentState {
+    pub hosts: BTreeMap<String, HostState>,
+}
+
+// ファイルに書き込む形式（状態のJSONのCRC32で破損を検出）
+#[derive(Serialize, Deserialize)]
+struct StateEnvelope {
+    version: u32,
+    saved_at: i64,
+    checksum: u32,
+    state: AgentState,
+}
+
+// 状態のJSONのチェックサム（BTreeMapと整数・文字列のみのため、再シリアライズしても同じバイト列になる）
+fn checksum(state: &AgentState) -> Result<u32> {
+    let bytes = serde_json::to_vec(state).context("Failed to serialize agent state")?;
+    Ok(crc32fast::hash(&bytes))
+}
+
+/// 状態ファイル
+#[derive(Debug, Clone)]
+pub struct StateFile {
+    path: PathBuf,
+}
+
+impl StateFile {
+    /// パスを指定して作成
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// 状態ファイルのパス
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 状態を読み込み（ファイルがない場合はNone、破損している場合はエラー）
+    pub fn load(&self) -> Result<Option<AgentState>> {
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read state file {}", self.path.display())),
+        };
+
+        let envelope: StateEnvelope = serde_json::from_slice(&content)
+            .with_context(|| format!("Failed to parse state file {}", self.path.display()))?;
+        if envelope.version != STATE_VERSION {
+            bail!(
+                "Unsupported state file version {} in {} (expected {})",
+                envelope.version,
+                self.path.display(),
+                STATE_VERSION
+            );
+        }
+        if checksum(&envelope.state)? != envelope.checksum {
+            bail!("Checksum mismatch in state file {}", self.path.display());
+        }
+
+        debug!(path = %self.path.display(), saved_at = envelope.saved_at, "State file loaded");
+        Ok(Some(envelope.state))
+    }
+
+    /// 状態を書き込み（一時ファイルに書き込んでからリネームするため、途中で停止しても既存のファイルは壊れない）
+    pub fn save(&self, state: &AgentState) -> Result<()> {
+        let envelope = StateEnvelope {
+            version: STATE_VERSION,
+            saved_at: chrono::Utc::now().timestamp(),
+            checksum: checksum(state)?,
+            state: state.clone(),
+        };
+        let content = serde_json::to_vec(&envelope).context("Failed to serialize agent state")?;
+
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create state directory {}", dir.display()))?;
+        }
+
+        let temp_path = self.temp_path();
+        let mut file = fs::File::create(&temp_path)
+            .with_context(|| format!("Failed to create {}", temp_path.display()))?;
+        file.write_all(&content)
+            .and_then(|_| file.sync_all())
+            .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+        fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("Failed to replace state file {}", self.path.display()))?;
+
+        Ok(())
+    }
+
+    /// 破損したファイルを退避（調査用に"<path>.corrupt"として残す）
+    pub fn quarantine(&self) -> Result<PathBuf> {
+        let corrupt_path = self.sibling_path("corrupt");
+        fs::rename(&self.path, &corrupt_path)
+            .with_context(|| format!("Failed to move state file {} aside", self.path.display()))?;
+        Ok(corrupt_path)
+    }
+
+    // 書き込み中の一時ファイルのパス
+    fn temp_path(&self) -> PathBuf {
+        self.sibling_path("tmp")
+    }
+
+    // 状態ファイルと同じディレクトリの、拡張子を追加したパス
+    fn sibling_path(&self, extension: &str) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".");
+        path.push(extension);
+        PathBuf::from(path)
+    }
+}
+
+/// 収集ループ間で共有する状態の保存先
+///
+/// ファイルへの書き込みはブロックするため、非同期タスクからは`spawn_blocking`で呼び出してください。
+pub struct StateStore {
+    file: StateFile,
+    write_every_cycle: bool,
+    // 状態と更新ごとに増える世代
+    state: Mutex<(AgentState, u64)>,
+    // 最後に書き込んだ世代（書き込みからリネームまで保持し、同時の保存を直列化する）
+    saved: Mutex<Option<u64>>,
+}
+
+impl StateStore {
+    /// 状態ファイルを開き、保存されていた状態を読み込む
+    ///
+    /// ファイルが破損している場合は退避して空の状態から開始します。
+    pub fn open(path: &str, config: &StateConfig) -> Result<(Self, AgentState)> {
+        let write_every_cycle = match config.write.as_str() {
+            "cycle" => true,
+            "shutdown" => false,
+            other => bail!("Unsupported state.write '{}' (expected cycle or shutdown)", other),
+        };
+
+        let file = StateFile::new(path);
+        let restored = match file.load() {
+            Ok(Some(state)) => {
+                info!(path = %file.path().display(), hosts = state.hosts.len(), "Restored counter baselines from state file");
+                state
+            }
+            Ok(None) => AgentState::default(),
+            Err(e) => {
+                warn!("{:#}, starting with a clean state", e);
+                match file.quarantine() {
+                    Ok(corrupt_path) => warn!("Moved the unreadable state file to {}", corrupt_path.display()),
+                    Err(e) => warn!("{:#}", e),
+                }
+                AgentState::default()
+            }
+        };
+
+        let store = Self {
+            file,
+            write_every_cycle,
+            // 保存しなかったホストの状態も次回に引き継ぐため、読み込んだ状態から開始
+            state: Mutex::new((restored.clone(), 0)),
+            saved: Mutex::new(None),
+        };
+        Ok((store, restored))
+    }
+
+    /// ホストの状態を更新し、収集サイクルごとに書き込む設定であれば保存
+    pub fn record_cycle(&self, host: &str, state: HostState) -> Result<()> {
+        self.update(host, state);
+        if self.write_every_cycle {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// ホストの状態を更新（保存はしない）
+    pub fn update(&self, host: &str, state: HostState) {
+        if let Ok(mut agent_state) = self.state.lock() {
+            agent_state.0.hosts.insert(host.to_string(), state);
+            agent_state.1 += 1;
+        }
+    }
+
+    /// 現在の状態をファイルに保存
+    ///
+    /// 他のホストの保存が同じか新しい状態を書き込み済みの場合は書き込みません。
+    pub fn save(&self) -> Result<()> {
+        let Ok(mut saved) = self.saved.lock() else {
+            bail!("State store lock poisoned");
+        };
+        let (state, generation) = match self.state.lock() {
+            Ok(state) => state.clone(),
+            Err(_) => bail!("State store lock poisoned"),
+        };
+        if saved.is_some_and(|saved| generation <= saved) {
+            debug!(generation, "State already saved");
+            return Ok(());
+        }
+        self.file.save(&state)?;
+        *saved = Some(generation);
+        Ok(())
+    }
+}
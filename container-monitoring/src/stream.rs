@@ -0,0 +1,131 @@
+use futures::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+use crate::docker::{ContainerEvent, ContainerInfo, DockerClient};
+
+/// ストリームチャネルのバッファサイズ（遅い購読者はこれを超えると取りこぼす）
+pub const STREAM_CHANNEL_CAPACITY: usize = 256;
+
+/// Dockerイベントストリームが切断された場合の再接続待ち時間
+const EVENTS_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// `/api/stream`で配信されるイベント
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// 収集サイクルの完了
+    Cycle {
+        host: String,
+        timestamp: i64,
+        containers: Vec<ContainerInfo>,
+    },
+    /// Dockerのコンテナライフサイクルイベント
+    Lifecycle(ContainerEvent),
+}
+
+impl StreamEvent {
+    /// SSEのイベント名
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StreamEvent::Cycle { .. } => "cycle",
+            StreamEvent::Lifecycle(_) => "lifecycle",
+        }
+    }
+}
+
+/// 購読者ごとのフィルタ（クエリパラメータ `?host=edge-1&name=web-*&image=nginx*`）
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StreamFilter {
+    /// Dockerホスト名（完全一致）
+    #[serde(default)]
+    pub host: Option<String>,
+
+    /// コンテナ名パターン（'*'と'?'をサポート）
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// イメージパターン（'*'と'?'をサポート）
+    #[serde(default)]
+    pub image: Option<String>,
+}
+
+impl StreamFilter {
+    /// コンテナがフィルタに一致するか判定
+    pub fn matches(&self, name: &str, image: &str) -> bool {
+        let name_matches = self.name.as_deref()
+            .is_none_or(|pattern| DockerClient::matches_pattern(name, pattern));
+        let image_matches = self.image.as_deref()
+            .is_none_or(|pattern| DockerClient::matches_pattern(image, pattern));
+
+        name_matches && image_matches
+    }
+
+    /// ホストがフィルタに一致するか判定
+    pub fn matches_host(&self, host: &str) -> bool {
+        self.host.as_deref().is_none_or(|expected| expected == host)
+    }
+
+    /// イベントにフィルタを適用（配信不要の場合はNone）
+    pub fn apply(&self, event: &StreamEvent) -> Option<StreamEvent> {
+        match event {
+            StreamEvent::Cycle { host, .. } | StreamEvent::Lifecycle(ContainerEvent { host, .. })
+                if !self.matches_host(host) =>
+            {
+                None
+            }
+            StreamEvent::Cycle { host, timestamp, containers } => {
+                let containers = containers.iter()
+                    .filter(|c| self.matches(&c.name, &c.image))
+                    .cloned()
+                    .collect();
+
+                Some(StreamEvent::Cycle {
+                    host: host.clone(),
+                    timestamp: *timestamp,
+                    containers,
+                })
+            }
+            StreamEvent::Lifecycle(container_event) => {
+                if self.matches(&container_event.container_name, &container_event.image) {
+                    Some(event.clone())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// 新しいストリームチャネルを作成
+pub fn channel() -> broadcast::Sender<StreamEvent> {
+    let (sender, _) = broadcast::channel(STREAM_CHANNEL_CAPACITY);
+    sender
+}
+
+/// Dockerのライフサイクルイベントをストリームチャネルへ転送し続ける
+pub async fn forward_container_events(docker_client: DockerClient, host: String, events: broadcast::Sender<StreamEvent>) {
+    loop {
+        info!(host = %host, "Subscribing to Docker container events");
+        let mut container_events = Box::pin(docker_client.container_events());
+
+        while let Some(result) = container_events.next().await {
+            match result {
+                Ok(mut event) => {
+                    event.host = host.clone();
+                    debug!(action = %event.action, container_id = %event.container_id, "Container event received");
+                    // 購読者がいない場合の送信エラーは無視する
+                    let _ = events.send(StreamEvent::Lifecycle(event));
+                }
+                Err(e) => {
+                    warn!(host = %host, "Docker event stream error: {}", e);
+                    break;
+                }
+            }
+        }
+
+        tokio::time::sleep(EVENTS_RECONNECT_DELAY).await;
+    }
+}
@@ -1,53 +1,55 @@
-use anyhow::{Context, Result};
-use opentelemetry::sdk::export::metrics::aggregation;
-use opentelemetry::sdk::metrics::reader;
-use opentelemetry::sdk::metrics::PeriodicReader;
-use opentelemetry::sdk::{trace, Resource};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use flate2::write::GzEncoder;
+use opentelemetry_http::{Bytes, HttpClient, HttpError, Request, Response};
+use opentelemetry_otlp::{
+    Compression, HttpExporterBuilder, LogExporterBuilder, MetricsExporterBuilder, SpanExporterBuilder,
+    TonicExporterBuilder, WithExportConfig,
+};
 use opentelemetry::KeyValue;
-use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::metrics::data::{self, ResourceMetrics, Temporality};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::metrics::exporter::PushMetricsExporter;
+use opentelemetry_sdk::metrics::reader::{AggregationSelector, DefaultAggregationSelector, TemporalitySelector};
+use opentelemetry_sdk::metrics::{Aggregation, InstrumentKind, MeterProvider, PeriodicReader};
+use opentelemetry_sdk::trace::{self, Sampler, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use prometheus::Registry;
+use std::fs::OpenOptions;
+use std::io::Write;
 use std::time::Duration;
-use tracing::info;
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+use tonic::transport::{Certificate, ClientTlsConfig, Identity};
+use tracing::{info, warn};
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::EnvFilter;
 
-use crate::config::Config;
+use crate::config::{Config, OtlpConfig, OtlpTlsConfig, TelemetryConfig};
+use crate::metrics::CounterStartTimes;
+use crate::resource::telemetry_resource;
+use crate::sinks::SinkExporter;
 
 #[tracing::instrument(level = "info")]
-pub fn init_telemetry(config: &Config) -> Result<impl Drop> {
-    // Set up OpenTelemetry resource
-    let resource = Resource::new(vec![
-        KeyValue::new("service.name", config.telemetry.service_name.clone()),
-        KeyValue::new("service.version", env!("CARGO_PKG_VERSION").to_string()),
-    ]);
-
-    // Configure OTLP exporter
-    let otlp_exporter = opentelemetry_otlp::new_exporter()
-        .tonic()
-        .with_endpoint(&config.telemetry.otel_endpoint);
-
-    // Initialize OpenTelemetry tracing
-    let tracer = opentelemetry_otlp::new_pipeline()
-        .tracing()
-        .with_exporter(otlp_exporter.clone())
-        .with_trace_config(
-            trace::config()
-                .with_resource(resource.clone())
-                .with_sampler(trace::Sampler::AlwaysOn),
-        )
-        .install_batch(opentelemetry::runtime::Tokio)
-        .context("Failed to initialize OpenTelemetry tracer")?;
-
-    // Initialize OpenTelemetry metrics
-    let meter_provider = opentelemetry_otlp::new_pipeline()
-        .metrics(opentelemetry::runtime::Tokio)
-        .with_exporter(otlp_exporter)
-        .with_resource(resource)
-        .with_period(Duration::from_secs(10))
-        .build()
-        .context("Failed to initialize OpenTelemetry metrics")?;
+pub fn init_telemetry(config: &Config) -> Result<TelemetryGuard> {
+    // Set up OpenTelemetry resource from the detectors, config and OTEL_* env vars
+    let resource = telemetry_resource(&config.telemetry)?;
 
-    // Set as global meter provider
-    let _meter_provider_guard = opentelemetry::global::set_meter_provider(meter_provider);
+    // Initialize OpenTelemetry tracing (nothing is started when traces are not exported)
+    let tracer_provider = build_tracer_provider(&config.telemetry, resource.clone())?;
+    let tracer = tracer_provider.as_ref().map(|tracer_provider| {
+        opentelemetry::global::set_tracer_provider(tracer_provider.clone());
+        tracer_provider.versioned_tracer("container-monitoring", Some(env!("CARGO_PKG_VERSION")), None::<&str>, None)
+    });
+
+    // Initialize OpenTelemetry metrics; /metrics reads the default Prometheus registry
+    let counter_start_times = CounterStartTimes::new();
+    let meter_provider = build_meter_provider_with_start_times(
+        &config.telemetry,
+        resource,
+        prometheus::default_registry(),
+        &counter_start_times,
+    )?;
+    opentelemetry::global::set_meter_provider(meter_provider.clone());
 
     // Set up tracing subscriber
     let env_filter = EnvFilter::try_from_default_env()
@@ -55,7 +57,7 @@ pub fn init_telemetry(config: &Config) -> Result<impl Drop> {
         .unwrap_or_else(|_| EnvFilter::new("info"));
 
     // Create a tracing layer with the configured tracer
-    let telemetry = tracing_opentelemetry::layer().with_tracer(tracer);
+    let telemetry = tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer));
 
     // Create the subscriber and set it as global default
     tracing_subscriber::registry()
@@ -64,22 +66,449 @@ pub fn init_telemetry(config: &Config) -> Result<impl Drop> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    info!(
-        "Telemetry initialized with OTLP exporter at {}",
-        config.telemetry.otel_endpoint
-    );
+    match config.telemetry.otel_exporter.as_str() {
+        "otlp" => info!(
+            "Telemetry initialized with OTLP exporter at {} ({})",
+            config.telemetry.otel_endpoint, config.telemetry.otlp.protocol
+        ),
+        exporter => info!("Telemetry initialized with {} exporter; metrics are served on /metrics", exporter),
+    }
 
     // Return a guard that will flush telemetry on drop
-    Ok(TelemetryGuard {})
+    Ok(TelemetryGuard {
+        meter_provider,
+        tracer_provider,
+        counter_start_times,
+    })
+}
+
+/// Build the trace sampler from `telemetry.sampler` and `telemetry.sampling_ratio`.
+pub fn build_sampler(telemetry: &TelemetryConfig) -> Result<Sampler> {
+    let ratio = telemetry.sampling_ratio;
+    if !(0.0..=1.0).contains(&ratio) {
+        bail!("telemetry.sampling_ratio must be between 0.0 and 1.0, got {}", ratio);
+    }
+
+    let sampler = match telemetry.sampler.as_str() {
+        "always_on" => Sampler::AlwaysOn,
+        "always_off" => Sampler::AlwaysOff,
+        "traceidratio" => Sampler::TraceIdRatioBased(ratio),
+        "parentbased_always_on" => Sampler::ParentBased(Box::new(Sampler::AlwaysOn)),
+        "parentbased_always_off" => Sampler::ParentBased(Box::new(Sampler::AlwaysOff)),
+        "parentbased_traceidratio" => Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(ratio))),
+        other => bail!("Unsupported trace sampler '{}'", other),
+    };
+
+    Ok(sampler)
+}
+
+/// Build the meter provider: a Prometheus reader on `registry` plus a periodic reader for the
+/// configured exporter (`otlp`, `stdout` or `file`; nothing else for `none`), plus one periodic
+/// reader per entry in `telemetry.sinks` (DogStatsD or InfluxDB line protocol).
+///
+/// The provider is not installed globally so callers (and tests) decide where it is used.
+pub fn build_meter_provider(telemetry: &TelemetryConfig, resource: Resource, registry: &Registry) -> Result<MeterProvider> {
+    build_meter_provider_with_start_times(telemetry, resource, registry, &CounterStartTimes::new())
+}
+
+/// Build the meter provider like `build_meter_provider`, stamping cumulative container counters
+/// with the start times in `start_times`. Pass the same map to the collectors that record to it
+/// (`MonitorBuilder::with_counter_start_times`).
+pub fn build_meter_provider_with_start_times(
+    telemetry: &TelemetryConfig,
+    resource: Resource,
+    registry: &Registry,
+    start_times: &CounterStartTimes,
+) -> Result<MeterProvider> {
+    let temporality = ConfiguredTemporality::new(&telemetry.otlp.temporality)?;
+    let prometheus_exporter = opentelemetry_prometheus::exporter()
+        .with_registry(registry.clone())
+        .build()
+        .context("Failed to initialize Prometheus exporter")?;
+
+    let builder = MeterProvider::builder()
+        .with_reader(prometheus_exporter)
+        .with_resource(resource);
+
+    let builder = match telemetry.otel_exporter.as_str() {
+        "otlp" => match exporter_builder::<MetricsExporterBuilder>(telemetry, &telemetry.otel_endpoint)? {
+            Some(exporter) => {
+                let exporter = exporter
+                    .build_metrics_exporter(Box::new(temporality), Box::new(DefaultAggregationSelector::new()))
+                    .context("Failed to initialize OpenTelemetry metrics")?;
+                builder.with_reader(periodic_reader(exporter, telemetry, start_times))
+            }
+            None => builder,
+        },
+        "stdout" | "file" => {
+            let exporter = opentelemetry_stdout::MetricsExporter::builder()
+                .with_writer(RecordWriter::new(telemetry_output(telemetry)?))
+                .with_temporality_selector(temporality)
+                .build();
+            builder.with_reader(periodic_reader(exporter, telemetry, start_times))
+        }
+        "none" => builder,
+        other => bail!("Unsupported telemetry exporter '{}' (expected otlp, stdout, file or none)", other),
+    };
+
+    // Each StatsD/InfluxDB sink gets its own reader so it can use its own interval
+    let mut builder = builder;
+    for sink in &telemetry.sinks {
+        let exporter = SinkExporter::new(sink)
+            .with_context(|| format!("Failed to initialize {} metrics sink", sink.kind))?;
+        let reader = PeriodicReader::builder(exporter, runtime::Tokio)
+            .with_interval(Duration::from_secs(sink.interval.max(1)))
+            .with_timeout(Duration::from_secs(telemetry.otlp.export_timeout.max(1)))
+            .build();
+        builder = builder.with_reader(reader);
+    }
+
+    Ok(builder.build())
+}
+
+/// Build the tracer provider for the configured exporter, or `None` when traces are not exported.
+pub fn build_tracer_provider(telemetry: &TelemetryConfig, resource: Resource) -> Result<Option<TracerProvider>> {
+    let config = trace::config()
+        .with_resource(resource)
+        .with_sampler(build_sampler(telemetry)?);
+
+    let builder = TracerProvider::builder().with_config(config);
+    let builder = match telemetry.otel_exporter.as_str() {
+        "otlp" => match exporter_builder::<SpanExporterBuilder>(telemetry, &telemetry.otel_endpoint)? {
+            Some(exporter) => {
+                let exporter = exporter
+                    .build_span_exporter()
+                    .context("Failed to initialize OpenTelemetry tracer")?;
+                builder.with_batch_exporter(exporter, runtime::Tokio)
+            }
+            None => return Ok(None),
+        },
+        "stdout" | "file" => {
+            let exporter = opentelemetry_stdout::SpanExporter::builder()
+                .with_writer(RecordWriter::new(telemetry_output(telemetry)?))
+                .build();
+            builder.with_batch_exporter(exporter, runtime::Tokio)
+        }
+        "none" => return Ok(None),
+        other => bail!("Unsupported telemetry exporter '{}' (expected otlp, stdout, file or none)", other),
+    };
+
+    Ok(Some(builder.build()))
+}
+
+/// Build an OTLP log exporter using the telemetry protocol, headers, compression and TLS settings.
+pub fn build_log_exporter(telemetry: &TelemetryConfig, endpoint: &str) -> Result<opentelemetry_otlp::LogExporter> {
+    let Some(exporter) = exporter_builder::<LogExporterBuilder>(telemetry, endpoint)? else {
+        bail!("Forwarding container logs requires telemetry.otlp.protocol to be grpc or http/protobuf");
+    };
+
+    exporter
+        .build_log_exporter()
+        .with_context(|| format!("Failed to create OTLP log exporter for {}", endpoint))
+}
+
+// Push metrics to an exporter every export_interval
+fn periodic_reader<E: PushMetricsExporter>(exporter: E, telemetry: &TelemetryConfig, start_times: &CounterStartTimes) -> PeriodicReader {
+    let exporter = StartTimeExporter {
+        exporter,
+        start_times: start_times.clone(),
+    };
+    PeriodicReader::builder(exporter, runtime::Tokio)
+        .with_interval(Duration::from_secs(telemetry.otlp.export_interval.max(1)))
+        .with_timeout(Duration::from_secs(telemetry.otlp.export_timeout.max(1)))
+        .build()
+}
+
+// Output for the stdout and file exporters
+fn telemetry_output(telemetry: &TelemetryConfig) -> Result<Box<dyn Write + Send + Sync>> {
+    if telemetry.otel_exporter == "stdout" {
+        return Ok(Box::new(std::io::stdout()));
+    }
+
+    let Some(path) = &telemetry.file_path else {
+        bail!("telemetry.file_path is required when otel_exporter is \"file\"");
+    };
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open telemetry file: {}", path))?;
+
+    Ok(Box::new(file))
+}
+
+// The stdout exporters serialize one record in many small writes; buffering until the
+// trailing newline keeps metric and span records from interleaving in the shared output.
+struct RecordWriter {
+    buffer: Vec<u8>,
+    output: Box<dyn Write + Send + Sync>,
+}
+
+impl RecordWriter {
+    fn new(output: Box<dyn Write + Send + Sync>) -> Self {
+        Self {
+            buffer: Vec::new(),
+            output,
+        }
+    }
+}
+
+impl Write for RecordWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+
+        if let Some(position) = self.buffer.iter().rposition(|&byte| byte == b'\n') {
+            let rest = self.buffer.split_off(position + 1);
+            let records = std::mem::replace(&mut self.buffer, rest);
+            self.output.write_all(&records)?;
+            self.output.flush()?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        // Incomplete records stay buffered until their newline arrives
+        Ok(())
+    }
+}
+
+// Cumulative for everything, or delta for counters and histograms. Up-down counters stay
+// cumulative because their deltas cannot be summed back into a meaningful current value.
+#[derive(Debug, Clone, Copy)]
+struct ConfiguredTemporality {
+    delta: bool,
+}
+
+impl ConfiguredTemporality {
+    fn new(temporality: &str) -> Result<Self> {
+        match temporality {
+            "cumulative" => Ok(Self { delta: false }),
+            "delta" => Ok(Self { delta: true }),
+            other => bail!("Unsupported OTLP temporality '{}' (expected cumulative or delta)", other),
+        }
+    }
+}
+
+impl TemporalitySelector for ConfiguredTemporality {
+    fn temporality(&self, kind: InstrumentKind) -> Temporality {
+        match kind {
+            InstrumentKind::Counter | InstrumentKind::ObservableCounter | InstrumentKind::Histogram if self.delta => {
+                Temporality::Delta
+            }
+            _ => Temporality::Cumulative,
+        }
+    }
+}
+
+// Create the exporter builder for the configured protocol, or None for "none"
+fn exporter_builder<B>(telemetry: &TelemetryConfig, endpoint: &str) -> Result<Option<B>>
+where
+    B: From<TonicExporterBuilder> + From<HttpExporterBuilder>,
+{
+    let otlp = &telemetry.otlp;
+
+    let timeout = Duration::from_secs(otlp.export_timeout.max(1));
+    let gzip = match otlp.compression.as_deref() {
+        None | Some("none") => false,
+        Some("gzip") => true,
+        Some(other) => bail!("Unsupported OTLP compression '{}' (expected gzip)", other),
+    };
+
+    match otlp.protocol.as_str() {
+        "grpc" => {
+            let mut exporter = opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint)
+                .with_timeout(timeout)
+                .with_metadata(grpc_metadata(otlp)?);
+
+            if gzip {
+                exporter = exporter.with_compression(Compression::Gzip);
+            }
+            if let Some(tls) = &otlp.tls {
+                exporter = exporter.with_tls_config(grpc_tls_config(tls)?);
+            }
+
+            Ok(Some(exporter.into()))
+        }
+        "http/protobuf" => {
+            let client = http_client(otlp, timeout)?;
+            let exporter = opentelemetry_otlp::new_exporter()
+                .http()
+                .with_endpoint(endpoint)
+                .with_timeout(timeout)
+                .with_headers(otlp.headers.clone());
+
+            let exporter = if gzip {
+                exporter.with_http_client(GzipHttpClient(client))
+            } else {
+                exporter.with_http_client(client)
+            };
+
+            Ok(Some(exporter.into()))
+        }
+        "none" => Ok(None),
+        other => bail!("Unsupported OTLP protocol '{}' (expected grpc, http/protobuf or none)", other),
+    }
+}
+
+// Convert configured headers into gRPC metadata
+fn grpc_metadata(otlp: &OtlpConfig) -> Result<MetadataMap> {
+    let mut metadata = MetadataMap::new();
+
+    for (name, value) in &otlp.headers {
+        let key = MetadataKey::from_bytes(name.to_ascii_lowercase().as_bytes())
+            .with_context(|| format!("Invalid OTLP header name: {}", name))?;
+        let value = MetadataValue::try_from(value.as_str())
+            .with_context(|| format!("Invalid OTLP header value for {}", name))?;
+        metadata.insert(key, value);
+    }
+
+    Ok(metadata)
+}
+
+// Build the tonic TLS settings from the CA and client certificate paths
+fn grpc_tls_config(tls: &OtlpTlsConfig) -> Result<ClientTlsConfig> {
+    let mut tls_config = ClientTlsConfig::new();
+
+    if let Some(ca_path) = &tls.ca_path {
+        let ca = std::fs::read(ca_path).with_context(|| format!("Failed to read OTLP CA certificate: {}", ca_path))?;
+        tls_config = tls_config.ca_certificate(Certificate::from_pem(ca));
+    }
+    if let Some((cert, key)) = client_identity(tls)? {
+        tls_config = tls_config.identity(Identity::from_pem(cert, key));
+    }
+
+    Ok(tls_config)
+}
+
+// Build the reqwest client used by the OTLP/HTTP exporter
+fn http_client(otlp: &OtlpConfig, timeout: Duration) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder().timeout(timeout);
+
+    if let Some(tls) = &otlp.tls {
+        if let Some(ca_path) = &tls.ca_path {
+            let ca = std::fs::read(ca_path).with_context(|| format!("Failed to read OTLP CA certificate: {}", ca_path))?;
+            let ca = reqwest::Certificate::from_pem(&ca)
+                .with_context(|| format!("Invalid OTLP CA certificate: {}", ca_path))?;
+            builder = builder.add_root_certificate(ca);
+        }
+        if let Some((cert, key)) = client_identity(tls)? {
+            let identity = reqwest::Identity::from_pkcs8_pem(&cert, &key)
+                .with_context(|| "Invalid OTLP client certificate or key")?;
+            builder = builder.identity(identity);
+        }
+    }
+
+    builder.build().with_context(|| "Failed to build OTLP HTTP client")
+}
+
+// Read the client certificate and key; both or neither must be configured
+fn client_identity(tls: &OtlpTlsConfig) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+    match (&tls.cert_path, &tls.key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            let cert = std::fs::read(cert_path)
+                .with_context(|| format!("Failed to read OTLP client certificate: {}", cert_path))?;
+            let key = std::fs::read(key_path)
+                .with_context(|| format!("Failed to read OTLP client key: {}", key_path))?;
+            Ok(Some((cert, key)))
+        }
+        (None, None) => Ok(None),
+        _ => bail!("telemetry.otlp.tls.cert_path and key_path must be set together"),
+    }
+}
+
+// The SDK stamps every cumulative point with the reader's start time. Container counters start
+// counting when a container run is first observed (or at its StartedAt after a restart), so their
+// points get the start time recorded by the collectors before they are exported.
+struct StartTimeExporter<E> {
+    exporter: E,
+    start_times: CounterStartTimes,
+}
+
+impl<E: TemporalitySelector> TemporalitySelector for StartTimeExporter<E> {
+    fn temporality(&self, kind: InstrumentKind) -> Temporality {
+        self.exporter.temporality(kind)
+    }
 }
 
-// Helper struct that will flush telemetry on drop
-pub struct TelemetryGuard {}
+impl<E: AggregationSelector> AggregationSelector for StartTimeExporter<E> {
+    fn aggregation(&self, kind: InstrumentKind) -> Aggregation {
+        self.exporter.aggregation(kind)
+    }
+}
+
+#[async_trait]
+impl<E: PushMetricsExporter> PushMetricsExporter for StartTimeExporter<E> {
+    async fn export(&self, metrics: &mut ResourceMetrics) -> opentelemetry::metrics::Result<()> {
+        for metric in metrics.scope_metrics.iter_mut().flat_map(|scope| scope.metrics.iter_mut()) {
+            let Some(sum) = data::Aggregation::as_mut(metric.data.as_mut()).downcast_mut::<data::Sum<u64>>() else {
+                continue;
+            };
+            if sum.temporality != Temporality::Cumulative {
+                continue;
+            }
+            for point in &mut sum.data_points {
+                let labels: Vec<KeyValue> = point.attributes.iter().map(|(key, value)| KeyValue::new(key.clone(), value.clone())).collect();
+                if let Some(start_time) = self.start_times.get(&metric.name, &labels) {
+                    point.start_time = Some(start_time);
+                }
+            }
+        }
+        self.exporter.export(metrics).await
+    }
+
+    async fn force_flush(&self) -> opentelemetry::metrics::Result<()> {
+        self.exporter.force_flush().await
+    }
+
+    fn shutdown(&self) -> opentelemetry::metrics::Result<()> {
+        self.exporter.shutdown()
+    }
+}
+
+// The OTLP/HTTP exporter has no compression support of its own, so bodies are gzipped here
+#[derive(Debug)]
+struct GzipHttpClient(reqwest::Client);
+
+#[async_trait]
+impl HttpClient for GzipHttpClient {
+    async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+        let (mut parts, body) = request.into_parts();
+
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&body)?;
+        parts.headers.insert("content-encoding", "gzip".parse()?);
+
+        HttpClient::send(&self.0, Request::from_parts(parts, encoder.finish()?)).await
+    }
+}
+
+// Helper struct that will flush telemetry on drop; only what init_telemetry started is shut down
+pub struct TelemetryGuard {
+    meter_provider: MeterProvider,
+    tracer_provider: Option<TracerProvider>,
+    counter_start_times: CounterStartTimes,
+}
+
+impl TelemetryGuard {
+    /// Start times read by the global meter provider's exporters; hand them to the collectors
+    /// that record to the global meter.
+    pub fn counter_start_times(&self) -> CounterStartTimes {
+        self.counter_start_times.clone()
+    }
+}
 
 impl Drop for TelemetryGuard {
     fn drop(&mut self) {
         info!("Shutting down telemetry");
-        opentelemetry::global::shutdown_tracer_provider();
-        opentelemetry::global::shutdown_meter_provider();
+        if self.tracer_provider.take().is_some() {
+            // Dropping the last provider reference flushes and shuts down its span processors
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+        if let Err(e) = self.meter_provider.shutdown() {
+            warn!("Failed to shut down meter provider: {}", e);
+        }
     }
 }
@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use anyhow::Result;
+use tempfile::tempdir;
+
+use container_monitoring::agent::{AgentCollector, ProcessStats, RuntimeStats};
+
+// テスト用のプロセスディレクトリ（/proc/<pid>）を作成
+fn write_process_dir(root: &Path) -> Result<()> {
+    std::fs::create_dir_all(root.join("fd"))?;
+    for fd in 0..5 {
+        std::fs::write(root.join("fd").join(fd.to_string()), "")?;
+    }
+
+    // コマンド名に空白と括弧を含む
+    std::fs::write(
+        root.join("stat"),
+        "4242 (container (mon)) S 1 4242 4242 0 -1 4194560 1500 0 0 0 250 75 0 0 20 0 9 0 12345 104857600 2560 18446744073709551615\n",
+    )?;
+
+    std::fs::write(root.join("status"), "\
+Name:\tcontainer-monit
+VmSize:\t  102400 kB
+VmRSS:\t   10240 kB
+Threads:\t9
+")?;
+
+    std::fs::write(root.join("limits"), "\
+Limit                     Soft Limit           Hard Limit           Units
+Max cpu time              unlimited            unlimited            seconds
+Max open files            1024                 524288               files
+")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_read_process_stats() -> Result<()> {
+    let process_dir = tempdir()?;
+    write_process_dir(process_dir.path())?;
+
+    let stats = ProcessStats::read(process_dir.path())?;
+
+    // utime/stimeはUSER_HZ単位
+    assert_eq!(stats.cpu_user_seconds, 2.5);
+    assert_eq!(stats.cpu_system_seconds, 0.75);
+    assert_eq!(stats.resident_memory_bytes, 10240 * 1024);
+    assert_eq!(stats.virtual_memory_bytes, 102400 * 1024);
+    assert_eq!(stats.threads, 9);
+    assert_eq!(stats.open_fds, 5);
+    assert_eq!(stats.max_fds, Some(1024));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_collect_agent_stats() -> Result<()> {
+    // プロセスディレクトリが存在しない場合はエラー
+    let missing = tempdir()?;
+    let mut collector = AgentCollector::with_process_dir(missing.path().join("missing"));
+    assert!(collector.collect().is_err());
+
+    // 実際の/proc/selfとランタイムから収集
+    let (sender, receiver) = tokio::sync::oneshot::channel::<()>();
+    let task = tokio::spawn(receiver);
+
+    let stats = AgentCollector::new().collect()?;
+    assert!(stats.process.resident_memory_bytes > 0);
+    assert!(stats.process.open_fds > 0);
+
+    let runtime = stats.runtime.expect("collector created inside the runtime");
+    assert_eq!(runtime.workers, 2);
+    assert!(runtime.alive_tasks >= 1);
+
+    sender.send(()).ok();
+    task.await??;
+
+    Ok(())
+}
+
+#[test]
+fn test_runtime_stats_of_current_thread_runtime() -> Result<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+
+    let stats = RuntimeStats::read(runtime.handle());
+    assert_eq!(stats.workers, 1);
+    assert_eq!(stats.alive_tasks, 0);
+    assert_eq!(stats.global_queue_depth, 0);
+
+    Ok(())
+}
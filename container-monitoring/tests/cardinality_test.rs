@@ -0,0 +1,79 @@
+use anyhow::Result;
+use opentelemetry::KeyValue;
+
+use container_monitoring::cardinality::{overflow_labels, series_key, ContainerLabels, SeriesLimiter, OVERFLOW_LABEL};
+
+// テスト用のコンテナラベル
+fn labels(name: &str) -> Vec<KeyValue> {
+    vec![
+        KeyValue::new("host", "local"),
+        KeyValue::new("container_name", name.to_string()),
+    ]
+}
+
+#[test]
+fn test_container_labels() -> Result<()> {
+    // 既定ではすべてのラベルを付与
+    let all = ContainerLabels::default().labels("local", "abc123", "web", "nginx:latest");
+    assert_eq!(series_key(&all), "host=local,container_id=abc123,container_name=web,image=nginx:latest");
+
+    // container_idを外すと同じ名前のコンテナは同じ系列になる
+    let without_id = ContainerLabels::new(&["container_name".to_string(), "image".to_string()])?;
+    assert_eq!(
+        without_id.labels("local", "abc123", "web", "nginx:latest"),
+        without_id.labels("local", "def456", "web", "nginx:latest"),
+    );
+
+    // hostは常に付与
+    let host_only = ContainerLabels::new(&[])?;
+    assert_eq!(series_key(&host_only.labels("local", "abc123", "web", "nginx:latest")), "host=local");
+
+    // 未知のラベルはエラー
+    assert!(ContainerLabels::new(&["pod".to_string()]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_limit_gauge_series() {
+    let mut limiter = SeriesLimiter::new(2);
+
+    // 同じラベルの系列は合計し、上限を超えた系列はoverflow系列に合計
+    let limited = limiter.limit("container_pids", "local", vec![
+        (labels("a"), 1),
+        (labels("b"), 2),
+        (labels("a"), 3),
+        (labels("c"), 4),
+        (labels("d"), 5),
+    ]);
+    assert_eq!(limited, vec![
+        (labels("a"), 4),
+        (labels("b"), 2),
+        (overflow_labels("local"), 9),
+    ]);
+    assert!(overflow_labels("local").contains(&KeyValue::new(OVERFLOW_LABEL, "true")));
+
+    // 受け入れ済みの系列が優先される
+    let limited = limiter.limit("container_pids", "local", vec![(labels("c"), 1), (labels("b"), 1)]);
+    assert_eq!(limited, vec![(labels("c"), 1), (labels("b"), 1)]);
+
+    // 報告されなくなった系列（a）の枠は解放されるが、cとbが埋めているためdは集約
+    let limited = limiter.limit("container_pids", "local", vec![(labels("d"), 1), (labels("c"), 1), (labels("b"), 1)]);
+    assert_eq!(limited, vec![(labels("c"), 1), (labels("b"), 1), (overflow_labels("local"), 1)]);
+
+    // bがなくなればdを受け入れる
+    let limited = limiter.limit("container_pids", "local", vec![(labels("d"), 1), (labels("c"), 1)]);
+    assert_eq!(limited, vec![(labels("d"), 1), (labels("c"), 1)]);
+}
+
+#[test]
+fn test_admit_counter_series() {
+    let mut limiter = SeriesLimiter::new(1);
+
+    assert!(limiter.admit("container_network_receive_bytes_total", &labels("a")));
+    assert!(limiter.admit("container_network_receive_bytes_total", &labels("a")));
+    assert!(!limiter.admit("container_network_receive_bytes_total", &labels("b")));
+
+    // 上限はメトリクスごと
+    assert!(limiter.admit("container_network_transmit_bytes_total", &labels("b")));
+}
@@ -0,0 +1,34 @@
+// 統合テストで共有するヘルパー（テストごとに使う関数が異なるため未使用の警告を抑制）
+#![allow(dead_code)]
+
+use std::net::TcpListener;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use container_monitoring::config::ServerConfig;
+use container_monitoring::server::start_metrics_server;
+use container_monitoring::stream;
+
+// 空いているポートを取得
+pub fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+// テスト用のメトリクスサーバーを起動し、接続可能になるまで待機
+pub async fn spawn_server(server_config: ServerConfig) -> Result<u16> {
+    let events = stream::channel();
+
+    let port = free_port();
+    tokio::spawn(async move {
+        start_metrics_server(events, port, &server_config).await.unwrap();
+    });
+
+    for _ in 0..50 {
+        if tokio::net::TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            return Ok(port);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    anyhow::bail!("metrics server did not start")
+}
@@ -42,16 +42,71 @@ fn test_load_valid_config() -> Result<()> {
 
     // 読み込んだ設定を検証
     assert_eq!(config.general.interval, 15);
+    assert!(config.general.intervals.containers.is_none());
+    assert!(!config.general.adaptive);
+    assert_eq!(config.general.max_interval_factor, 4);
+    assert_eq!(config.general.missed_tick_behavior, "skip");
     assert_eq!(config.telemetry.service_name, "test-service");
     assert_eq!(config.telemetry.otel_exporter, "otlp");
     assert_eq!(config.telemetry.otel_endpoint, "http://localhost:4317");
     assert_eq!(config.telemetry.prometheus_port, 8080);
+    assert_eq!(config.telemetry.otlp.protocol, "grpc");
+    assert!(config.telemetry.otlp.headers.is_empty());
+    assert!(config.telemetry.otlp.compression.is_none());
+    assert!(config.telemetry.otlp.tls.is_none());
+    assert_eq!(config.telemetry.otlp.export_interval, 10);
+    assert_eq!(config.telemetry.otlp.export_timeout, 10);
+    assert_eq!(config.telemetry.otlp.temporality, "cumulative");
+    assert!(config.telemetry.file_path.is_none());
+    assert_eq!(config.telemetry.sampler, "parentbased_always_on");
+    assert_eq!(config.telemetry.sampling_ratio, 1.0);
+    assert_eq!(config.telemetry.resource_detectors, vec!["host", "os", "process", "container"]);
     assert_eq!(config.docker.socket_path, "/var/run/docker.sock");
+    assert!(config.docker.host.is_none());
+    assert_eq!(config.docker.timeout, 120);
+    let hosts = config.docker.resolved_hosts();
+    assert_eq!(hosts.len(), 1);
+    assert_eq!(hosts[0].name, "local");
     assert!(config.metrics.enable_cpu);
     assert!(config.metrics.enable_memory);
     assert!(config.metrics.enable_network);
     assert!(config.metrics.enable_disk);
+    assert!(config.metrics.enable_inspect);
+    assert_eq!(config.metrics.inspect_interval, 60);
+    assert_eq!(config.metrics.sysfs_path, "/sys");
+    assert!(!config.metrics.enable_disk_usage);
+    assert_eq!(config.metrics.disk_usage_interval, 300);
+    assert!(!config.metrics.enable_host);
+    assert_eq!(config.metrics.procfs_path, "/proc");
+    assert!(config.metrics.enable_agent);
+    assert_eq!(config.metrics.top_processes, 0);
+    assert_eq!(config.metrics.cardinality.container_labels, vec!["container_id", "container_name", "image"]);
+    assert_eq!(config.metrics.cardinality.max_series_per_metric, 1000);
+    assert!(!config.metrics.kubernetes.enabled);
+    assert_eq!(config.metrics.kubernetes.source, "kubelet");
     assert_eq!(config.logging.level, "debug");
+    assert!(!config.container_logs.enabled);
+    assert!(!config.container_logs.otlp.enabled);
+    assert!(config.container_logs.otlp.endpoint.is_none());
+    assert!(config.container_logs.otlp.parse_json);
+    assert_eq!(config.container_logs.otlp.max_queue_size, 2048);
+
+    // [server]が省略された場合は認証なしで0.0.0.0にバインド
+    assert_eq!(config.server.bind_address, "0.0.0.0");
+    assert!(config.server.tls.is_none());
+    assert_eq!(config.server.auth.public_routes, vec!["health".to_string()]);
+
+    // シンクが省略された場合はOTLPとPrometheusのみ
+    assert!(config.telemetry.sinks.is_empty());
+
+    // [state]が省略された場合は状態ファイルを使わない
+    assert!(config.state.path.is_none());
+    assert_eq!(config.state.write, "cycle");
+
+    // [remote_write]が省略された場合はプッシュしない
+    assert!(!config.remote_write.enabled);
+    assert_eq!(config.remote_write.queue, "memory");
+    assert_eq!(config.remote_write.max_queue_batches, 240);
 
     Ok(())
 }
@@ -79,3 +134,178 @@ fn test_load_invalid_config() {
     let result = load_config(&config_path);
     assert!(result.is_err());
 }
+
+#[test]
+fn test_load_server_config() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let config_path = temp_dir.path().join("server_config.toml");
+
+    // TLSと認証を含む設定ファイルを作成
+    let config_content = r#"
+    [general]
+    interval = 15
+
+    [telemetry]
+    service_name = "test-service"
+    otel_exporter = "otlp"
+    otel_endpoint = "http://localhost:4317"
+    prometheus_port = 8443
+
+    [docker]
+    socket_path = "/var/run/docker.sock"
+
+    [metrics]
+    enable_cpu = true
+    enable_memory = true
+    enable_network = true
+    enable_disk = true
+
+    [logging]
+    level = "info"
+
+    [server]
+    bind_address = "127.0.0.1"
+
+    [server.tls]
+    cert_path = "/tls/server.pem"
+    key_path = "/tls/server.key"
+    client_ca_path = "/tls/ca.pem"
+
+    [server.auth]
+    bearer_token = "token"
+    public_routes = []
+    "#;
+
+    let mut file = File::create(&config_path)?;
+    file.write_all(config_content.as_bytes())?;
+
+    let config = load_config(&config_path)?;
+
+    assert_eq!(config.server.bind_address, "127.0.0.1");
+    let tls = config.server.tls.expect("tls section should be parsed");
+    assert_eq!(tls.cert_path, "/tls/server.pem");
+    assert_eq!(tls.client_ca_path.as_deref(), Some("/tls/ca.pem"));
+    assert_eq!(config.server.auth.bearer_token.as_deref(), Some("token"));
+    assert!(config.server.auth.basic.is_none());
+    assert!(config.server.auth.public_routes.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_load_multiple_docker_hosts() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let config_path = temp_dir.path().join("hosts_config.toml");
+
+    // 複数のDockerホストを含む設定ファイルを作成
+    let config_content = r#"
+    [general]
+    interval = 15
+
+    [telemetry]
+    service_name = "test-service"
+    otel_exporter = "otlp"
+    otel_endpoint = "http://localhost:4317"
+    prometheus_port = 8080
+
+    [docker]
+
+    [[docker.hosts]]
+    name = "build-1"
+    host = "tcp://build-1:2376"
+    timeout = 30
+
+    [docker.hosts.tls]
+    ca_path = "/certs/ca.pem"
+    cert_path = "/certs/cert.pem"
+    key_path = "/certs/key.pem"
+
+    [[docker.hosts]]
+    name = "build-2"
+    host = "ssh://monitor@build-2"
+
+    [metrics]
+    enable_cpu = true
+    enable_memory = true
+    enable_network = true
+    enable_disk = true
+
+    [logging]
+    level = "info"
+    "#;
+
+    let mut file = File::create(&config_path)?;
+    file.write_all(config_content.as_bytes())?;
+
+    let config = load_config(&config_path)?;
+    let hosts = config.docker.resolved_hosts();
+
+    assert_eq!(hosts.len(), 2);
+    assert_eq!(hosts[0].name, "build-1");
+    assert_eq!(hosts[0].connection.host.as_deref(), Some("tcp://build-1:2376"));
+    assert_eq!(hosts[0].connection.timeout, 30);
+    assert_eq!(hosts[0].connection.tls.as_ref().map(|tls| tls.ca_path.as_str()), Some("/certs/ca.pem"));
+    assert_eq!(hosts[1].name, "build-2");
+    assert_eq!(hosts[1].connection.timeout, 120);
+    assert!(hosts[1].connection.tls.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_load_telemetry_sinks() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let config_path = temp_dir.path().join("sinks_config.toml");
+
+    let config_content = r#"
+    [general]
+    interval = 15
+
+    [telemetry]
+    service_name = "test-service"
+    otel_exporter = "none"
+    otel_endpoint = "http://localhost:4317"
+    prometheus_port = 8080
+
+    [[telemetry.sinks]]
+    type = "dogstatsd"
+    address = "127.0.0.1:8125"
+    tags = ["env:test"]
+
+    [[telemetry.sinks]]
+    type = "influxdb"
+    path = "/tmp/metrics.lp"
+    prefix = "cm_"
+    interval = 60
+
+    [docker]
+    socket_path = "/var/run/docker.sock"
+
+    [metrics]
+    enable_cpu = true
+    enable_memory = true
+    enable_network = true
+    enable_disk = true
+
+    [logging]
+    level = "info"
+    "#;
+
+    let mut file = File::create(&config_path)?;
+    file.write_all(config_content.as_bytes())?;
+
+    let config = load_config(&config_path)?;
+
+    let sinks = &config.telemetry.sinks;
+    assert_eq!(sinks.len(), 2);
+    assert_eq!(sinks[0].kind, "dogstatsd");
+    assert_eq!(sinks[0].address.as_deref(), Some("127.0.0.1:8125"));
+    assert_eq!(sinks[0].tags, vec!["env:test".to_string()]);
+    assert_eq!(sinks[0].interval, 10);
+    assert_eq!(sinks[1].kind, "influxdb");
+    assert_eq!(sinks[1].path.as_deref(), Some("/tmp/metrics.lp"));
+    assert_eq!(sinks[1].prefix, "cm_");
+    assert_eq!(sinks[1].interval, 60);
+
+    Ok(())
+}
@@ -0,0 +1,389 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa, KeyPair, PKCS_RSA_SHA256};
+use tempfile::tempdir;
+use warp::Filter;
+
+use container_monitoring::config::{DockerConfig, DockerTlsConfig};
+use bollard::container::BlkioStats;
+
+use container_monitoring::docker::{block_device_name, BlockDeviceStats, ContainerInfo, DockerClient};
+
+use common::free_port;
+
+// Docker APIの/containers/{id}/statsレスポンスの最小構成
+fn stats_json(id: &str) -> serde_json::Value {
+    let cpu = serde_json::json!({
+        "cpu_usage": { "total_usage": 200, "usage_in_usermode": 100, "usage_in_kernelmode": 100 },
+        "system_cpu_usage": 2000,
+        "online_cpus": 2,
+        "throttling_data": { "periods": 0, "throttled_periods": 0, "throttled_time": 0 }
+    });
+
+    serde_json::json!({
+        "id": id,
+        "name": format!("/{}", id),
+        "read": "2024-01-01T00:00:01Z",
+        "preread": "2024-01-01T00:00:00Z",
+        "num_procs": 0,
+        "pids_stats": { "current": 3 },
+        "memory_stats": { "usage": 1048576, "limit": 4194304 },
+        "blkio_stats": {},
+        "cpu_stats": cpu,
+        "precpu_stats": {
+            "cpu_usage": { "total_usage": 100, "usage_in_usermode": 50, "usage_in_kernelmode": 50 },
+            "system_cpu_usage": 1000,
+            "online_cpus": 2,
+            "throttling_data": { "periods": 0, "throttled_periods": 0, "throttled_time": 0 }
+        },
+        "storage_stats": {}
+    })
+}
+
+// Docker APIの/containers/{id}/jsonレスポンスの最小構成
+fn inspect_json(id: &str) -> serde_json::Value {
+    serde_json::json!({
+        "Id": id,
+        "RestartCount": 3,
+        "State": {
+            "Status": "running",
+            "Running": true,
+            "OOMKilled": true,
+            "ExitCode": 137,
+            "StartedAt": "2024-01-01T00:00:00.123456789Z",
+            "Health": { "Status": "unhealthy", "FailingStreak": 2, "Log": [] }
+        }
+    })
+}
+
+// 平文HTTPでDocker APIのstats、inspect、topを返すスタブを起動（"slow"で始まるコンテナは応答が遅い）
+async fn spawn_stats_stub() -> u16 {
+    let stats = warp::path::full()
+        .and_then(|path: warp::path::FullPath| async move {
+            if path.as_str().ends_with("/top") {
+                return Ok(warp::reply::json(&serde_json::json!({
+                    "Titles": ["PID", "%CPU", "TIME", "RSS", "COMMAND"],
+                    "Processes": [
+                        ["4242", "12.5", "00:01:05", "20480", "gunicorn: worker"],
+                        ["4243", "0.1", "1-02:00:00", "1024", "sh"]
+                    ]
+                })));
+            }
+            if let Some(prefix) = path.as_str().strip_suffix("/json") {
+                let id = prefix.rsplit('/').next().unwrap_or_default().to_string();
+                return Ok(warp::reply::json(&inspect_json(&id)));
+            }
+            let Some(prefix) = path.as_str().strip_suffix("/stats") else {
+                return Err(warp::reject::not_found());
+            };
+            let id = prefix.rsplit('/').next().unwrap_or_default().to_string();
+            if id.starts_with("slow") {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+            Ok(warp::reply::json(&stats_json(&id)))
+        });
+
+    let port = free_port();
+    tokio::spawn(warp::serve(stats).run(([127, 0, 0, 1], port)));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    port
+}
+
+#[tokio::test]
+async fn test_connect_over_tcp_with_tls_client_certificate() -> Result<()> {
+    let dir = tempdir()?;
+    let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+
+    // CA、デーモン用のサーバー証明書、クライアント証明書を生成
+    let mut ca_params = CertificateParams::new(vec![]);
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params.distinguished_name.push(DnType::CommonName, "docker test CA");
+    let ca = Certificate::from_params(ca_params)?;
+
+    let server = Certificate::from_params(CertificateParams::new(vec!["localhost".to_string()]))?;
+
+    // bollardはRSAのクライアント鍵のみをサポート
+    let rsa_key = PKey::from_rsa(Rsa::generate(2048)?)?;
+    let rsa_pem = String::from_utf8(rsa_key.private_key_to_pem_pkcs8()?)?;
+    let mut client_params = CertificateParams::new(vec!["client".to_string()]);
+    client_params.alg = &PKCS_RSA_SHA256;
+    client_params.key_pair = Some(KeyPair::from_pem(&rsa_pem)?);
+    let client = Certificate::from_params(client_params)?;
+
+    std::fs::write(path("ca.pem"), ca.serialize_pem()?)?;
+    std::fs::write(path("server.pem"), server.serialize_pem_with_signer(&ca)?)?;
+    std::fs::write(path("server-key.pem"), server.serialize_private_key_pem())?;
+    std::fs::write(path("cert.pem"), client.serialize_pem_with_signer(&ca)?)?;
+    std::fs::write(path("key.pem"), rsa_pem)?;
+
+    // Docker APIのサブセットを返すHTTPSスタブ（mTLS必須、バージョン接頭辞の有無を問わない）
+    let containers = warp::path::full()
+        .and_then(|path: warp::path::FullPath| async move {
+            if path.as_str().ends_with("/containers/json") {
+                Ok(warp::reply::json(&serde_json::json!([{
+                    "Id": "abc123",
+                    "Names": ["/remote-web"],
+                    "Image": "nginx:1.25",
+                    "State": "running"
+                }])))
+            } else {
+                Err(warp::reject::not_found())
+            }
+        });
+
+    let port = free_port();
+    let server = warp::serve(containers)
+        .tls()
+        .cert_path(path("server.pem"))
+        .key_path(path("server-key.pem"))
+        .client_auth_required_path(path("ca.pem"));
+    tokio::spawn(server.run(([127, 0, 0, 1], port)));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let docker_client = DockerClient::new(&DockerConfig {
+        host: Some(format!("tcp://localhost:{}", port)),
+        tls: Some(DockerTlsConfig {
+            ca_path: path("ca.pem"),
+            cert_path: path("cert.pem"),
+            key_path: path("key.pem"),
+        }),
+        timeout: 5,
+        api_version: Some("1.41".to_string()),
+        ..Default::default()
+    })?;
+
+    let containers = docker_client.list_containers().await?;
+    assert_eq!(containers.len(), 1);
+    assert_eq!(containers[0].id, "abc123");
+    assert_eq!(containers[0].name, "remote-web");
+    assert_eq!(containers[0].status, "running");
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_api_version_is_rejected() {
+    let result = DockerClient::new(&DockerConfig {
+        api_version: Some("latest".to_string()),
+        ..Default::default()
+    });
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_slow_containers_are_reported_as_stale() -> Result<()> {
+    let port = spawn_stats_stub().await;
+    let docker_client = DockerClient::new(&DockerConfig {
+        host: Some(format!("tcp://127.0.0.1:{}", port)),
+        ..Default::default()
+    })?;
+
+    let mut containers: Vec<ContainerInfo> = ["fast-1", "fast-2", "slow-1", "fast-3"]
+        .iter()
+        .map(|id| ContainerInfo {
+            id: id.to_string(),
+            name: id.to_string(),
+            status: "running".to_string(),
+            ..Default::default()
+        })
+        .collect();
+
+    // 遅いコンテナはサイクル全体を止めずにタイムアウトする
+    let started = Instant::now();
+    docker_client
+        .collect_container_stats(&mut containers, 2, Duration::from_millis(500))
+        .await?;
+    assert!(started.elapsed() < Duration::from_secs(3));
+
+    for container in &containers {
+        if container.id.starts_with("slow") {
+            assert!(container.stale);
+            assert!(container.stats.is_none());
+        } else {
+            assert!(!container.stale);
+            let stats = container.stats.as_ref().expect("stats should be collected");
+            assert_eq!(stats.memory_usage_bytes, 1048576);
+            assert_eq!(stats.pids, 3);
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_inspect_container_details() -> Result<()> {
+    let port = spawn_stats_stub().await;
+    let docker_client = DockerClient::new(&DockerConfig {
+        host: Some(format!("tcp://127.0.0.1:{}", port)),
+        ..Default::default()
+    })?;
+
+    let details = docker_client.inspect_container("web-1").await?;
+    assert_eq!(details.health_status.as_deref(), Some("unhealthy"));
+    assert_eq!(details.failing_streak, 2);
+    assert_eq!(details.restart_count, 3);
+    assert_eq!(details.started_at, Some(1704067200));
+    assert_eq!(details.exit_code, 137);
+    assert!(details.oom_killed);
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_block_io_cgroup_v1() -> Result<()> {
+    let blkio: BlkioStats = serde_json::from_value(serde_json::json!({
+        "io_service_bytes_recursive": [
+            { "major": 8, "minor": 0, "op": "Read", "value": 4096 },
+            { "major": 8, "minor": 0, "op": "Write", "value": 8192 },
+            { "major": 8, "minor": 0, "op": "Discard", "value": 512 },
+            { "major": 8, "minor": 0, "op": "Sync", "value": 12288 },
+            { "major": 8, "minor": 0, "op": "Total", "value": 12800 },
+            { "major": 8, "minor": 16, "op": "Read", "value": 100 }
+        ],
+        "io_serviced_recursive": [
+            { "major": 8, "minor": 0, "op": "Read", "value": 2 },
+            { "major": 8, "minor": 0, "op": "Write", "value": 3 },
+            { "major": 8, "minor": 0, "op": "Total", "value": 5 }
+        ]
+    }))?;
+
+    let devices = DockerClient::parse_block_io(&blkio);
+    assert_eq!(devices, vec![
+        BlockDeviceStats {
+            major: 8, minor: 0,
+            read_bytes: 4096, write_bytes: 8192, discard_bytes: 512,
+            read_ops: 2, write_ops: 3, discard_ops: 0,
+        },
+        BlockDeviceStats { major: 8, minor: 16, read_bytes: 100, ..Default::default() },
+    ]);
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_block_io_cgroup_v2() -> Result<()> {
+    // cgroup v2ではio.statの値が小文字の操作名で報告される
+    let blkio: BlkioStats = serde_json::from_value(serde_json::json!({
+        "io_service_bytes_recursive": [
+            { "major": 259, "minor": 0, "op": "read", "value": 1048576 },
+            { "major": 259, "minor": 0, "op": "write", "value": 2097152 }
+        ],
+        "io_serviced_recursive": [
+            { "major": 259, "minor": 0, "op": "read", "value": 10 },
+            { "major": 259, "minor": 0, "op": "write", "value": 20 }
+        ]
+    }))?;
+
+    let devices = DockerClient::parse_block_io(&blkio);
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].read_bytes, 1048576);
+    assert_eq!(devices[0].write_bytes, 2097152);
+    assert_eq!(devices[0].read_ops, 10);
+    assert_eq!(devices[0].write_ops, 20);
+
+    // 統計情報がない場合は空
+    assert!(DockerClient::parse_block_io(&serde_json::from_value(serde_json::json!({}))?).is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_block_device_name_from_sysfs() -> Result<()> {
+    let sysfs = tempdir()?;
+    let device_dir = sysfs.path().join("dev/block/259:0");
+    std::fs::create_dir_all(&device_dir)?;
+    std::fs::write(device_dir.join("uevent"), "MAJOR=259\nMINOR=0\nDEVNAME=nvme0n1\nDEVTYPE=disk\n")?;
+
+    assert_eq!(block_device_name(sysfs.path(), 259, 0).as_deref(), Some("nvme0n1"));
+    assert_eq!(block_device_name(sysfs.path(), 8, 0), None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_disk_usage() -> Result<()> {
+    // Docker APIの/system/dfのスタブ（localドライバー以外のボリュームはサイズが-1）
+    let df = warp::path::full()
+        .and_then(|path: warp::path::FullPath| async move {
+            if !path.as_str().ends_with("/system/df") {
+                return Err(warp::reject::not_found());
+            }
+            Ok(warp::reply::json(&serde_json::json!({
+                "LayersSize": 1092588,
+                "Containers": [{
+                    "Id": "abc123",
+                    "Names": ["/ci-runner"],
+                    "Image": "runner:latest",
+                    "SizeRw": 52428800,
+                    "SizeRootFs": 157286400
+                }],
+                "Volumes": [
+                    {
+                        "Name": "build-cache",
+                        "Driver": "local",
+                        "Mountpoint": "/var/lib/docker/volumes/build-cache/_data",
+                        "Labels": {},
+                        "Scope": "local",
+                        "Options": {},
+                        "UsageData": { "Size": 1073741824, "RefCount": 2 }
+                    },
+                    {
+                        "Name": "nfs-share",
+                        "Driver": "nfs",
+                        "Mountpoint": "",
+                        "Labels": {},
+                        "Scope": "global",
+                        "Options": {},
+                        "UsageData": { "Size": -1, "RefCount": -1 }
+                    }
+                ]
+            })))
+        });
+
+    let port = free_port();
+    tokio::spawn(warp::serve(df).run(([127, 0, 0, 1], port)));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let docker_client = DockerClient::new(&DockerConfig {
+        host: Some(format!("tcp://127.0.0.1:{}", port)),
+        ..Default::default()
+    })?;
+
+    let usage = docker_client.disk_usage().await?;
+    assert_eq!(usage.containers.len(), 1);
+    assert_eq!(usage.containers[0].name, "ci-runner");
+    assert_eq!(usage.containers[0].writable_layer_bytes, 52428800);
+    assert_eq!(usage.containers[0].root_fs_bytes, 157286400);
+
+    assert_eq!(usage.volumes.len(), 1);
+    assert_eq!(usage.volumes[0].name, "build-cache");
+    assert_eq!(usage.volumes[0].size_bytes, 1073741824);
+    assert_eq!(usage.volumes[0].ref_count, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_top_processes() -> Result<()> {
+    let port = spawn_stats_stub().await;
+    let docker_client = DockerClient::new(&DockerConfig {
+        host: Some(format!("tcp://127.0.0.1:{}", port)),
+        ..Default::default()
+    })?;
+
+    let processes = docker_client.top_processes("web-1").await?;
+    assert_eq!(processes.len(), 2);
+    assert_eq!(processes[0].pid, 4242);
+    assert_eq!(processes[0].command, "gunicorn: worker");
+    assert_eq!(processes[0].cpu_seconds, 65.0);
+    assert_eq!(processes[0].cpu_usage_percent, 12.5);
+    assert_eq!(processes[0].rss_bytes, 20480 * 1024);
+    assert_eq!(processes[1].cpu_seconds, 93600.0);
+
+    Ok(())
+}
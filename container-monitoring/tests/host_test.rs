@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use anyhow::Result;
+use tempfile::tempdir;
+
+use container_monitoring::host::{HostCollector, HostStats};
+
+// テスト用のprocfsを作成
+fn write_procfs(root: &Path) -> Result<()> {
+    std::fs::create_dir_all(root.join("net"))?;
+
+    std::fs::write(root.join("stat"), "\
+cpu  1000 20 300 5000 40 5 6 7 0 0
+cpu0 500 10 150 2500 20 2 3 3 0 0
+cpu1 500 10 150 2500 20 3 3 4 0 0
+intr 12345
+ctxt 67890
+btime 1700000000
+")?;
+
+    std::fs::write(root.join("meminfo"), "\
+MemTotal:       16384000 kB
+MemFree:         2048000 kB
+MemAvailable:    8192000 kB
+Buffers:          512000 kB
+Cached:          4096000 kB
+SwapTotal:       1024000 kB
+SwapFree:        1000000 kB
+")?;
+
+    std::fs::write(root.join("loadavg"), "0.52 0.38 0.25 2/512 12345\n")?;
+
+    std::fs::write(root.join("net/dev"), "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:  123456     100    0    0    0     0          0         0   123456     100    0    0    0     0       0          0
+  eth0: 9876543    5000    0    0    0     0          0         0  1234567    3000    0    0    0     0       0          0
+")?;
+
+    std::fs::write(root.join("diskstats"), "\
+   7       0 loop0 10 0 80 1 0 0 0 0 0 1 1 0 0 0 0
+ 259       0 nvme0n1 1000 10 20000 500 2000 20 40000 800 0 900 1300 0 0 0 0
+")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_read_host_stats() -> Result<()> {
+    let procfs = tempdir()?;
+    write_procfs(procfs.path())?;
+
+    let stats = HostStats::read(procfs.path())?;
+
+    assert_eq!(stats.cpu_count, 2);
+    assert_eq!(stats.cpu.user, 1000);
+    assert_eq!(stats.cpu.idle, 5000);
+    assert_eq!(stats.cpu.steal, 7);
+
+    assert_eq!(stats.memory.total_bytes, 16384000 * 1024);
+    assert_eq!(stats.memory.available_bytes, 8192000 * 1024);
+    assert_eq!(stats.memory.swap_free_bytes, 1000000 * 1024);
+
+    assert_eq!(stats.load.load1, 0.52);
+    assert_eq!(stats.load.load15, 0.25);
+
+    assert_eq!(stats.networks.len(), 2);
+    assert_eq!(stats.networks[1].interface, "eth0");
+    assert_eq!(stats.networks[1].receive_bytes, 9876543);
+    assert_eq!(stats.networks[1].transmit_bytes, 1234567);
+
+    // loopデバイスは除外され、セクタ数はバイトに変換される
+    assert_eq!(stats.disks.len(), 1);
+    assert_eq!(stats.disks[0].device, "nvme0n1");
+    assert_eq!(stats.disks[0].reads_completed, 1000);
+    assert_eq!(stats.disks[0].read_bytes, 20000 * 512);
+    assert_eq!(stats.disks[0].writes_completed, 2000);
+    assert_eq!(stats.disks[0].written_bytes, 40000 * 512);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_collect_requires_procfs() -> Result<()> {
+    let procfs = tempdir()?;
+
+    // procfsが存在しない場合はエラー
+    let mut collector = HostCollector::new(procfs.path());
+    assert!(collector.collect().await.is_err());
+
+    write_procfs(procfs.path())?;
+    collector.collect().await?;
+
+    Ok(())
+}
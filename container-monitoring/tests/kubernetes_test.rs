@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use warp::Filter;
+
+use container_monitoring::config::KubernetesConfig;
+use container_monitoring::docker::ContainerInfo;
+use container_monitoring::kubernetes::{is_pause_container, parse_pods, pod_ref, workload_from_pod_name, PodResolver};
+
+// kubeletの/podsの応答（DeploymentとStatefulSetのポッド）
+const PODS: &str = r#"{
+  "kind": "PodList",
+  "items": [
+    {
+      "metadata": {
+        "name": "web-7d4b9c8f6d-x2k8q",
+        "namespace": "shop",
+        "uid": "uid-web",
+        "labels": {"app": "web", "pod-template-hash": "7d4b9c8f6d"},
+        "ownerReferences": [{"kind": "ReplicaSet", "name": "web-7d4b9c8f6d", "controller": true}]
+      },
+      "spec": {"nodeName": "node-1"}
+    },
+    {
+      "metadata": {
+        "name": "db-0",
+        "namespace": "shop",
+        "uid": "uid-db",
+        "ownerReferences": [{"kind": "StatefulSet", "name": "db", "controller": true}]
+      },
+      "spec": {"nodeName": "node-1"}
+    }
+  ]
+}"#;
+
+// kubeletが作成するコンテナ（pauseコンテナはcontainer名が"POD"）
+fn kubelet_container(container: &str, pod: &str, namespace: &str, uid: &str) -> ContainerInfo {
+    let mut labels = HashMap::from([
+        ("io.kubernetes.pod.name".to_string(), pod.to_string()),
+        ("io.kubernetes.pod.namespace".to_string(), namespace.to_string()),
+        ("io.kubernetes.pod.uid".to_string(), uid.to_string()),
+        ("io.kubernetes.container.name".to_string(), container.to_string()),
+    ]);
+    if container == "POD" {
+        labels.insert("io.kubernetes.docker.type".to_string(), "podsandbox".to_string());
+    }
+    ContainerInfo {
+        id: format!("{}-{}", pod, container),
+        name: format!("k8s_{}_{}_{}_{}_0", container, pod, namespace, uid),
+        labels,
+        ..Default::default()
+    }
+}
+
+// /podsを返すkubeletのスタブを起動（受信したAuthorizationヘッダーを記録）
+async fn spawn_kubelet(authorizations: Arc<Mutex<Vec<Option<String>>>>) -> SocketAddr {
+    let route = warp::get()
+        .and(warp::path!("pods"))
+        .and(warp::header::optional::<String>("authorization"))
+        .map(move |authorization: Option<String>| {
+            authorizations.lock().unwrap().push(authorization);
+            warp::reply::with_header(PODS, "content-type", "application/json")
+        });
+
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr
+}
+
+#[test]
+fn test_pod_ref_from_labels_and_name() {
+    let container = kubelet_container("app", "web-7d4b9c8f6d-x2k8q", "shop", "uid-web");
+    let pod = pod_ref(&container).unwrap();
+    assert_eq!(pod.pod, "web-7d4b9c8f6d-x2k8q");
+    assert_eq!(pod.namespace, "shop");
+    assert_eq!(pod.uid, "uid-web");
+    assert_eq!(pod.container, "app");
+
+    // ラベルがない場合はk8s_<コンテナ>_<ポッド>_<namespace>_<uid>_<試行回数>の名前から読み取る
+    let named = ContainerInfo {
+        name: "k8s_POD_db-0_shop_uid-db_1".to_string(),
+        ..Default::default()
+    };
+    let pod = pod_ref(&named).unwrap();
+    assert_eq!((pod.pod.as_str(), pod.namespace.as_str(), pod.uid.as_str()), ("db-0", "shop", "uid-db"));
+    assert!(is_pause_container(&named));
+
+    assert!(is_pause_container(&kubelet_container("POD", "db-0", "shop", "uid-db")));
+    assert!(!is_pause_container(&container));
+
+    // Kubernetes以外のコンテナ
+    let plain = ContainerInfo {
+        name: "web_1".to_string(),
+        ..Default::default()
+    };
+    assert!(pod_ref(&plain).is_none());
+    assert!(!is_pause_container(&plain));
+}
+
+#[test]
+fn test_workload_from_pod_name() {
+    let deployment = HashMap::from([("pod-template-hash".to_string(), "7d4b9c8f6d".to_string())]);
+    assert_eq!(workload_from_pod_name("web-7d4b9c8f6d-x2k8q", &deployment).as_deref(), Some("web"));
+
+    let controller = HashMap::from([("controller-revision-hash".to_string(), "db-5f6c".to_string())]);
+    assert_eq!(workload_from_pod_name("db-0", &controller).as_deref(), Some("db"));
+    assert_eq!(workload_from_pod_name("fluentd-abcde", &controller).as_deref(), Some("fluentd"));
+
+    assert_eq!(workload_from_pod_name("debug", &HashMap::new()), None);
+}
+
+#[test]
+fn test_parse_kubelet_pods() -> Result<()> {
+    let pods = parse_pods(PODS.as_bytes())?;
+
+    // ReplicaSetが管理するポッドはDeployment名をworkloadとする
+    let web = &pods["uid-web"];
+    assert_eq!(web.name, "web-7d4b9c8f6d-x2k8q");
+    assert_eq!(web.namespace, "shop");
+    assert_eq!(web.workload.as_deref(), Some("web"));
+    assert_eq!(web.node.as_deref(), Some("node-1"));
+
+    assert_eq!(pods["uid-db"].workload.as_deref(), Some("db"));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_enrich_from_kubelet() -> Result<()> {
+    let authorizations = Arc::new(Mutex::new(Vec::new()));
+    let addr = spawn_kubelet(authorizations.clone()).await;
+
+    let mut token = tempfile::NamedTempFile::new()?;
+    writeln!(token, "kubelet-token")?;
+
+    let config = KubernetesConfig {
+        enabled: true,
+        kubelet_url: format!("http://{}", addr),
+        token_path: Some(token.path().to_string_lossy().into_owned()),
+        ..KubernetesConfig::default()
+    };
+    let mut resolver = PodResolver::new(&config)?;
+
+    let mut containers = vec![
+        kubelet_container("POD", "web-7d4b9c8f6d-x2k8q", "shop", "uid-web"),
+        kubelet_container("app", "web-7d4b9c8f6d-x2k8q", "shop", "uid-web"),
+        kubelet_container("app", "api-0", "shop", "uid-unknown"),
+        ContainerInfo {
+            id: "plain".to_string(),
+            name: "redis".to_string(),
+            ..Default::default()
+        },
+    ];
+    resolver.enrich(&mut containers).await;
+
+    // pauseコンテナは除外される
+    assert_eq!(containers.len(), 3);
+    assert!(containers.iter().all(|c| !is_pause_container(c)));
+
+    let web = containers[0].pod.as_ref().unwrap();
+    assert_eq!(web.name, "web-7d4b9c8f6d-x2k8q");
+    assert_eq!(web.workload.as_deref(), Some("web"));
+    assert_eq!(web.node.as_deref(), Some("node-1"));
+
+    // kubeletが知らないポッドはコンテナのラベルから付与する
+    let unknown = containers[1].pod.as_ref().unwrap();
+    assert_eq!((unknown.name.as_str(), unknown.namespace.as_str()), ("api-0", "shop"));
+    assert!(unknown.node.is_none());
+
+    assert!(containers[2].pod.is_none());
+
+    // トークンファイルの内容をBearerトークンとして送信
+    assert_eq!(
+        authorizations.lock().unwrap().first().cloned().flatten().as_deref(),
+        Some("Bearer kubelet-token")
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_deleted_pod_does_not_keep_refreshing() -> Result<()> {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let addr = spawn_kubelet(requests.clone()).await;
+
+    let config = KubernetesConfig {
+        enabled: true,
+        kubelet_url: format!("http://{}", addr),
+        ..KubernetesConfig::default()
+    };
+    let mut resolver = PodResolver::new(&config)?;
+
+    // 削除されたポッドの終了したコンテナは`docker ps -a`に残り続ける
+    let mut containers = vec![kubelet_container("app", "job-x7k2p", "batch", "uid-deleted")];
+    resolver.enrich(&mut containers).await;
+    assert_eq!(requests.lock().unwrap().len(), 1);
+
+    // 最短の更新間隔が経過しても、更新後も一覧になかったポッドでは再取得しない
+    tokio::time::sleep(Duration::from_millis(5100)).await;
+    resolver.enrich(&mut containers).await;
+    assert_eq!(requests.lock().unwrap().len(), 1);
+
+    // 新しい未知のポッドは再取得する
+    containers.push(kubelet_container("app", "api-1", "shop", "uid-new"));
+    resolver.enrich(&mut containers).await;
+    assert_eq!(requests.lock().unwrap().len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_enrich_from_labels_without_kubelet() -> Result<()> {
+    let config = KubernetesConfig {
+        enabled: true,
+        source: "labels".to_string(),
+        node_name: Some("node-2".to_string()),
+        ..KubernetesConfig::default()
+    };
+    let mut resolver = PodResolver::new(&config)?;
+
+    // pauseコンテナのポッドラベル（pod-template-hash）からDeployment名を推定
+    let mut sandbox = kubelet_container("POD", "web-7d4b9c8f6d-x2k8q", "shop", "uid-web");
+    sandbox.labels.insert("pod-template-hash".to_string(), "7d4b9c8f6d".to_string());
+    let mut containers = vec![sandbox, kubelet_container("app", "web-7d4b9c8f6d-x2k8q", "shop", "uid-web")];
+    resolver.enrich(&mut containers).await;
+
+    assert_eq!(containers.len(), 1);
+    let pod = containers[0].pod.as_ref().unwrap();
+    assert_eq!(pod.workload.as_deref(), Some("web"));
+    assert_eq!(pod.node.as_deref(), Some("node-2"));
+
+    Ok(())
+}
+
+#[test]
+fn test_unknown_kubernetes_source_is_rejected() {
+    let config = KubernetesConfig {
+        enabled: true,
+        source: "apiserver".to_string(),
+        ..KubernetesConfig::default()
+    };
+    assert!(PodResolver::new(&config).is_err());
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use opentelemetry::logs::{AnyValue, LogResult, Severity};
+use opentelemetry::{Key, Value};
+use opentelemetry_sdk::export::logs::{LogData, LogExporter};
+use opentelemetry_sdk::Resource;
+
+use container_monitoring::config::{LogExportConfig, LogPatternConfig};
+use container_monitoring::docker::{ContainerInfo, LogStream};
+use container_monitoring::log_export::{log_record, LogForwarder};
+use container_monitoring::logs::{LineBuffer, LogPatterns};
+
+// テスト用のパターン設定を作成
+fn pattern(name: &str, regex: &str) -> LogPatternConfig {
+    LogPatternConfig {
+        name: name.to_string(),
+        regex: regex.to_string(),
+    }
+}
+
+#[test]
+fn test_line_buffer_joins_partial_chunks() {
+    let mut buffer = LineBuffer::new(1024);
+
+    assert_eq!(buffer.push(b"first line\nsecond "), vec!["first line"]);
+    assert!(buffer.push(b"half").is_empty());
+    assert_eq!(buffer.push(b" done\r\nthird\n"), vec!["second half done", "third"]);
+}
+
+#[test]
+fn test_line_buffer_splits_long_output() {
+    let mut buffer = LineBuffer::new(4);
+
+    // 改行のない出力は最大サイズで区切られる
+    assert_eq!(buffer.push(b"abcdefghij"), vec!["abcd", "efgh"]);
+    assert_eq!(buffer.push(b"\n"), vec!["ij"]);
+}
+
+#[test]
+fn test_log_patterns() -> anyhow::Result<()> {
+    let patterns = LogPatterns::new(&[
+        pattern("error", "ERROR|panic"),
+        pattern("timeout", "(?i)timed? ?out"),
+    ])?;
+
+    let matches: Vec<&str> = patterns.matching("ERROR request timed out").collect();
+    assert_eq!(matches, vec!["error", "timeout"]);
+    assert_eq!(patterns.matching("thread 'main' panicked").collect::<Vec<_>>(), vec!["error"]);
+    assert_eq!(patterns.matching("GET /health 200").count(), 0);
+
+    // 不正な正規表現はエラー
+    assert!(LogPatterns::new(&[pattern("broken", "(unclosed")]).is_err());
+
+    Ok(())
+}
+
+// 送信されたレコードを保持するテスト用エクスポーター
+#[derive(Debug, Clone, Default)]
+struct CapturingExporter {
+    exported: Arc<Mutex<Vec<LogData>>>,
+}
+
+#[async_trait]
+impl LogExporter for CapturingExporter {
+    async fn export(&mut self, batch: Vec<LogData>) -> LogResult<()> {
+        self.exported.lock().unwrap().extend(batch);
+        Ok(())
+    }
+}
+
+// レコードの属性を取得
+fn attribute<'a>(attributes: &'a [(Key, AnyValue)], key: &str) -> Option<&'a AnyValue> {
+    attributes.iter().find(|(k, _)| k.as_str() == key).map(|(_, value)| value)
+}
+
+#[test]
+fn test_log_record_parses_json_lines() {
+    let line = r#"{"level":"warn","msg":"disk almost full","usage":93,"ratio":0.93,"tags":["disk"],"ctx":{"mount":"/data"},"trace":null}"#;
+    let record = log_record("abc123", LogStream::Stderr, line, true);
+
+    assert!(matches!(&record.body, Some(AnyValue::String(body)) if body.as_str() == "disk almost full"));
+    assert_eq!(record.severity_number, Some(Severity::Warn));
+    assert_eq!(record.severity_text.as_deref(), Some("warn"));
+
+    let attributes = record.attributes.unwrap();
+    assert!(matches!(attribute(&attributes, "container.id"), Some(AnyValue::String(id)) if id.as_str() == "abc123"));
+    assert!(matches!(attribute(&attributes, "log.iostream"), Some(AnyValue::String(stream)) if stream.as_str() == "stderr"));
+    assert!(matches!(attribute(&attributes, "usage"), Some(AnyValue::Int(93))));
+    assert!(matches!(attribute(&attributes, "ratio"), Some(AnyValue::Double(ratio)) if *ratio == 0.93));
+    assert!(matches!(attribute(&attributes, "tags"), Some(AnyValue::ListAny(values)) if values.len() == 1));
+    assert!(matches!(attribute(&attributes, "ctx"), Some(AnyValue::Map(fields)) if fields.len() == 1));
+    // 本文に使ったフィールドとnullは属性に含めない
+    assert!(attribute(&attributes, "msg").is_none());
+    assert!(attribute(&attributes, "trace").is_none());
+
+    // JSONでない行や解析が無効な場合は行全体が本文
+    let record = log_record("abc123", LogStream::Stdout, "plain text", true);
+    assert!(matches!(&record.body, Some(AnyValue::String(body)) if body.as_str() == "plain text"));
+    assert!(record.severity_number.is_none());
+
+    let record = log_record("abc123", LogStream::Stdout, line, false);
+    assert!(matches!(&record.body, Some(AnyValue::String(body)) if body.as_str() == line));
+    assert_eq!(record.attributes.unwrap().len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_forwarder_attaches_container_resource() {
+    let exporter = CapturingExporter::default();
+    let exported = exporter.exported.clone();
+    let config = LogExportConfig {
+        enabled: true,
+        ..Default::default()
+    };
+    let forwarder = LogForwarder::with_exporter(
+        exporter,
+        &config,
+        Resource::new(vec![opentelemetry::KeyValue::new("service.name", "test-service")]),
+    );
+
+    let container = ContainerInfo {
+        id: "abc123".to_string(),
+        name: "web-1".to_string(),
+        image: "nginx:latest".to_string(),
+        status: "running".to_string(),
+        stats: None,
+        stale: false,
+        details: None,
+        processes: Vec::new(),
+        labels: HashMap::from([
+            ("com.docker.compose.project".to_string(), "shop".to_string()),
+            ("com.docker.compose.service".to_string(), "web".to_string()),
+            ("maintainer".to_string(), "someone".to_string()),
+        ]),
+        pod: None,
+    };
+    forwarder.register_container("local", &container);
+    forwarder.emit("abc123", LogStream::Stdout, "GET / 200");
+    forwarder.emit("unknown", LogStream::Stdout, "no resource");
+
+    let flushing = forwarder.clone();
+    tokio::task::spawn_blocking(move || flushing.flush()).await.unwrap();
+
+    let exported = exported.lock().unwrap();
+    assert_eq!(exported.len(), 2);
+
+    let resource = &exported[0].resource;
+    assert_eq!(resource.get(Key::new("service.name")), Some(Value::from("test-service")));
+    assert_eq!(resource.get(Key::new("container.id")), Some(Value::from("abc123")));
+    assert_eq!(resource.get(Key::new("container.name")), Some(Value::from("web-1")));
+    assert_eq!(resource.get(Key::new("container.image.name")), Some(Value::from("nginx:latest")));
+    assert_eq!(resource.get(Key::new("com.docker.compose.service")), Some(Value::from("web")));
+    assert!(resource.get(Key::new("maintainer")).is_none());
+
+    // 登録されていないコンテナのレコードは基本のリソースのみ
+    assert!(exported[1].resource.get(Key::new("container.id")).is_none());
+}
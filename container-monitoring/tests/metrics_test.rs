@@ -1,104 +1,81 @@
-use std::collections::HashMap;
-use std::sync::Arc;
-
-use anyhow::Result;
-use mockall::predicate::*;
-use mockall::mock;
-use tokio::sync::Mutex;
-
-use container_monitoring::docker::{ContainerInfo, ContainerStats, DockerClient};
-use container_monitoring::metrics::MetricsCollector;
-use container_monitoring::config::MetricsConfig;
-
-// DockerClientのモック作成
-mock! {
-    pub DockerClientMock {}
-    impl Clone for DockerClientMock {
-        fn clone(&self) -> Self;
-    }
-    #[async_trait::async_trait]
-    impl DockerClient for DockerClientMock {
-        fn new(_config: &container_monitoring::config::DockerConfig) -> Result<Self>;
-        async fn list_containers(&self) -> Result<Vec<ContainerInfo>>;
-        async fn collect_container_stats(&self, containers: &mut [ContainerInfo]) -> Result<()>;
-    }
+use container_monitoring::metrics::DeltaTracker;
+
+// エージェントの起動時刻（UNIX秒）
+const AGENT_STARTED_AT: i64 = 1_700_000_000;
+
+#[test]
+fn test_delta_tracker_first_observation() {
+    let mut tracker = DeltaTracker::new();
+
+    // エージェントより前から動いているコンテナは基準値として0
+    assert_eq!(tracker.delta("container1/network_rx", Some(AGENT_STARTED_AT - 3600), 1000), 0);
+    assert_eq!(tracker.delta("container1/network_rx", Some(AGENT_STARTED_AT - 3600), 1200), 200);
+
+    // 開始時刻が分からない場合も基準値として0
+    assert_eq!(tracker.delta("container2/network_rx", None, 1000), 0);
+
+    // エージェントの起動後に開始したコンテナも基準値として0
+    assert_eq!(tracker.delta("container3/network_rx", Some(AGENT_STARTED_AT + 10), 1000), 0);
+    assert_eq!(tracker.delta("container3/network_rx", Some(AGENT_STARTED_AT + 10), 1100), 100);
 }
 
-#[tokio::test]
-async fn test_metrics_collector_calculate_delta() -> Result<()> {
-    // テスト用の設定
-    let config = MetricsConfig {
-        enable_cpu: true,
-        enable_memory: true,
-        enable_network: true,
-        enable_disk: true,
-    };
-
-    // DockerClientのモックを作成
-    let mut mock_docker = MockDockerClientMock::new();
-    
-    // list_containersの振る舞いを設定
-    mock_docker
-        .expect_list_containers()
-        .returning(|| {
-            Ok(vec![
-                ContainerInfo {
-                    id: "container1".to_string(),
-                    name: "test_container".to_string(),
-                    image: "test_image".to_string(),
-                    status: "running".to_string(),
-                    stats: None,
-                },
-            ])
-        });
-    
-    // collect_container_statsの振る舞いを設定
-    mock_docker
-        .expect_collect_container_stats()
-        .returning(|containers| {
-            // コンテナにダミーの統計情報を設定
-            for container in containers {
-                container.stats = Some(ContainerStats {
-                    cpu_usage_percent: 10.0,
-                    memory_usage_bytes: 1024 * 1024, // 1MB
-                    memory_limit_bytes: 1024 * 1024 * 10, // 10MB
-                    memory_usage_percent: 10.0,
-                    network_rx_bytes: 1000,
-                    network_tx_bytes: 500,
-                    block_read_bytes: 2000,
-                    block_write_bytes: 1000,
-                    pids: 5,
-                });
-            }
-            Ok(())
-        });
-
-    // MetricsCollectorを作成
-    let mut collector = MetricsCollector::new(mock_docker, &config)?;
-    
-    // プライベートのcalculate_deltaメソッドをテストするためのヘルパー
-    let mut prev_values = HashMap::new();
-    prev_values.insert("container1".to_string(), 500u64);
-    
-    // 最初のデルタを計算（1000 - 500 = 500）
-    let delta = collector.calculate_delta(&mut prev_values, "container1", 1000);
-    assert_eq!(delta, 500);
-    assert_eq!(prev_values.get("container1"), Some(&1000));
-    
-    // 2回目のデルタを計算（1500 - 1000 = 500）
-    let delta = collector.calculate_delta(&mut prev_values, "container1", 1500);
-    assert_eq!(delta, 500);
-    assert_eq!(prev_values.get("container1"), Some(&1500));
-    
-    // カウンターがリセットされた場合（300 < 1500）
-    let delta = collector.calculate_delta(&mut prev_values, "container1", 300);
-    assert_eq!(delta, 300); // リセット時は現在値をそのまま返す
-    assert_eq!(prev_values.get("container1"), Some(&300));
-    
-    // 存在しないコンテナのデルタを計算
-    let delta = collector.calculate_delta(&mut prev_values, "non_existent", 100);
-    assert_eq!(delta, 100); // 初回は現在値をそのまま返す
-    assert_eq!(prev_values.get("non_existent"), Some(&100));
-    
-    Ok(())
+#[test]
+fn test_delta_tracker_increase_and_reset() {
+    let mut tracker = DeltaTracker::new();
+    let started_at = Some(AGENT_STARTED_AT - 3600);
+
+    tracker.delta("container1/network_rx", started_at, 500);
+
+    // 前回値との差分
+    assert_eq!(tracker.delta("container1/network_rx", started_at, 1000), 500);
+    assert_eq!(tracker.delta("container1/network_rx", started_at, 1500), 500);
+
+    // 開始時刻が変わらないまま値が減少した場合（カウンターリセット）は現在値
+    assert_eq!(tracker.delta("container1/network_rx", started_at, 300), 300);
+    assert_eq!(tracker.delta("container1/network_rx", started_at, 400), 100);
+}
+
+#[test]
+fn test_delta_tracker_container_restart() {
+    let mut tracker = DeltaTracker::new();
+
+    tracker.delta("container1/network_rx", Some(AGENT_STARTED_AT - 3600), 5000);
+
+    // 同じコンテナIDで再起動した場合は、前回値より大きくても新しい実行の値全体
+    assert_eq!(tracker.delta("container1/network_rx", Some(AGENT_STARTED_AT + 60), 8000), 8000);
+    assert_eq!(tracker.delta("container1/network_rx", Some(AGENT_STARTED_AT + 60), 8500), 500);
+
+    // inspectの結果がない周期は開始時刻を比較せず差分を計算
+    assert_eq!(tracker.delta("container1/network_rx", None, 9000), 500);
+}
+
+#[test]
+fn test_delta_tracker_retain() {
+    let mut tracker = DeltaTracker::new();
+    tracker.delta("container1/network_rx", None, 100);
+    tracker.delta("container1/8:0/read/bytes", None, 100);
+    tracker.delta("container2/network_rx", None, 100);
+
+    let removed = tracker.retain(|key| key.starts_with("container1/"));
+    assert_eq!(removed, 1);
+
+    // 残ったキーは差分、削除したキーは再び初回の観測として扱う
+    assert_eq!(tracker.delta("container1/8:0/read/bytes", None, 150), 50);
+    assert_eq!(tracker.delta("container2/network_rx", None, 150), 0);
+}
+
+#[test]
+fn test_delta_tracker_restore_baselines() {
+    let started_at = Some(AGENT_STARTED_AT - 3600);
+    let mut tracker = DeltaTracker::new();
+    tracker.delta("container1/network_rx", started_at, 1000);
+    tracker.delta("container2/network_rx", started_at, 500);
+
+    // 再起動後のトラッカーに前回値を復元すると、初回の観測でも前回値からの差分になる
+    let mut restarted = DeltaTracker::new();
+    restarted.restore(tracker.baselines());
+    assert_eq!(restarted.delta("container1/network_rx", started_at, 1800), 800);
+
+    // エージェントの停止中に再起動したコンテナは新しい実行の値全体
+    assert_eq!(restarted.delta("container2/network_rx", Some(AGENT_STARTED_AT + 300), 200), 200);
 }
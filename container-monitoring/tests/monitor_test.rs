@@ -0,0 +1,648 @@
+use std::os::unix::fs::PermissionsExt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use opentelemetry::metrics::MeterProvider as _;
+use opentelemetry_sdk::Resource;
+use prometheus::Registry;
+use warp::Filter;
+
+use container_monitoring::config::{Config, DockerConfig, LogPatternConfig, SinkConfig, TelemetryConfig};
+use container_monitoring::metrics::CounterStartTimes;
+use container_monitoring::monitor::{Monitor, MonitorBuilder};
+use container_monitoring::telemetry::{build_meter_provider, build_meter_provider_with_start_times};
+
+// Docker APIの一覧・stats・inspectを返すスタブを起動（実行中の"web"とOOMで終了した"batch"）
+// "web"は2回目以降のinspectで、同じコンテナIDのまま再起動した状態を返す
+async fn spawn_docker_stub() -> u16 {
+    let web_inspections = Arc::new(AtomicUsize::new(0));
+    let api = warp::path::full().and_then(move |path: warp::path::FullPath| {
+        let web_inspections = web_inspections.clone();
+        async move {
+            let path = path.as_str();
+            if path.ends_with("/containers/json") {
+                return Ok(warp::reply::json(&serde_json::json!([{
+                    "Id": "web",
+                    "Names": ["/web"],
+                    "Image": "nginx:1.25",
+                    "State": "running",
+                    "Labels": { "app": "web" }
+                }, {
+                    "Id": "batch",
+                    "Names": ["/batch"],
+                    "Image": "busybox:1.36",
+                    "State": "exited",
+                    "Labels": {}
+                }])));
+            }
+            if path.ends_with("/batch/json") {
+                return Ok(warp::reply::json(&serde_json::json!({
+                    "Id": "batch",
+                    "RestartCount": 0,
+                    "State": { "Status": "exited", "Running": false, "ExitCode": 137, "OOMKilled": true }
+                })));
+            }
+            if path.ends_with("/web/json") {
+                let restarted = web_inspections.fetch_add(1, Ordering::SeqCst) > 0;
+                return Ok(warp::reply::json(&serde_json::json!({
+                    "Id": "web",
+                    "RestartCount": u32::from(restarted),
+                    "State": {
+                        "Status": "running",
+                        "Running": true,
+                        "StartedAt": if restarted { "2024-06-01T00:00:00Z" } else { "2024-01-01T00:00:00Z" }
+                    }
+                })));
+            }
+            if path.ends_with("/web/top") {
+                return Ok(warp::reply::json(&serde_json::json!({
+                    "Titles": ["PID", "%CPU", "TIME", "RSS", "COMMAND"],
+                    "Processes": [
+                        ["4711", "1.5", "00:00:03", "2048", "nginx"],
+                        ["4712", "12.0", "00:01:00", "8192", "php-fpm"]
+                    ]
+                })));
+            }
+            if path.ends_with("/web/stats") {
+                let cpu = |total: u64, system: u64| serde_json::json!({
+                    "cpu_usage": { "total_usage": total, "usage_in_usermode": total / 2, "usage_in_kernelmode": total / 2 },
+                    "system_cpu_usage": system,
+                    "online_cpus": 2,
+                    "throttling_data": { "periods": 0, "throttled_periods": 0, "throttled_time": 0 }
+                });
+                return Ok(warp::reply::json(&serde_json::json!({
+                    "id": "web",
+                    "name": "/web",
+                    "read": "2024-01-01T00:00:01Z",
+                    "preread": "2024-01-01T00:00:00Z",
+                    "num_procs": 0,
+                    "pids_stats": { "current": 3 },
+                    "memory_stats": { "usage": 1048576, "limit": 4194304 },
+                    "blkio_stats": {},
+                    "cpu_stats": cpu(200, 2000),
+                    "precpu_stats": cpu(100, 1000),
+                    "storage_stats": {}
+                })));
+            }
+            Err(warp::reject::not_found())
+        }
+    });
+
+    let (addr, server) = warp::serve(api).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr.port()
+}
+
+// スタブのDockerホストを1秒間隔で監視する設定を作成
+fn monitor_config(port: u16) -> Config {
+    let mut config = Config::default();
+    config.general.interval = 1;
+    config.docker = DockerConfig {
+        host: Some(format!("tcp://127.0.0.1:{}", port)),
+        ..DockerConfig::default()
+    };
+    config.metrics.enable_agent = false;
+    config
+}
+
+// 最初の収集サイクルが完了するまで待つ
+async fn wait_for_snapshot(monitor: &Monitor) {
+    for _ in 0..50 {
+        if !monitor.snapshot().hosts.is_empty() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("no collection cycle completed");
+}
+
+#[test]
+fn test_default_config_runs_without_exporters() {
+    let config = Config::default();
+    assert_eq!(config.general.interval, 15);
+    assert_eq!(config.telemetry.otel_exporter, "none");
+    assert!(config.telemetry.sinks.is_empty());
+    assert_eq!(config.logging.level, "info");
+    assert!(!config.remote_write.enabled);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_embedded_monitor_records_to_custom_meter() -> Result<()> {
+    let port = spawn_docker_stub().await;
+
+    // 呼び出し側のMeterProvider（Prometheusレジストリで確認）
+    let registry = Registry::new();
+    let provider = build_meter_provider(&TelemetryConfig::default(), Resource::default(), &registry)?;
+
+    let mut monitor = MonitorBuilder::new(monitor_config(port))
+        .with_meter(provider.meter("platform-agent"))
+        .build()?;
+    monitor.start().await?;
+    wait_for_snapshot(&monitor).await;
+
+    let snapshot = monitor.snapshot();
+    assert_eq!(snapshot.hosts.len(), 1);
+    assert_eq!(snapshot.hosts[0].host, "local");
+    let containers = &snapshot.hosts[0].containers;
+    assert_eq!(containers.len(), 2);
+    assert_eq!(containers[0].name, "web");
+    assert_eq!(containers[0].labels.get("app").map(String::as_str), Some("web"));
+
+    // 終了したコンテナもインスペクトし、終了コードとOOMを報告する
+    let details = containers[1].details.as_ref().expect("exited container should be inspected");
+    assert_eq!(details.exit_code, 137);
+    assert!(details.oom_killed);
+
+    // コンテナのメトリクスは指定したメーターに記録される
+    let names: Vec<String> = registry.gather().iter().map(|family| family.get_name().to_string()).collect();
+    assert!(names.iter().any(|name| name.starts_with("container_memory_usage_bytes")), "{:?}", names);
+
+    // 累計値のカウンターは既定でcontainer_idを付与しない
+    let counter_labels: Vec<String> = registry
+        .gather()
+        .iter()
+        .filter(|family| family.get_name().starts_with("container_network_receive_bytes"))
+        .flat_map(|family| family.get_metric())
+        .flat_map(|metric| metric.get_label())
+        .map(|label| label.get_name().to_string())
+        .collect();
+    assert!(counter_labels.contains(&"container_name".to_string()), "{:?}", counter_labels);
+    assert!(!counter_labels.contains(&"container_id".to_string()), "{:?}", counter_labels);
+
+    monitor.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_shutdown_saves_state() -> Result<()> {
+    let port = spawn_docker_stub().await;
+    let dir = tempfile::tempdir()?;
+    let state_path = dir.path().join("state.json");
+
+    let mut config = monitor_config(port);
+    config.state.path = Some(state_path.to_string_lossy().into_owned());
+
+    let registry = Registry::new();
+    let provider = build_meter_provider(&TelemetryConfig::default(), Resource::default(), &registry)?;
+    let mut monitor = MonitorBuilder::new(config).with_meter(provider.meter("platform-agent")).build()?;
+    monitor.start().await?;
+    wait_for_snapshot(&monitor).await;
+
+    // 二重に開始することはできない
+    assert!(monitor.start().await.is_err());
+
+    monitor.shutdown().await?;
+    let state = std::fs::read_to_string(&state_path)?;
+    assert!(state.contains("\"local\""), "{}", state);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_top_processes_are_labelled_by_rank() -> Result<()> {
+    let port = spawn_docker_stub().await;
+    let mut config = monitor_config(port);
+    config.metrics.top_processes = 2;
+
+    let registry = Registry::new();
+    let provider = build_meter_provider(&TelemetryConfig::default(), Resource::default(), &registry)?;
+    let mut monitor = MonitorBuilder::new(config).with_meter(provider.meter("platform-agent")).build()?;
+    monitor.start().await?;
+    wait_for_snapshot(&monitor).await;
+
+    // PIDはスナップショットにのみ含まれる
+    let snapshot = monitor.snapshot();
+    let pids: Vec<u64> = snapshot.hosts[0].containers[0].processes.iter().map(|process| process.pid).collect();
+    assert_eq!(pids, vec![4712, 4711]);
+
+    // メトリクスの系列はCPU使用率の順位とコマンド名で識別する
+    let series: Vec<(String, String)> = registry
+        .gather()
+        .iter()
+        .filter(|family| family.get_name().starts_with("container_process_cpu_usage_percent"))
+        .flat_map(|family| family.get_metric())
+        .map(|metric| {
+            let label = |name: &str| {
+                metric.get_label().iter().find(|label| label.get_name() == name).map(|label| label.get_value().to_string())
+            };
+            assert_eq!(label("pid"), None);
+            (label("rank").unwrap_or_default(), label("command").unwrap_or_default())
+        })
+        .collect();
+    assert!(series.contains(&("1".to_string(), "php-fpm".to_string())), "{:?}", series);
+    assert!(series.contains(&("2".to_string(), "nginx".to_string())), "{:?}", series);
+
+    monitor.shutdown().await?;
+    Ok(())
+}
+
+// ファイルエクスポーターの最後のレコードから、指定したメトリクスのデータポイントの開始時刻（UNIX秒）を取得
+fn start_times(contents: &str, name: &str) -> Vec<u64> {
+    fn find<'a>(value: &'a serde_json::Value, name: &str, found: &mut Vec<&'a serde_json::Value>) {
+        match value {
+            serde_json::Value::Object(object) if object.get("name").and_then(|n| n.as_str()) == Some(name) => found.push(value),
+            serde_json::Value::Object(object) => object.values().for_each(|value| find(value, name, found)),
+            serde_json::Value::Array(values) => values.iter().for_each(|value| find(value, name, found)),
+            _ => {}
+        }
+    }
+
+    let record: serde_json::Value = serde_json::from_str(contents.lines().last().unwrap_or("null")).unwrap();
+    let mut metrics = Vec::new();
+    find(&record, name, &mut metrics);
+    metrics
+        .iter()
+        .filter_map(|metric| metric.pointer("/sum/dataPoints").and_then(|points| points.as_array()))
+        .flatten()
+        .filter_map(|point| point.get("startTimeUnixNano").and_then(|nanos| nanos.as_u64()))
+        .map(|nanos| nanos / 1_000_000_000)
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_counter_start_time_follows_container_run() -> Result<()> {
+    let port = spawn_docker_stub().await;
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("metrics.jsonl");
+
+    // 毎サイクルinspectし、2回目のサイクルで"web"の再起動を検出する
+    let mut config = monitor_config(port);
+    config.metrics.inspect_interval = 0;
+
+    let telemetry = TelemetryConfig {
+        otel_exporter: "file".to_string(),
+        file_path: Some(path.to_string_lossy().into_owned()),
+        ..TelemetryConfig::default()
+    };
+    let counter_start_times = CounterStartTimes::new();
+    let provider = build_meter_provider_with_start_times(&telemetry, Resource::default(), &Registry::new(), &counter_start_times)?;
+    let mut monitor = MonitorBuilder::new(config)
+        .with_meter(provider.meter("platform-agent"))
+        .with_counter_start_times(counter_start_times)
+        .build()?;
+    let started = chrono::Utc::now().timestamp() as u64;
+    monitor.start().await?;
+
+    // 最初の観測では累計を開始した時刻が開始時刻になる
+    wait_for_snapshot(&monitor).await;
+    let first = provider.clone();
+    tokio::task::spawn_blocking(move || first.force_flush()).await??;
+    let first_start_times = start_times(&std::fs::read_to_string(&path)?, "container_network_receive_bytes_total");
+    assert!(!first_start_times.is_empty());
+    assert!(first_start_times.iter().all(|start| *start >= started), "{:?}", first_start_times);
+
+    // 再起動後は新しい実行のStartedAt（2024-06-01T00:00:00Z）が開始時刻になる
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    monitor.shutdown().await?;
+    tokio::task::spawn_blocking(move || provider.force_flush()).await??;
+    let start_times = start_times(&std::fs::read_to_string(&path)?, "container_network_receive_bytes_total");
+    assert_eq!(start_times, vec![1_717_200_000]);
+
+    Ok(())
+}
+
+// 同じイメージの2つのコンテナ（開始時刻が異なる）を返すスタブを起動
+// 受信バイト数はstatsを返すたびに1000ずつ増える
+async fn spawn_replica_stub(stats_calls: Arc<[AtomicUsize; 2]>) -> u16 {
+    let api = warp::path::full().map(move |path: warp::path::FullPath| {
+        let path = path.as_str();
+        let replicas = ["web-1", "web-2"];
+        if path.ends_with("/containers/json") {
+            let containers: Vec<_> = replicas
+                .iter()
+                .map(|id| serde_json::json!({ "Id": id, "Names": [format!("/{}", id)], "Image": "nginx:1.25", "State": "running" }))
+                .collect();
+            return warp::reply::json(&containers);
+        }
+        for (replica, id) in replicas.iter().enumerate() {
+            if path.ends_with(&format!("/{}/json", id)) {
+                return warp::reply::json(&serde_json::json!({
+                    "Id": id,
+                    "State": { "Status": "running", "Running": true, "StartedAt": format!("2024-0{}-01T00:00:00Z", replica + 1) }
+                }));
+            }
+            if path.ends_with(&format!("/{}/stats", id)) {
+                let calls = stats_calls[replica].fetch_add(1, Ordering::SeqCst) as u64 + 1;
+                let cpu = serde_json::json!({
+                    "cpu_usage": { "total_usage": 0, "usage_in_usermode": 0, "usage_in_kernelmode": 0 },
+                    "system_cpu_usage": 0,
+                    "online_cpus": 1,
+                    "throttling_data": { "periods": 0, "throttled_periods": 0, "throttled_time": 0 }
+                });
+                let network = serde_json::json!({
+                    "rx_bytes": calls * 1000, "rx_packets": 0, "rx_errors": 0, "rx_dropped": 0,
+                    "tx_bytes": 0, "tx_packets": 0, "tx_errors": 0, "tx_dropped": 0
+                });
+                return warp::reply::json(&serde_json::json!({
+                    "id": id,
+                    "name": format!("/{}", id),
+                    "read": "2024-01-01T00:00:01Z",
+                    "preread": "2024-01-01T00:00:00Z",
+                    "num_procs": 0,
+                    "pids_stats": {},
+                    "memory_stats": {},
+                    "blkio_stats": {},
+                    "cpu_stats": cpu,
+                    "precpu_stats": cpu,
+                    "storage_stats": {},
+                    "networks": { "eth0": network }
+                }));
+            }
+        }
+        warp::reply::json(&serde_json::Value::Null)
+    });
+
+    let (addr, server) = warp::serve(api).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr.port()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_containers_sharing_counter_series_are_summed() -> Result<()> {
+    let stats_calls = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0)]);
+    let port = spawn_replica_stub(stats_calls.clone()).await;
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("metrics.jsonl");
+
+    // イメージのみで集約し、2つのコンテナの累計を1つの系列にする
+    let mut config = monitor_config(port);
+    config.metrics.cardinality.counter_labels = vec!["image".to_string()];
+    config.metrics.inspect_interval = 0;
+
+    let telemetry = TelemetryConfig {
+        otel_exporter: "file".to_string(),
+        file_path: Some(path.to_string_lossy().into_owned()),
+        ..TelemetryConfig::default()
+    };
+    let registry = Registry::new();
+    let counter_start_times = CounterStartTimes::new();
+    let provider = build_meter_provider_with_start_times(&telemetry, Resource::default(), &registry, &counter_start_times)?;
+    let mut monitor = MonitorBuilder::new(config)
+        .with_meter(provider.meter("platform-agent"))
+        .with_counter_start_times(counter_start_times)
+        .build()?;
+    let started = chrono::Utc::now().timestamp() as u64;
+    monitor.start().await?;
+
+    // 3回目のstatsを返した時点で、2回目までのサイクルの増加分は加算済み
+    for _ in 0..50 {
+        if stats_calls.iter().all(|calls| calls.load(Ordering::SeqCst) >= 3) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    monitor.shutdown().await?;
+
+    // 開始時刻の異なるコンテナが交互に加算しても累計し直さない
+    let received = registry
+        .gather()
+        .iter()
+        .filter(|family| family.get_name().starts_with("container_network_receive_bytes"))
+        .flat_map(|family| family.get_metric())
+        .map(|metric| metric.get_counter().get_value())
+        .collect::<Vec<_>>();
+    assert_eq!(received.len(), 1, "{:?}", received);
+    assert!(received[0] >= 2000.0, "{:?}", received);
+
+    // 開始時刻はどちらのコンテナのStartedAtでもなく、累計を開始した時刻
+    tokio::task::spawn_blocking(move || provider.force_flush()).await??;
+    let start_times = start_times(&std::fs::read_to_string(&path)?, "container_network_receive_bytes_total");
+    assert_eq!(start_times.len(), 1);
+    assert!(start_times[0] >= started, "{:?}", start_times);
+
+    Ok(())
+}
+
+// 実行中の"web"の標準出力と標準エラー出力のログを1回ずつ返すスタブを起動
+async fn spawn_logging_stub() -> u16 {
+    // Docker APIの多重化ストリームのフレーム（ストリーム種別と長さのヘッダーに続けて出力）
+    fn frame(stream: u8, output: &str) -> Vec<u8> {
+        let mut frame = vec![stream, 0, 0, 0];
+        frame.extend_from_slice(&(output.len() as u32).to_be_bytes());
+        frame.extend_from_slice(output.as_bytes());
+        frame
+    }
+
+    let api = warp::path::full().map(|path: warp::path::FullPath| {
+        let path = path.as_str();
+        if path.ends_with("/containers/json") {
+            let containers = serde_json::json!([{ "Id": "web", "Names": ["/web"], "Image": "nginx:1.25", "State": "running" }]);
+            return warp::http::Response::builder()
+                .header("content-type", "application/json")
+                .body(containers.to_string().into_bytes());
+        }
+        if path.ends_with("/web/logs") {
+            let mut body = frame(1, "GET / 200\nERROR upstream timed out\n");
+            body.extend(frame(2, "warning: slow request\n"));
+            return warp::http::Response::builder()
+                .header("content-type", "application/vnd.docker.multiplexed-stream")
+                .body(body);
+        }
+        warp::http::Response::builder().status(404).body(Vec::new())
+    });
+
+    let (addr, server) = warp::serve(api).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr.port()
+}
+
+// 指定したメトリクスの系列ごとのラベル名と値
+fn series_labels(registry: &Registry, name: &str) -> Vec<Vec<(String, String)>> {
+    registry
+        .gather()
+        .iter()
+        .filter(|family| family.get_name().starts_with(name))
+        .flat_map(|family| family.get_metric())
+        .map(|metric| {
+            metric
+                .get_label()
+                .iter()
+                .map(|label| (label.get_name().to_string(), label.get_value().to_string()))
+                .collect()
+        })
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_log_counters_use_counter_labels_and_series_limit() -> Result<()> {
+    let port = spawn_logging_stub().await;
+
+    // 系列数の上限を1にし、標準エラー出力の系列をoverflow系列に集約させる
+    let mut config = monitor_config(port);
+    config.container_logs.enabled = true;
+    config.container_logs.patterns = vec![LogPatternConfig {
+        name: "error".to_string(),
+        regex: "ERROR".to_string(),
+    }];
+    config.metrics.cardinality.max_series_per_metric = 1;
+
+    let registry = Registry::new();
+    let provider = build_meter_provider(&TelemetryConfig::default(), Resource::default(), &registry)?;
+    let mut monitor = MonitorBuilder::new(config).with_meter(provider.meter("platform-agent")).build()?;
+    monitor.start().await?;
+    for _ in 0..50 {
+        if series_labels(&registry, "container_log_lines").len() >= 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    monitor.shutdown().await?;
+
+    // 累計値のカウンターと同じく、既定ではcontainer_idを付与しない
+    let lines = series_labels(&registry, "container_log_lines");
+    assert_eq!(lines.len(), 2, "{:?}", lines);
+    let label = |series: &[(String, String)], name: &str| {
+        series.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone())
+    };
+    assert!(lines.iter().all(|series| label(series, "container_id").is_none()), "{:?}", lines);
+    assert!(lines.iter().any(|series| label(series, "container_name").as_deref() == Some("web")), "{:?}", lines);
+
+    // 上限を超えた系列はoverflow系列として記録する
+    assert!(lines.iter().any(|series| label(series, "otel_metric_overflow").as_deref() == Some("true")), "{:?}", lines);
+
+    let matches = series_labels(&registry, "container_log_pattern_matches");
+    assert_eq!(matches.len(), 1, "{:?}", matches);
+    assert_eq!(label(&matches[0], "pattern").as_deref(), Some("error"));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_failed_start_leaves_nothing_running() -> Result<()> {
+    let port = spawn_docker_stub().await;
+
+    // ホストのタスクを開始する前に、remote_writeの設定の誤りで失敗する
+    let mut config = monitor_config(port);
+    config.remote_write.enabled = true;
+
+    let registry = Registry::new();
+    let provider = build_meter_provider(&TelemetryConfig::default(), Resource::default(), &registry)?;
+    let mut monitor = MonitorBuilder::new(config).with_meter(provider.meter("platform-agent")).build()?;
+    assert!(monitor.start().await.is_err());
+
+    // 失敗した開始は何も残さないため、再度の開始も同じ理由で失敗する
+    let error = monitor.start().await.expect_err("start should fail again");
+    assert!(format!("{:#}", error).contains("remote_write.url"), "{:#}", error);
+
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(monitor.snapshot().hosts.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_dropped_monitor_stops_collecting() -> Result<()> {
+    let stats_calls = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0)]);
+    let port = spawn_replica_stub(stats_calls.clone()).await;
+
+    let registry = Registry::new();
+    let provider = build_meter_provider(&TelemetryConfig::default(), Resource::default(), &registry)?;
+    let mut monitor = MonitorBuilder::new(monitor_config(port)).with_meter(provider.meter("platform-agent")).build()?;
+    monitor.start().await?;
+    wait_for_snapshot(&monitor).await;
+
+    // shutdownを呼び出さずに破棄しても、収集ループは止まる
+    drop(monitor);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let calls = stats_calls[0].load(Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(stats_calls[0].load(Ordering::SeqCst), calls);
+
+    Ok(())
+}
+
+// ホストごとのcontainer_monitoring_host_upの値
+fn host_up(registry: &Registry, host: &str) -> Option<f64> {
+    registry
+        .gather()
+        .iter()
+        .filter(|family| family.get_name().starts_with("container_monitoring_host_up"))
+        .flat_map(|family| family.get_metric())
+        .find(|metric| metric.get_label().iter().any(|label| label.get_name() == "host" && label.get_value() == host))
+        .map(|metric| metric.get_gauge().get_value())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unreachable_host_is_reported_down() -> Result<()> {
+    // APIバージョンが不正なためクライアントを作成できない
+    let mut config = monitor_config(1);
+    config.docker.api_version = Some("latest".to_string());
+
+    let registry = Registry::new();
+    let provider = build_meter_provider(&TelemetryConfig::default(), Resource::default(), &registry)?;
+    let mut monitor = MonitorBuilder::new(config).with_meter(provider.meter("platform-agent")).build()?;
+
+    // 接続できないホストがあっても開始し、ダウンとして報告する
+    monitor.start().await?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(host_up(&registry, "local"), Some(0.0));
+    assert!(monitor.snapshot().hosts.is_empty());
+
+    monitor.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_is_reconnected_after_failure() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let state_path = dir.path().join("state.json");
+
+    // 1回目は失敗し、2回目以降はトンネルのソケットを作成するssh
+    let ssh = dir.path().join("ssh");
+    std::fs::write(
+        &ssh,
+        format!(
+            "#!/bin/sh
+while [ $# -gt 0 ]; do
+  if [ \"$1\" = \"-L\" ]; then sock=\"${{2%%:*}}\"; fi
+  shift
+done
+\
+             if [ ! -e {marker} ]; then touch {marker}; echo 'Connection refused' >&2; exit 255; fi
+\
+             touch \"$sock\"
+exec sleep 30
+",
+            marker = dir.path().join("attempted").display()
+        ),
+    )?;
+    std::fs::set_permissions(&ssh, std::fs::Permissions::from_mode(0o755))?;
+
+    let mut config = monitor_config(1);
+    config.docker.host = Some("ssh://monitor@build-2".to_string());
+    config.docker.ssh.command = ssh.to_string_lossy().into_owned();
+    config.state.path = Some(state_path.to_string_lossy().into_owned());
+
+    let registry = Registry::new();
+    let provider = build_meter_provider(&TelemetryConfig::default(), Resource::default(), &registry)?;
+    let mut monitor = MonitorBuilder::new(config).with_meter(provider.meter("platform-agent")).build()?;
+    monitor.start().await?;
+    assert_eq!(host_up(&registry, "local"), Some(0.0));
+
+    // 再接続したホストのコレクターは終了時に状態を保存する
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    monitor.shutdown().await?;
+    let state = std::fs::read_to_string(&state_path)?;
+    assert!(state.contains("\"local\""), "{}", state);
+
+    Ok(())
+}
+
+#[test]
+fn test_sinks_cannot_be_combined_with_custom_meter() -> Result<()> {
+    let registry = Registry::new();
+    let provider = build_meter_provider(&TelemetryConfig::default(), Resource::default(), &registry)?;
+
+    let result = MonitorBuilder::new(Config::default())
+        .with_meter(provider.meter("platform-agent"))
+        .with_sinks(vec![SinkConfig {
+            address: Some("127.0.0.1:8125".to_string()),
+            ..SinkConfig::default()
+        }])
+        .build();
+    assert!(result.is_err());
+
+    Ok(())
+}
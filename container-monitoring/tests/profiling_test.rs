@@ -0,0 +1,88 @@
+#![cfg(feature = "profiling")]
+
+mod common;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use pprof::protos::{Message, Profile};
+use reqwest::StatusCode;
+
+use container_monitoring::config::{PprofConfig, ServerConfig};
+use container_monitoring::profiling::{capture_cpu_profile, memory_summary_from_status, ProfileFormat};
+
+// pprofエンドポイントの設定を指定してメトリクスサーバーを起動
+async fn spawn_server(pprof: PprofConfig) -> Result<u16> {
+    common::spawn_server(ServerConfig {
+        bind_address: "127.0.0.1".to_string(),
+        pprof,
+        ..Default::default()
+    })
+    .await
+}
+
+#[test]
+fn test_memory_summary_from_status() {
+    let status = "Name:\tcontainer-monit\nVmHWM:\t   20480 kB\nVmRSS:\t   10240 kB\nVmSize:\t  204800 kB\nVmData:\t   51200 kB\nRssAnon:\t    8192 kB\nRssFile:\t    2048 kB\nThreads:\t12\n";
+
+    let summary = memory_summary_from_status(status);
+    assert_eq!(summary.rss_bytes, 10240 * 1024);
+    assert_eq!(summary.peak_rss_bytes, 20480 * 1024);
+    assert_eq!(summary.virtual_bytes, 204800 * 1024);
+    assert_eq!(summary.data_bytes, 51200 * 1024);
+    assert_eq!(summary.anon_rss_bytes, 8192 * 1024);
+    assert_eq!(summary.file_rss_bytes, 2048 * 1024);
+    assert_eq!(summary.threads, 12);
+}
+
+#[test]
+fn test_profile_format() {
+    assert_eq!(ProfileFormat::parse("svg"), Some(ProfileFormat::Flamegraph));
+    assert_eq!(ProfileFormat::parse("pprof"), Some(ProfileFormat::Pprof));
+    assert_eq!(ProfileFormat::parse("json"), None);
+    assert_eq!(ProfileFormat::Flamegraph.content_type(), "image/svg+xml");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_capture_cpu_profile_in_pprof_format() -> Result<()> {
+    // プロファイル中にCPUを使う処理を実行
+    let busy = tokio::task::spawn_blocking(|| (0..50_000_000u64).fold(0u64, |acc, n| acc.wrapping_add(n * n)));
+
+    let profile = capture_cpu_profile(Duration::from_secs(1), 100, ProfileFormat::Pprof).await?;
+    busy.await?;
+
+    // go tool pprofで読めるprotobufとしてデコードできる
+    let profile = Profile::decode(profile.expect("no other profile running").as_slice())?;
+    assert!(!profile.string_table.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pprof_routes() -> Result<()> {
+    // 無効な場合は404
+    let port = spawn_server(PprofConfig::default()).await?;
+    let response = reqwest::get(format!("http://127.0.0.1:{}/debug/pprof/heap", port)).await?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let port = spawn_server(PprofConfig {
+        enabled: true,
+        ..Default::default()
+    })
+    .await?;
+    let url = |path: &str| format!("http://127.0.0.1:{}/debug/pprof/{}", port, path);
+
+    // メモリ使用量のJSON
+    let response = reqwest::get(url("heap")).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let summary: serde_json::Value = response.json().await?;
+    assert!(summary["rss_bytes"].as_u64().unwrap_or_default() > 0);
+
+    // 不正なパラメーターは400
+    let response = reqwest::get(url("profile?format=json")).await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = reqwest::get(url("profile?seconds=0")).await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    Ok(())
+}
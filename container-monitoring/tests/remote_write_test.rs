@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use prometheus::{Gauge, Histogram, HistogramOpts, IntCounterVec, Opts, Registry};
+use prost::Message;
+use warp::http::StatusCode;
+use warp::Filter;
+
+use container_monitoring::config::{BasicAuthConfig, RemoteWriteConfig};
+use container_monitoring::remote_write::{write_request, RemoteWriter, TimeSeries, WriteRequest};
+
+// スタブが受信したリクエスト（ヘッダーと展開済みのリクエスト）
+type Received = Arc<Mutex<Vec<(HashMap<String, String>, WriteRequest)>>>;
+
+// remote_writeのスタブを起動（先頭のfailures回は`status`を返す）
+async fn spawn_receiver(received: Received, failures: usize, status: StatusCode) -> SocketAddr {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let route = warp::post()
+        .and(warp::path!("api" / "v1" / "write"))
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .map(move |headers: warp::http::HeaderMap, body: warp::hyper::body::Bytes| {
+            if attempts.fetch_add(1, Ordering::SeqCst) < failures {
+                return warp::reply::with_status("unavailable", status);
+            }
+
+            let headers: HashMap<String, String> = headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_str().unwrap_or_default().to_string()))
+                .collect();
+            let body = snap::raw::Decoder::new().decompress_vec(&body).unwrap();
+            let request = WriteRequest::decode(body.as_slice()).unwrap();
+            received.lock().unwrap().push((headers, request));
+            warp::reply::with_status("", StatusCode::NO_CONTENT)
+        });
+
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr
+}
+
+// テスト用の設定を作成（再試行の待ち時間は短くする）
+fn remote_write_config(addr: SocketAddr) -> RemoteWriteConfig {
+    RemoteWriteConfig {
+        enabled: true,
+        url: format!("http://{}/api/v1/write", addr),
+        retry_backoff_ms: 10,
+        ..RemoteWriteConfig::default()
+    }
+}
+
+// カウンター・ゲージ・ヒストグラムを登録したレジストリを作成
+fn registry() -> Result<Registry> {
+    let registry = Registry::new();
+
+    let counter = IntCounterVec::new(Opts::new("container_network_rx_bytes_total", "rx"), &["container_name"])?;
+    counter.with_label_values(&["web"]).inc_by(42);
+    registry.register(Box::new(counter))?;
+
+    let gauge = Gauge::new("container_cpu_usage", "cpu")?;
+    gauge.set(12.5);
+    registry.register(Box::new(gauge))?;
+
+    let histogram = Histogram::with_opts(HistogramOpts::new("collect_seconds", "duration").buckets(vec![0.1, 1.0]))?;
+    histogram.observe(0.5);
+    registry.register(Box::new(histogram))?;
+
+    Ok(registry)
+}
+
+// 名前と（指定されていれば）ラベルが一致する系列を検索
+fn find<'a>(request: &'a WriteRequest, name: &str, label: Option<(&str, &str)>) -> Option<&'a TimeSeries> {
+    request.timeseries.iter().find(|series| {
+        series.labels.iter().any(|l| l.name == "__name__" && l.value == name)
+            && label.is_none_or(|(key, value)| series.labels.iter().any(|l| l.name == key && l.value == value))
+    })
+}
+
+#[test]
+fn test_write_request_matches_prometheus_series() -> Result<()> {
+    let external_labels = HashMap::from([
+        ("instance".to_string(), "edge-01".to_string()),
+        ("container_name".to_string(), "ignored".to_string()),
+    ]);
+    let request = write_request(&registry()?.gather(), &external_labels, 1_700_000_000_000);
+
+    // ラベルは名前順で、外部ラベルは既存のラベルを上書きしない
+    let counter = find(&request, "container_network_rx_bytes_total", None).expect("counter series");
+    let labels: Vec<(&str, &str)> = counter.labels.iter().map(|l| (l.name.as_str(), l.value.as_str())).collect();
+    assert_eq!(
+        labels,
+        vec![
+            ("__name__", "container_network_rx_bytes_total"),
+            ("container_name", "web"),
+            ("instance", "edge-01"),
+        ]
+    );
+    assert_eq!(counter.samples[0].value, 42.0);
+    assert_eq!(counter.samples[0].timestamp, 1_700_000_000_000);
+
+    assert_eq!(find(&request, "container_cpu_usage", None).unwrap().samples[0].value, 12.5);
+
+    // ヒストグラムは/metricsと同じく_bucket（+Infを含む）、_sum、_countに展開
+    assert_eq!(find(&request, "collect_seconds_bucket", Some(("le", "0.1"))).unwrap().samples[0].value, 0.0);
+    assert_eq!(find(&request, "collect_seconds_bucket", Some(("le", "1"))).unwrap().samples[0].value, 1.0);
+    assert_eq!(find(&request, "collect_seconds_bucket", Some(("le", "+Inf"))).unwrap().samples[0].value, 1.0);
+    assert_eq!(find(&request, "collect_seconds_sum", None).unwrap().samples[0].value, 0.5);
+    assert_eq!(find(&request, "collect_seconds_count", None).unwrap().samples[0].value, 1.0);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_push_with_basic_auth_and_retries() -> Result<()> {
+    let received: Received = Arc::new(Mutex::new(Vec::new()));
+    let addr = spawn_receiver(received.clone(), 2, StatusCode::SERVICE_UNAVAILABLE).await;
+
+    let mut config = remote_write_config(addr);
+    config.basic = Some(BasicAuthConfig {
+        username: "agent".to_string(),
+        password: "secret".to_string(),
+    });
+    let mut writer = RemoteWriter::new(&config)?;
+
+    // 2回の503の後に成功する
+    writer.write(&registry()?.gather()).await?;
+    assert_eq!(writer.queued(), 0);
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    let (headers, request) = &received[0];
+    assert_eq!(headers.get("content-encoding").map(String::as_str), Some("snappy"));
+    assert_eq!(headers.get("content-type").map(String::as_str), Some("application/x-protobuf"));
+    assert_eq!(headers.get("x-prometheus-remote-write-version").map(String::as_str), Some("0.1.0"));
+    // "agent:secret"のBase64
+    assert_eq!(headers.get("authorization").map(String::as_str), Some("Basic YWdlbnQ6c2VjcmV0"));
+    assert!(find(request, "container_cpu_usage", None).is_some());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_outage_keeps_bounded_queue() -> Result<()> {
+    let received: Received = Arc::new(Mutex::new(Vec::new()));
+    let addr = spawn_receiver(received.clone(), usize::MAX, StatusCode::SERVICE_UNAVAILABLE).await;
+
+    let mut config = remote_write_config(addr);
+    config.max_retries = 0;
+    config.max_queue_batches = 2;
+    let mut writer = RemoteWriter::new(&config)?;
+
+    // 障害中はバッチを保持し、上限を超えると古いものから破棄
+    for _ in 0..3 {
+        assert!(writer.write(&registry()?.gather()).await.is_err());
+    }
+    assert_eq!(writer.queued(), 2);
+    assert!(received.lock().unwrap().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rejected_batch_is_dropped() -> Result<()> {
+    let received: Received = Arc::new(Mutex::new(Vec::new()));
+    let addr = spawn_receiver(received.clone(), 1, StatusCode::BAD_REQUEST).await;
+
+    let mut writer = RemoteWriter::new(&remote_write_config(addr))?;
+
+    // 400は再試行せずに破棄し、次のバッチは送信される
+    writer.write(&registry()?.gather()).await?;
+    assert_eq!(writer.queued(), 0);
+    assert!(received.lock().unwrap().is_empty());
+
+    writer.write(&registry()?.gather()).await?;
+    assert_eq!(received.lock().unwrap().len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_disk_queue_survives_restart() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let queue_path = dir.path().join("queue");
+
+    // 送信先が停止している間にバッチをディスクに保存
+    let down = spawn_receiver(Arc::new(Mutex::new(Vec::new())), usize::MAX, StatusCode::SERVICE_UNAVAILABLE).await;
+    let mut config = remote_write_config(down);
+    config.max_retries = 0;
+    config.queue = "disk".to_string();
+    config.queue_path = Some(queue_path.to_string_lossy().into_owned());
+    let mut writer = RemoteWriter::new(&config)?;
+    assert!(writer.write(&registry()?.gather()).await.is_err());
+    assert!(writer.write(&registry()?.gather()).await.is_err());
+    drop(writer);
+
+    // 再起動後に保存されていたバッチを古い順に送信
+    let received: Received = Arc::new(Mutex::new(Vec::new()));
+    let up = spawn_receiver(received.clone(), 0, StatusCode::OK).await;
+    config.url = format!("http://{}/api/v1/write", up);
+    let mut writer = RemoteWriter::new(&config)?;
+    assert_eq!(writer.queued(), 2);
+
+    writer.flush().await?;
+    assert_eq!(writer.queued(), 0);
+    assert_eq!(received.lock().unwrap().len(), 2);
+    assert_eq!(std::fs::read_dir(&queue_path)?.count(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_remote_write_configs_are_rejected() {
+    let missing_url = RemoteWriteConfig { enabled: true, ..RemoteWriteConfig::default() };
+    assert!(RemoteWriter::new(&missing_url).is_err());
+
+    let base = RemoteWriteConfig {
+        enabled: true,
+        url: "http://127.0.0.1:1/api/v1/write".to_string(),
+        ..RemoteWriteConfig::default()
+    };
+    let missing_path = RemoteWriteConfig { queue: "disk".to_string(), ..base.clone() };
+    assert!(RemoteWriter::new(&missing_path).is_err());
+    let unknown_queue = RemoteWriteConfig { queue: "redis".to_string(), ..base };
+    assert!(RemoteWriter::new(&unknown_queue).is_err());
+}
@@ -0,0 +1,99 @@
+use anyhow::Result;
+use opentelemetry::{Key, Value};
+use serial_test::serial;
+use tempfile::tempdir;
+
+use container_monitoring::config::TelemetryConfig;
+use container_monitoring::resource::{
+    container_id_from_cgroup, container_id_from_mountinfo, detect_container_id, telemetry_resource,
+};
+
+const CONTAINER_ID: &str = "3f6b1c2d4e5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0";
+
+// テスト用のテレメトリー設定を作成
+fn telemetry_config(resource_detectors: &[&str]) -> TelemetryConfig {
+    TelemetryConfig {
+        service_name: "test-service".to_string(),
+        prometheus_port: 0,
+        resource_detectors: resource_detectors.iter().map(|name| name.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_container_id_from_cgroup() {
+    // cgroup v1（Docker）
+    let cgroup = format!("12:pids:/docker/{id}\n11:memory:/docker/{id}\n0::/\n", id = CONTAINER_ID);
+    assert_eq!(container_id_from_cgroup(&cgroup).as_deref(), Some(CONTAINER_ID));
+
+    // systemd cgroupドライバー（docker-<id>.scope）
+    let cgroup = format!("0::/system.slice/docker-{}.scope\n", CONTAINER_ID);
+    assert_eq!(container_id_from_cgroup(&cgroup).as_deref(), Some(CONTAINER_ID));
+
+    // コンテナ外やcgroup名前空間内
+    assert!(container_id_from_cgroup("0::/\n").is_none());
+    assert!(container_id_from_cgroup("0::/user.slice/user-1000.slice/session-2.scope\n").is_none());
+}
+
+#[test]
+fn test_container_id_from_mountinfo() {
+    let mountinfo = format!(
+        "\\
+1362 1180 0:160 / / rw,relatime - overlay overlay rw
+1367 1362 259:2 /var/lib/docker/containers/{id}/resolv.conf /etc/resolv.conf rw,relatime - ext4 /dev/nvme0n1p2 rw
+1368 1362 259:2 /var/lib/docker/containers/{id}/hostname /etc/hostname rw,relatime - ext4 /dev/nvme0n1p2 rw
+",
+        id = CONTAINER_ID
+    );
+    assert_eq!(container_id_from_mountinfo(&mountinfo).as_deref(), Some(CONTAINER_ID));
+
+    // ホスト上ではshmのマウント先にIDが含まれてもルートは"/"
+    let mountinfo = format!(
+        "88 30 0:50 / /var/lib/docker/containers/{}/mounts/shm rw - tmpfs shm rw\n",
+        CONTAINER_ID
+    );
+    assert!(container_id_from_mountinfo(&mountinfo).is_none());
+}
+
+#[test]
+fn test_detect_container_id_falls_back_to_mountinfo() -> Result<()> {
+    let process_dir = tempdir()?;
+    std::fs::write(process_dir.path().join("cgroup"), "0::/\n")?;
+    std::fs::write(
+        process_dir.path().join("mountinfo"),
+        format!("1368 1362 259:2 /var/lib/docker/containers/{}/hostname /etc/hostname rw - ext4 /dev/sda1 rw\n", CONTAINER_ID),
+    )?;
+
+    assert_eq!(detect_container_id(process_dir.path()).as_deref(), Some(CONTAINER_ID));
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_telemetry_resource_precedence() -> Result<()> {
+    std::env::remove_var("OTEL_SERVICE_NAME");
+    std::env::set_var("OTEL_RESOURCE_ATTRIBUTES", "deployment.environment=staging,service.name=from-attributes");
+
+    let resource = telemetry_resource(&telemetry_config(&["os", "process"]))?;
+    assert_eq!(resource.get(Key::new("service.name")), Some(Value::from("from-attributes")));
+    assert_eq!(resource.get(Key::new("deployment.environment")), Some(Value::from("staging")));
+    assert_eq!(resource.get(Key::new("process.pid")), Some(Value::from(std::process::id() as i64)));
+    assert!(resource.get(Key::new("os.type")).is_some());
+    assert!(resource.get(Key::new("service.version")).is_some());
+    // 無効にした検出器の属性は含まれない
+    assert!(resource.get(Key::new("host.arch")).is_none());
+
+    // OTEL_SERVICE_NAMEが最優先
+    std::env::set_var("OTEL_SERVICE_NAME", "from-env");
+    let resource = telemetry_resource(&telemetry_config(&[]))?;
+    assert_eq!(resource.get(Key::new("service.name")), Some(Value::from("from-env")));
+
+    std::env::remove_var("OTEL_SERVICE_NAME");
+    std::env::remove_var("OTEL_RESOURCE_ATTRIBUTES");
+    let resource = telemetry_resource(&telemetry_config(&["host"]))?;
+    assert_eq!(resource.get(Key::new("service.name")), Some(Value::from("test-service")));
+    assert!(resource.get(Key::new("host.arch")).is_some());
+
+    assert!(telemetry_resource(&telemetry_config(&["kubernetes"])).is_err());
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::time::{Instant, MissedTickBehavior};
+
+use container_monitoring::config::{GeneralConfig, GroupIntervals};
+use container_monitoring::schedule::{adapt_interval, missed_tick_behavior, CollectionSchedule};
+
+// テスト用の全体設定を作成
+fn general_config(adaptive: bool, missed_tick_behavior: &str) -> GeneralConfig {
+    GeneralConfig {
+        interval: 10,
+        intervals: GroupIntervals::default(),
+        adaptive,
+        max_interval_factor: 4,
+        missed_tick_behavior: missed_tick_behavior.to_string(),
+    }
+}
+
+// 3回分の収集時刻（開始からの経過時間）を取得。1回目のサイクルは間隔を超える
+async fn tick_offsets(schedule: &mut CollectionSchedule, first_cycle: Duration) -> Vec<Duration> {
+    let start = Instant::now();
+    let mut offsets = Vec::new();
+
+    for cycle in 0..3 {
+        schedule.tick().await;
+        offsets.push(Instant::now() - start);
+        let elapsed = if cycle == 0 { first_cycle } else { Duration::from_secs(1) };
+        tokio::time::sleep(elapsed).await;
+        schedule.record_cycle(elapsed);
+    }
+
+    offsets
+}
+
+#[test]
+fn test_adapt_interval() {
+    let base = Duration::from_secs(10);
+    let max = Duration::from_secs(40);
+    let secs = Duration::from_secs;
+
+    // 予算内なら変わらない
+    assert_eq!(adapt_interval(base, base, max, secs(6), 0), base);
+    // 超過したら2倍、所要時間の方が長ければ所要時間、上限あり
+    assert_eq!(adapt_interval(base, base, max, secs(12), 0), secs(20));
+    assert_eq!(adapt_interval(base, base, max, secs(25), 0), secs(25));
+    assert_eq!(adapt_interval(secs(25), base, max, secs(60), 0), max);
+    // 余裕のあるサイクルが1回だけでは縮めない
+    assert_eq!(adapt_interval(secs(40), base, max, secs(20), 1), secs(40));
+    assert_eq!(adapt_interval(secs(20), base, max, secs(12), 0), secs(20));
+    // 3回続いたら4分の3に縮める（基本の間隔まで）
+    assert_eq!(adapt_interval(secs(40), base, max, secs(20), 3), secs(30));
+    assert_eq!(adapt_interval(secs(12), base, max, secs(1), 3), base);
+}
+
+#[test]
+fn test_invalid_schedule_settings() {
+    assert_eq!(missed_tick_behavior("delay").unwrap(), MissedTickBehavior::Delay);
+    assert!(missed_tick_behavior("catch_up").is_err());
+
+    let general = general_config(false, "skip");
+    assert!(CollectionSchedule::new("containers", Duration::ZERO, &general).is_err());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_missed_tick_behavior() -> Result<()> {
+    let interval = Duration::from_secs(10);
+    let secs = Duration::from_secs;
+
+    // burstは取りこぼした収集を連続で実行
+    let mut schedule = CollectionSchedule::new("containers", interval, &general_config(false, "burst"))?;
+    assert_eq!(tick_offsets(&mut schedule, secs(25)).await, vec![secs(0), secs(25), secs(26)]);
+
+    // delayは遅れた時点から間隔を空ける
+    let mut schedule = CollectionSchedule::new("containers", interval, &general_config(false, "delay"))?;
+    assert_eq!(tick_offsets(&mut schedule, secs(25)).await, vec![secs(0), secs(25), secs(35)]);
+
+    // skipは取りこぼした収集を飛ばして元の周期に合わせる
+    let mut schedule = CollectionSchedule::new("containers", interval, &general_config(false, "skip"))?;
+    assert_eq!(tick_offsets(&mut schedule, secs(25)).await, vec![secs(0), secs(25), secs(30)]);
+
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_adaptive_schedule_stretches_and_recovers() -> Result<()> {
+    let secs = Duration::from_secs;
+    let mut schedule = CollectionSchedule::new("host", secs(10), &general_config(true, "skip"))?;
+
+    // 間隔を超えたサイクルの後は延長した間隔で実行
+    let offsets = tick_offsets(&mut schedule, secs(12)).await;
+    assert_eq!(offsets[1], secs(12 + 20));
+
+    // 余裕のあるサイクルが1回あっても延長した間隔を維持する
+    assert_eq!(schedule.period(), secs(20));
+    assert_eq!(offsets[2], offsets[1] + secs(20));
+
+    // 余裕のあるサイクルが続くと少しずつ元の間隔に戻る
+    schedule.record_cycle(secs(1));
+    assert_eq!(schedule.period(), secs(15));
+    for _ in 0..3 {
+        schedule.record_cycle(secs(1));
+    }
+    assert_eq!(schedule.period(), Duration::from_millis(11250));
+    for _ in 0..3 {
+        schedule.record_cycle(secs(1));
+    }
+    assert_eq!(schedule.period(), secs(10));
+
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_adaptive_schedule_does_not_oscillate() -> Result<()> {
+    let secs = Duration::from_secs;
+    let mut schedule = CollectionSchedule::new("containers", secs(10), &general_config(true, "skip"))?;
+
+    // 基本の間隔を少し超えるサイクルの後、所要時間がばらつく場合
+    schedule.record_cycle(secs(12));
+    assert_eq!(schedule.period(), secs(20));
+
+    // 間隔の半分以下のサイクルが連続しなければ縮めず、延長と縮小を繰り返さない
+    for elapsed in [9, 11, 9, 9, 11, 9, 11] {
+        schedule.record_cycle(secs(elapsed));
+        assert_eq!(schedule.period(), secs(20));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use std::path::Path;
+
+use anyhow::Result;
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa};
+use reqwest::StatusCode;
+use tempfile::tempdir;
+
+use container_monitoring::config::{AuthConfig, BasicAuthConfig, ServerConfig, ServerTlsConfig};
+use container_monitoring::server::start_metrics_server;
+use container_monitoring::stream;
+
+use common::{free_port, spawn_server};
+
+// テスト用のCAと、それで署名したlocalhost向け証明書を生成
+fn generate_certificates(dir: &Path) -> Result<(Certificate, String)> {
+    let mut ca_params = CertificateParams::new(vec![]);
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params.distinguished_name.push(DnType::CommonName, "container-monitoring test CA");
+    let ca = Certificate::from_params(ca_params)?;
+
+    let server = Certificate::from_params(CertificateParams::new(vec!["localhost".to_string()]))?;
+    std::fs::write(dir.join("ca.pem"), ca.serialize_pem()?)?;
+    std::fs::write(dir.join("server.pem"), server.serialize_pem_with_signer(&ca)?)?;
+    std::fs::write(dir.join("server.key"), server.serialize_private_key_pem())?;
+
+    let ca_pem = ca.serialize_pem()?;
+    Ok((ca, ca_pem))
+}
+
+#[tokio::test]
+async fn test_auth_protects_routes() -> Result<()> {
+    let port = spawn_server(ServerConfig {
+        bind_address: "127.0.0.1".to_string(),
+        tls: None,
+        auth: AuthConfig {
+            basic: Some(BasicAuthConfig {
+                username: "prometheus".to_string(),
+                password: "secret".to_string(),
+            }),
+            bearer_token: Some("token-123".to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await?;
+
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://127.0.0.1:{}/{}", port, path);
+
+    // /healthはデフォルトで認証不要
+    let response = client.get(url("health")).send().await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // 資格情報なし・誤った資格情報では401
+    let response = client.get(url("metrics")).send().await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response.headers().contains_key("www-authenticate"));
+
+    let response = client.get(url("metrics")).basic_auth("prometheus", Some("wrong")).send().await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Basic認証とBearerトークンのどちらでもアクセス可能
+    let response = client.get(url("metrics")).basic_auth("prometheus", Some("secret")).send().await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client.get(url("metrics")).bearer_auth("token-123").send().await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_tls_with_client_certificates() -> Result<()> {
+    let dir = tempdir()?;
+    let (ca, ca_pem) = generate_certificates(dir.path())?;
+
+    let port = spawn_server(ServerConfig {
+        bind_address: "127.0.0.1".to_string(),
+        tls: Some(ServerTlsConfig {
+            cert_path: dir.path().join("server.pem").to_string_lossy().into_owned(),
+            key_path: dir.path().join("server.key").to_string_lossy().into_owned(),
+            client_ca_path: Some(dir.path().join("ca.pem").to_string_lossy().into_owned()),
+        }),
+        auth: AuthConfig::default(),
+        ..Default::default()
+    })
+    .await?;
+
+    let url = format!("https://localhost:{}/health", port);
+    let root = reqwest::Certificate::from_pem(ca_pem.as_bytes())?;
+
+    // クライアント証明書なしではハンドシェイクに失敗
+    let client = reqwest::Client::builder()
+        .add_root_certificate(root.clone())
+        .build()?;
+    assert!(client.get(&url).send().await.is_err());
+
+    // CAで署名したクライアント証明書で接続可能
+    let client_cert = Certificate::from_params(CertificateParams::new(vec!["client".to_string()]))?;
+    let identity = reqwest::Identity::from_pkcs8_pem(
+        client_cert.serialize_pem_with_signer(&ca)?.as_bytes(),
+        client_cert.serialize_private_key_pem().as_bytes(),
+    )?;
+    let client = reqwest::Client::builder()
+        .add_root_certificate(root)
+        .identity(identity)
+        .build()?;
+    let response = client.get(&url).send().await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await?, "ok");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_invalid_tls_files_are_reported() -> Result<()> {
+    let dir = tempdir()?;
+    generate_certificates(dir.path())?;
+
+    // 別の証明書の秘密鍵と、PEMとして読めないファイル
+    let other = Certificate::from_params(CertificateParams::new(vec!["localhost".to_string()]))?;
+    std::fs::write(dir.path().join("other.key"), other.serialize_private_key_pem())?;
+    std::fs::write(dir.path().join("broken.pem"), "-----BEGIN CERTIFICATE-----\nnot base64\n")?;
+
+    let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+    let cases = [
+        ("broken.pem", "server.key", None),
+        ("server.pem", "broken.pem", None),
+        ("server.pem", "other.key", None),
+        ("server.pem", "server.key", Some("broken.pem")),
+    ];
+
+    // warpの内部でpanicせず、起動時のエラーとして返る
+    for (cert, key, client_ca) in cases {
+        let server_config = ServerConfig {
+            bind_address: "127.0.0.1".to_string(),
+            tls: Some(ServerTlsConfig {
+                cert_path: path(cert),
+                key_path: path(key),
+                client_ca_path: client_ca.map(path),
+            }),
+            ..Default::default()
+        };
+        let result = start_metrics_server(stream::channel(), free_port(), &server_config).await;
+        assert!(result.is_err(), "{} / {} / {:?} should be rejected", cert, key, client_ca);
+    }
+
+    Ok(())
+}
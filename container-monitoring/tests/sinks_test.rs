@@ -0,0 +1,196 @@
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::Result;
+use opentelemetry::metrics::MeterProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::metrics::MeterProvider;
+use opentelemetry_sdk::Resource;
+use prometheus::Registry;
+use warp::Filter;
+
+use container_monitoring::config::{SinkConfig, TelemetryConfig};
+use container_monitoring::sinks::{dogstatsd_lines, influx_lines, Sample, SampleValue, SinkExporter};
+use container_monitoring::telemetry::build_meter_provider;
+
+// スタブが受信した書き込み（Authorizationヘッダーと本文）
+type Received = Arc<Mutex<Vec<(Option<String>, String)>>>;
+
+// シンクのみを有効にしたテレメトリー設定を作成
+fn telemetry_config(sink: SinkConfig) -> TelemetryConfig {
+    TelemetryConfig {
+        service_name: "test-service".to_string(),
+        prometheus_port: 0,
+        resource_detectors: Vec::new(),
+        sinks: vec![sink],
+        ..Default::default()
+    }
+}
+
+// ゲージとカウンターを記録してエクスポートを強制（PeriodicReaderのflushはブロッキング）
+async fn record_and_flush(provider: MeterProvider) {
+    let meter = provider.meter("sinks-test");
+    let counter = meter.u64_counter("test_requests").init();
+    counter.add(3, &[KeyValue::new("container_name", "web 1")]);
+    let _gauge = meter
+        .f64_observable_gauge("test_temperature")
+        .with_callback(|observer| observer.observe(21.5, &[]))
+        .init();
+
+    tokio::task::spawn_blocking(move || provider.force_flush().unwrap())
+        .await
+        .unwrap();
+}
+
+// 時刻を固定したデータポイントを作成
+fn sample(name: &str, labels: &[(&str, &str)], value: SampleValue) -> Sample {
+    Sample {
+        name: name.to_string(),
+        labels: labels.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+        value,
+        timestamp: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+    }
+}
+
+#[test]
+fn test_dogstatsd_line_format() {
+    let samples = vec![
+        sample("container_cpu_usage", &[("container_name", "web")], SampleValue::Gauge(12.5)),
+        sample("container_network_rx_bytes", &[], SampleValue::Counter(42.0)),
+        sample("request_duration", &[], SampleValue::Histogram { count: 2, sum: 0.75 }),
+    ];
+
+    let lines = dogstatsd_lines(&samples, "cm.", &["env:test".to_string()]);
+
+    assert_eq!(
+        lines,
+        vec![
+            "cm.container_cpu_usage:12.5|g|#env:test,container_name:web",
+            "cm.container_network_rx_bytes:42|c|#env:test",
+            "cm.request_duration.count:2|c|#env:test",
+            "cm.request_duration.sum:0.75|c|#env:test",
+        ]
+    );
+
+    // タグの区切り文字はラベルの値から取り除く
+    let samples = vec![sample("m", &[("image", "a,b|c")], SampleValue::Gauge(1.0))];
+    assert_eq!(dogstatsd_lines(&samples, "", &[]), vec!["m:1|g|#image:a_b_c"]);
+}
+
+#[test]
+fn test_influx_line_format_and_escaping() {
+    let samples = vec![
+        sample("container_cpu_usage", &[("container_name", "web 1"), ("image", "a=b,c")], SampleValue::Gauge(12.0)),
+        sample("request_duration", &[("empty", "")], SampleValue::Histogram { count: 2, sum: 0.75 }),
+    ];
+
+    let lines = influx_lines(&samples, "", &["env:test".to_string()]);
+
+    assert_eq!(
+        lines,
+        vec![
+            r"container_cpu_usage,env=test,container_name=web\ 1,image=a\=b\,c value=12.0 1700000000000000000",
+            "request_duration,env=test count=2i,sum=0.75 1700000000000000000",
+        ]
+    );
+}
+
+#[test]
+fn test_invalid_sink_configs_are_rejected() {
+    for sink in [
+        SinkConfig { kind: "graphite".to_string(), ..SinkConfig::default() },
+        SinkConfig { kind: "dogstatsd".to_string(), ..SinkConfig::default() },
+        SinkConfig { kind: "influxdb".to_string(), ..SinkConfig::default() },
+        SinkConfig {
+            kind: "influxdb".to_string(),
+            url: Some("http://127.0.0.1:1/write".to_string()),
+            path: Some("/tmp/metrics.lp".to_string()),
+            ..SinkConfig::default()
+        },
+    ] {
+        assert!(SinkExporter::new(&sink).is_err(), "{:?} should be rejected", sink);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_dogstatsd_sink_sends_udp_packets() -> Result<()> {
+    let listener = UdpSocket::bind("127.0.0.1:0")?;
+    listener.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let config = telemetry_config(SinkConfig {
+        kind: "dogstatsd".to_string(),
+        address: Some(listener.local_addr()?.to_string()),
+        prefix: "cm.".to_string(),
+        tags: vec!["env:test".to_string()],
+        ..SinkConfig::default()
+    });
+    let provider = build_meter_provider(&config, Resource::default(), &Registry::new())?;
+    record_and_flush(provider).await;
+
+    let mut buffer = [0u8; 2048];
+    let size = listener.recv(&mut buffer)?;
+    let packet = String::from_utf8_lossy(&buffer[..size]).to_string();
+
+    // 1パケットに改行区切りで複数のメトリクスがまとめられる
+    let lines: Vec<&str> = packet.lines().collect();
+    assert!(lines.contains(&"cm.test_requests:3|c|#env:test,container_name:web 1"), "{}", packet);
+    assert!(lines.contains(&"cm.test_temperature:21.5|g|#env:test"), "{}", packet);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_influxdb_sink_posts_line_protocol() -> Result<()> {
+    let received: Received = Arc::new(Mutex::new(Vec::new()));
+    let route = {
+        let received = received.clone();
+        warp::post()
+            .and(warp::path!("api" / "v2" / "write"))
+            .and(warp::header::optional::<String>("authorization"))
+            .and(warp::body::bytes())
+            .map(move |authorization: Option<String>, body: warp::hyper::body::Bytes| {
+                received.lock().unwrap().push((authorization, String::from_utf8_lossy(&body).to_string()));
+                warp::reply()
+            })
+    };
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let config = telemetry_config(SinkConfig {
+        kind: "influxdb".to_string(),
+        url: Some(format!("http://{}/api/v2/write?org=test&bucket=containers&precision=ns", addr)),
+        token: Some("secret".to_string()),
+        ..SinkConfig::default()
+    });
+    let provider = build_meter_provider(&config, Resource::default(), &Registry::new())?;
+    record_and_flush(provider).await;
+
+    let received = received.lock().unwrap();
+    let (authorization, body) = received.first().expect("no InfluxDB write received");
+    assert_eq!(authorization.as_deref(), Some("Token secret"));
+    assert!(body.lines().any(|line| line.starts_with(r"test_requests,container_name=web\ 1 value=3.0 ")), "{}", body);
+    assert!(body.lines().any(|line| line.starts_with("test_temperature value=21.5 ")), "{}", body);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_influxdb_sink_appends_to_file() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("metrics.lp");
+
+    let config = telemetry_config(SinkConfig {
+        kind: "influxdb".to_string(),
+        path: Some(path.to_string_lossy().into_owned()),
+        ..SinkConfig::default()
+    });
+    let provider = build_meter_provider(&config, Resource::default(), &Registry::new())?;
+    record_and_flush(provider).await;
+
+    // InfluxDBではカウンターを累計値で書き込む
+    let contents = std::fs::read_to_string(&path)?;
+    assert!(contents.lines().any(|line| line.starts_with(r"test_requests,container_name=web\ 1 value=3.0 ")), "{}", contents);
+
+    Ok(())
+}
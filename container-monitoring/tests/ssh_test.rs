@@ -0,0 +1,110 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use container_monitoring::config::{DockerConfig, DockerSshConfig};
+use container_monitoring::docker::DockerClient;
+use container_monitoring::ssh::SshTunnel;
+
+// sshの代わりに実行するシェルスクリプトを作成（$sockに-Lで指定されたローカルソケットのパスが入る）
+fn fake_ssh(dir: &Path, body: &str) -> Result<PathBuf> {
+    let path = dir.join("ssh");
+    let script = format!(
+        "#!/bin/sh\nwhile [ $# -gt 0 ]; do\n  if [ \"$1\" = \"-L\" ]; then sock=\"${{2%%:*}}\"; fi\n  shift\ndone\n{}\n",
+        body
+    );
+    std::fs::write(&path, script)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(path)
+}
+
+fn ssh_config(command: &Path) -> DockerSshConfig {
+    DockerSshConfig {
+        command: command.to_string_lossy().into_owned(),
+        connect_timeout: 5,
+        ..DockerSshConfig::default()
+    }
+}
+
+#[test]
+fn test_ssh_command_line() -> Result<()> {
+    let config = DockerSshConfig {
+        identity_file: Some("/root/.ssh/id_ed25519".to_string()),
+        remote_socket_path: "/run/docker.sock".to_string(),
+        ..DockerSshConfig::default()
+    };
+
+    let command = SshTunnel::command("monitor@build-2:2222", Path::new("/tmp/tunnel.sock"), &config)?;
+    let args: Vec<String> = command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+
+    assert_eq!(command.get_program(), "ssh");
+    assert_eq!(
+        args,
+        vec![
+            "-nNT", "-o", "BatchMode=yes", "-o", "ExitOnForwardFailure=yes", "-o", "StreamLocalBindUnlink=yes",
+            "-L", "/tmp/tunnel.sock:/run/docker.sock",
+            "-p", "2222",
+            "-i", "/root/.ssh/id_ed25519",
+            "monitor@build-2",
+        ]
+    );
+
+    // ポートのない宛先はそのまま渡す
+    let command = SshTunnel::command("build-2", Path::new("/tmp/tunnel.sock"), &DockerSshConfig::default())?;
+    assert_eq!(command.get_args().last().and_then(|arg| arg.to_str()), Some("build-2"));
+
+    assert!(SshTunnel::command("", Path::new("/tmp/tunnel.sock"), &config).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_ssh_failure_reports_stderr() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let ssh = fake_ssh(dir.path(), "echo 'monitor@build-2: Permission denied (publickey).' >&2\nexit 255")?;
+
+    let error = SshTunnel::open("monitor@build-2", &ssh_config(&ssh)).err().expect("tunnel should fail");
+    let message = format!("{:#}", error);
+    assert!(message.contains("Permission denied (publickey)"), "{}", message);
+
+    Ok(())
+}
+
+#[test]
+fn test_chatty_ssh_does_not_stall_tunnel() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    // パイプの容量（64KiB）を超える出力の後にソケットを作成
+    let ssh = fake_ssh(
+        dir.path(),
+        "i=0\nwhile [ $i -lt 3000 ]; do echo \"debug1: client_input_channel_req: channel 0 rtype keepalive $i\" >&2; i=$((i+1)); done\ntouch \"$sock\"\nexec sleep 30",
+    )?;
+
+    let started = Instant::now();
+    let tunnel = SshTunnel::open("monitor@build-2", &ssh_config(&ssh))?;
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(tunnel.local_socket().exists());
+
+    // ドロップでsshを終了し、ローカルソケットを削除
+    let socket = tunnel.local_socket().to_path_buf();
+    drop(tunnel);
+    assert!(!socket.exists());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_docker_client_over_ssh_tunnel() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let ssh = fake_ssh(dir.path(), "touch \"$sock\"\nexec sleep 30")?;
+
+    let config = DockerConfig {
+        host: Some("ssh://monitor@build-2".to_string()),
+        ssh: ssh_config(&ssh),
+        ..DockerConfig::default()
+    };
+    let client = tokio::task::spawn_blocking(move || DockerClient::new(&config)).await?;
+    assert!(client.is_ok());
+
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use tempfile::tempdir;
+
+use container_monitoring::config::StateConfig;
+use container_monitoring::state::{AgentState, Baseline, HostState, InventoryEntry, StateFile, StateStore};
+
+// テスト用の状態
+fn sample_state() -> AgentState {
+    let mut baselines = BTreeMap::new();
+    baselines.insert("abc123/network_rx".to_string(), Baseline { started_at: Some(1_700_000_000), value: 4096 });
+    baselines.insert("abc123/8:0/read/bytes".to_string(), Baseline { started_at: None, value: 1 << 40 });
+
+    let mut state = AgentState::default();
+    state.hosts.insert("local".to_string(), HostState {
+        baselines,
+        inventory: vec![InventoryEntry {
+            id: "abc123".to_string(),
+            name: "web".to_string(),
+            image: "nginx:latest".to_string(),
+            status: "running".to_string(),
+            started_at: Some(1_700_000_000),
+        }],
+    });
+    state
+}
+
+#[test]
+fn test_save_and_load_state_file() -> Result<()> {
+    let dir = tempdir()?;
+    let file = StateFile::new(dir.path().join("nested").join("state.json"));
+
+    // ファイルがない場合はNone
+    assert!(file.load()?.is_none());
+
+    // 保存したディレクトリも作成され、一時ファイルは残らない
+    file.save(&sample_state())?;
+    assert_eq!(file.load()?, Some(sample_state()));
+    assert!(!dir.path().join("nested").join("state.json.tmp").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_detect_corrupt_state_file() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("state.json");
+    let file = StateFile::new(&path);
+    file.save(&sample_state())?;
+
+    // 値が書き換わった場合はチェックサムの不一致
+    let content = std::fs::read_to_string(&path)?;
+    std::fs::write(&path, content.replace("4096", "4097"))?;
+    let error = file.load().unwrap_err();
+    assert!(error.to_string().contains("Checksum mismatch"));
+
+    // 途中で切れた場合は解析エラー
+    std::fs::write(&path, &content[..content.len() / 2])?;
+    assert!(file.load().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_state_store_falls_back_to_clean_state() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("state.json");
+    std::fs::write(&path, "{\"version\":1")?;
+
+    // 破損したファイルは退避して空の状態から開始
+    let (store, restored) = StateStore::open(path.to_str().unwrap(), &StateConfig::default())?;
+    assert_eq!(restored, AgentState::default());
+    assert!(!path.exists());
+    assert!(dir.path().join("state.json.corrupt").exists());
+
+    // 収集サイクルごとに保存
+    let host_state = sample_state().hosts.remove("local").unwrap();
+    store.record_cycle("local", host_state)?;
+    assert_eq!(StateFile::new(&path).load()?, Some(sample_state()));
+
+    Ok(())
+}
+
+#[test]
+fn test_state_store_write_on_shutdown() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("state.json");
+    let config = StateConfig {
+        write: "shutdown".to_string(),
+        ..Default::default()
+    };
+
+    // 終了時のみの設定では収集サイクルごとに書き込まない
+    let (store, _) = StateStore::open(path.to_str().unwrap(), &config)?;
+    store.record_cycle("local", HostState::default())?;
+    assert!(!path.exists());
+
+    store.save()?;
+    let (_, restored) = StateStore::open(path.to_str().unwrap(), &config)?;
+    assert!(restored.hosts.contains_key("local"));
+
+    // 未知の書き込みタイミングはエラー
+    let config = StateConfig {
+        write: "hourly".to_string(),
+        ..Default::default()
+    };
+    assert!(StateStore::open(path.to_str().unwrap(), &config).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_state_store_keeps_hosts_that_did_not_save() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("state.json");
+
+    // 2つのホストの状態を保存
+    let mut state = sample_state();
+    state.hosts.insert("build-2".to_string(), state.hosts["local"].clone());
+    StateFile::new(&path).save(&state)?;
+
+    // 片方のホストのみが収集サイクルを終えても、もう片方の状態は失われない
+    let (store, restored) = StateStore::open(path.to_str().unwrap(), &StateConfig::default())?;
+    assert_eq!(restored, state);
+    store.record_cycle("local", HostState::default())?;
+
+    let saved = StateFile::new(&path).load()?.unwrap();
+    assert_eq!(saved.hosts["local"], HostState::default());
+    assert_eq!(saved.hosts["build-2"], state.hosts["build-2"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_concurrent_saves_keep_state_file_valid() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("state.json");
+    let (store, _) = StateStore::open(path.to_str().unwrap(), &StateConfig::default())?;
+    let store = std::sync::Arc::new(store);
+
+    // 複数のホストが同時に収集サイクルを終えても、書き込み途中のファイルが置かれない
+    let handles: Vec<_> = (0..8)
+        .map(|i| {
+            let store = store.clone();
+            std::thread::spawn(move || -> Result<()> {
+                let host_state = sample_state().hosts.remove("local").unwrap();
+                for _ in 0..20 {
+                    store.record_cycle(&format!("host-{}", i), host_state.clone())?;
+                }
+                Ok(())
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap()?;
+    }
+
+    // 最後の保存は全ホストの最新の状態を含む
+    let saved = StateFile::new(&path).load()?.unwrap();
+    assert_eq!(saved.hosts.len(), 8);
+    assert!(!dir.path().join("state.json.tmp").exists());
+
+    Ok(())
+}
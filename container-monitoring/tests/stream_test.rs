@@ -0,0 +1,76 @@
+use container_monitoring::docker::{ContainerEvent, ContainerInfo};
+use container_monitoring::stream::{StreamEvent, StreamFilter};
+
+// テスト用のコンテナ情報を作成
+fn container(name: &str, image: &str) -> ContainerInfo {
+    ContainerInfo {
+        id: format!("{}-id", name),
+        name: name.to_string(),
+        image: image.to_string(),
+        status: "running".to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_filter_cycle_containers() {
+    let filter = StreamFilter {
+        name: Some("web-*".to_string()),
+        ..Default::default()
+    };
+
+    let event = StreamEvent::Cycle {
+        host: "local".to_string(),
+        timestamp: 0,
+        containers: vec![container("web-1", "nginx"), container("db", "postgres")],
+    };
+
+    // 一致するコンテナのみが残る
+    match filter.apply(&event) {
+        Some(StreamEvent::Cycle { containers, .. }) => {
+            assert_eq!(containers.len(), 1);
+            assert_eq!(containers[0].name, "web-1");
+        }
+        other => panic!("unexpected event: {:?}", other),
+    }
+}
+
+#[test]
+fn test_filter_lifecycle_events() {
+    let filter = StreamFilter {
+        image: Some("nginx*".to_string()),
+        ..Default::default()
+    };
+
+    let event = |image: &str| StreamEvent::Lifecycle(ContainerEvent {
+        host: "local".to_string(),
+        timestamp: 0,
+        action: "start".to_string(),
+        container_id: "abc".to_string(),
+        container_name: "web".to_string(),
+        image: image.to_string(),
+    });
+
+    assert!(filter.apply(&event("nginx:1.25")).is_some());
+    assert!(filter.apply(&event("redis:7")).is_none());
+
+    // フィルタなしではすべてのイベントを配信
+    assert!(StreamFilter::default().apply(&event("redis:7")).is_some());
+}
+
+#[test]
+fn test_filter_by_host() {
+    let filter = StreamFilter {
+        host: Some("edge-1".to_string()),
+        ..Default::default()
+    };
+
+    let event = |host: &str| StreamEvent::Cycle {
+        host: host.to_string(),
+        timestamp: 0,
+        containers: vec![container("web-1", "nginx")],
+    };
+
+    assert!(filter.apply(&event("edge-1")).is_some());
+    assert!(filter.apply(&event("edge-2")).is_none());
+}
@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use flate2::read::GzDecoder;
+use opentelemetry::metrics::MeterProvider as _;
+use opentelemetry::trace::{
+    SamplingDecision, SpanContext, SpanId, SpanKind, TraceContextExt, TraceFlags, TraceId, TraceState,
+};
+use opentelemetry::Context;
+use opentelemetry_proto::tonic::collector::metrics::v1::metrics_service_server::{MetricsService, MetricsServiceServer};
+use opentelemetry_proto::tonic::collector::metrics::v1::{ExportMetricsServiceRequest, ExportMetricsServiceResponse};
+use opentelemetry_proto::tonic::metrics::v1::{metric, AggregationTemporality};
+use opentelemetry_sdk::metrics::MeterProvider;
+use opentelemetry_sdk::trace::ShouldSample;
+use opentelemetry_sdk::Resource;
+use prometheus::Registry;
+use prost::Message;
+use tokio_stream::wrappers::TcpListenerStream;
+use warp::Filter;
+
+use container_monitoring::config::{OtlpConfig, TelemetryConfig};
+use container_monitoring::telemetry::{build_meter_provider, build_sampler, build_tracer_provider};
+
+// スタブが受信したリクエスト（ヘッダーとデコード済みの本文）
+type Received = Arc<Mutex<Vec<(HashMap<String, String>, ExportMetricsServiceRequest)>>>;
+
+// テスト用のテレメトリー設定を作成
+fn telemetry_config(endpoint: String, otlp: OtlpConfig) -> TelemetryConfig {
+    TelemetryConfig {
+        service_name: "test-service".to_string(),
+        otel_exporter: "otlp".to_string(),
+        otel_endpoint: endpoint,
+        prometheus_port: 0,
+        otlp,
+        resource_detectors: Vec::new(),
+        ..Default::default()
+    }
+}
+
+// OTLP/HTTPの/v1/metricsを受け付けるスタブを起動
+async fn spawn_http_receiver(received: Received) -> SocketAddr {
+    let route = warp::post()
+        .and(warp::path!("v1" / "metrics"))
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .map(move |headers: warp::http::HeaderMap, body: warp::hyper::body::Bytes| {
+            let headers: HashMap<String, String> = headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_str().unwrap_or_default().to_string()))
+                .collect();
+
+            let mut body = body.to_vec();
+            if headers.get("content-encoding").map(String::as_str) == Some("gzip") {
+                let mut decoded = Vec::new();
+                GzDecoder::new(body.as_slice()).read_to_end(&mut decoded).unwrap();
+                body = decoded;
+            }
+
+            let request = ExportMetricsServiceRequest::decode(body.as_slice()).unwrap();
+            received.lock().unwrap().push((headers, request));
+            warp::reply()
+        });
+
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr
+}
+
+// OTLP/gRPCのMetricsServiceスタブ
+struct GrpcReceiver {
+    received: Received,
+}
+
+#[tonic::async_trait]
+impl MetricsService for GrpcReceiver {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportMetricsServiceRequest>,
+    ) -> Result<tonic::Response<ExportMetricsServiceResponse>, tonic::Status> {
+        let headers = request
+            .metadata()
+            .clone()
+            .into_headers()
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_str().unwrap_or_default().to_string()))
+            .collect();
+
+        self.received.lock().unwrap().push((headers, request.into_inner()));
+        Ok(tonic::Response::new(ExportMetricsServiceResponse { partial_success: None }))
+    }
+}
+
+// OTLP/gRPCのスタブを起動
+async fn spawn_grpc_receiver(received: Received) -> Result<SocketAddr> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(MetricsServiceServer::new(GrpcReceiver { received }))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    Ok(addr)
+}
+
+// カウンターを記録してエクスポートを強制（PeriodicReaderのflushはブロッキング）
+async fn record_and_flush(provider: MeterProvider) {
+    let counter = provider.meter("telemetry-test").u64_counter("test_requests_total").init();
+    counter.add(3, &[]);
+
+    tokio::task::spawn_blocking(move || provider.force_flush().unwrap())
+        .await
+        .unwrap();
+}
+
+// 受信したリクエストからカウンターの集計方法を取得
+fn counter_temporality(request: &ExportMetricsServiceRequest) -> Option<i32> {
+    request
+        .resource_metrics
+        .iter()
+        .flat_map(|resource| &resource.scope_metrics)
+        .flat_map(|scope| &scope.metrics)
+        .find(|metric| metric.name == "test_requests_total")
+        .and_then(|metric| match &metric.data {
+            Some(metric::Data::Sum(sum)) => Some(sum.aggregation_temporality),
+            _ => None,
+        })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_http_protobuf_export_with_headers_gzip_and_delta() -> Result<()> {
+    let received = Received::default();
+    let addr = spawn_http_receiver(received.clone()).await;
+
+    let otlp = OtlpConfig {
+        protocol: "http/protobuf".to_string(),
+        headers: HashMap::from([("x-api-key".to_string(), "secret".to_string())]),
+        compression: Some("gzip".to_string()),
+        temporality: "delta".to_string(),
+        ..Default::default()
+    };
+    let config = telemetry_config(format!("http://{}", addr), otlp);
+    let provider = build_meter_provider(&config, Resource::default(), &Registry::new())?;
+    record_and_flush(provider).await;
+
+    let received = received.lock().unwrap();
+    assert!(!received.is_empty());
+    let (headers, request) = &received[0];
+    assert_eq!(headers.get("x-api-key").map(String::as_str), Some("secret"));
+    assert_eq!(headers.get("content-encoding").map(String::as_str), Some("gzip"));
+    assert_eq!(headers.get("content-type").map(String::as_str), Some("application/x-protobuf"));
+    assert_eq!(counter_temporality(request), Some(AggregationTemporality::Delta as i32));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_grpc_export_with_metadata_and_cumulative() -> Result<()> {
+    let received = Received::default();
+    let addr = spawn_grpc_receiver(received.clone()).await?;
+
+    let otlp = OtlpConfig {
+        headers: HashMap::from([("Authorization".to_string(), "Bearer token".to_string())]),
+        ..Default::default()
+    };
+    let config = telemetry_config(format!("http://{}", addr), otlp);
+    let provider = build_meter_provider(&config, Resource::default(), &Registry::new())?;
+    record_and_flush(provider).await;
+
+    let received = received.lock().unwrap();
+    assert!(!received.is_empty());
+    let (headers, request) = &received[0];
+    assert_eq!(headers.get("authorization").map(String::as_str), Some("Bearer token"));
+    assert_eq!(counter_temporality(request), Some(AggregationTemporality::Cumulative as i32));
+
+    Ok(())
+}
+
+#[test]
+fn test_exporter_settings_are_validated() {
+    let endpoint = "http://localhost:4317".to_string();
+
+    // noneとプロトコルnoneはPrometheusのみで動作する
+    let mut config = telemetry_config(endpoint.clone(), OtlpConfig::default());
+    config.otel_exporter = "none".to_string();
+    assert!(build_meter_provider(&config, Resource::default(), &Registry::new()).is_ok());
+    assert!(build_tracer_provider(&config, Resource::default()).unwrap().is_none());
+
+    let config = telemetry_config(
+        endpoint.clone(),
+        OtlpConfig {
+            protocol: "none".to_string(),
+            ..Default::default()
+        },
+    );
+    assert!(build_meter_provider(&config, Resource::default(), &Registry::new()).is_ok());
+    assert!(build_tracer_provider(&config, Resource::default()).unwrap().is_none());
+
+    // fileはfile_pathが必要
+    let mut config = telemetry_config(endpoint.clone(), OtlpConfig::default());
+    config.otel_exporter = "file".to_string();
+    assert!(build_meter_provider(&config, Resource::default(), &Registry::new()).is_err());
+
+    // 未対応のエクスポーターはエラー
+    config.otel_exporter = "jaeger".to_string();
+    assert!(build_meter_provider(&config, Resource::default(), &Registry::new()).is_err());
+
+    // 未対応の値はエラー
+    for otlp in [
+        OtlpConfig {
+            protocol: "http/json".to_string(),
+            ..Default::default()
+        },
+        OtlpConfig {
+            compression: Some("zstd".to_string()),
+            ..Default::default()
+        },
+        OtlpConfig {
+            temporality: "lowmemory".to_string(),
+            ..Default::default()
+        },
+    ] {
+        let config = telemetry_config(endpoint.clone(), otlp);
+        assert!(build_meter_provider(&config, Resource::default(), &Registry::new()).is_err());
+    }
+}
+
+#[test]
+fn test_prometheus_only_mode_serves_metrics_from_registry() -> Result<()> {
+    let mut config = telemetry_config("http://127.0.0.1:1".to_string(), OtlpConfig::default());
+    config.otel_exporter = "none".to_string();
+
+    let registry = Registry::new();
+    let provider = build_meter_provider(&config, Resource::default(), &registry)?;
+    let counter = provider.meter("telemetry-test").u64_counter("test_requests_total").init();
+    counter.add(3, &[]);
+
+    // OTLPへ送信せずにスクレイプ時にレジストリから取得できる
+    let families = registry.gather();
+    assert!(families.iter().any(|family| family.get_name().starts_with("test_requests")));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_file_exporter_writes_json_records() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("telemetry.jsonl");
+
+    let mut config = telemetry_config(String::new(), OtlpConfig::default());
+    config.otel_exporter = "file".to_string();
+    config.file_path = Some(path.to_string_lossy().into_owned());
+
+    let provider = build_meter_provider(&config, Resource::default(), &Registry::new())?;
+    record_and_flush(provider).await;
+
+    // 1行に1レコードのJSONとして書き込まれる
+    let contents = std::fs::read_to_string(&path)?;
+    let records: Vec<serde_json::Value> = contents
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert!(!records.is_empty());
+    assert!(contents.contains("test_requests_total"));
+
+    Ok(())
+}
+
+// サンプラーの判定結果を取得
+fn sampling_decision(sampler: &dyn ShouldSample, parent: Option<&Context>) -> SamplingDecision {
+    sampler
+        .should_sample(parent, TraceId::from_bytes(42u128.to_be_bytes()), "collect_metrics", &SpanKind::Internal, &[], &[])
+        .decision
+}
+
+#[test]
+fn test_build_sampler() -> Result<()> {
+    let mut config = telemetry_config("http://localhost:4317".to_string(), OtlpConfig::default());
+    let sampled_parent = Context::new().with_remote_span_context(SpanContext::new(
+        TraceId::from_bytes(42u128.to_be_bytes()),
+        SpanId::from_bytes(7u64.to_be_bytes()),
+        TraceFlags::SAMPLED,
+        true,
+        TraceState::default(),
+    ));
+
+    // 既定はparentbased_always_on
+    let sampler = build_sampler(&config)?;
+    assert_eq!(sampling_decision(&sampler, None), SamplingDecision::RecordAndSample);
+
+    // 割合0のtraceidratioは新しいトレースを記録しない
+    config.sampler = "traceidratio".to_string();
+    config.sampling_ratio = 0.0;
+    let sampler = build_sampler(&config)?;
+    assert_eq!(sampling_decision(&sampler, None), SamplingDecision::Drop);
+    assert_eq!(sampling_decision(&sampler, Some(&sampled_parent)), SamplingDecision::Drop);
+
+    // parentbasedでは記録された親スパンに従う
+    config.sampler = "parentbased_traceidratio".to_string();
+    let sampler = build_sampler(&config)?;
+    assert_eq!(sampling_decision(&sampler, None), SamplingDecision::Drop);
+    assert_eq!(sampling_decision(&sampler, Some(&sampled_parent)), SamplingDecision::RecordAndSample);
+
+    config.sampling_ratio = 1.5;
+    assert!(build_sampler(&config).is_err());
+    config.sampling_ratio = 1.0;
+    config.sampler = "probabilistic".to_string();
+    assert!(build_sampler(&config).is_err());
+
+    Ok(())
+}